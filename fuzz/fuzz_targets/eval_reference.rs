@@ -0,0 +1,184 @@
+#![no_main]
+
+mod reference;
+
+use chip8::eval::EvalError;
+use chip8::types::{Button, Register, State};
+use libfuzzer_sys::fuzz_target;
+use reference::{Fault, Machine};
+
+const MAX_STEPS: usize = 256;
+
+const REGISTERS: [Register; 16] = {
+    use Register::*;
+
+    [
+        V0, V1, V2, V3, V4, V5, V6, V7, V8, V9, VA, VB, VC, VD, VE, VF,
+    ]
+};
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    Ok,
+    UnknownOpcode,
+    StackUnderflow,
+    MachineCode,
+}
+
+impl From<Result<(), EvalError>> for Outcome {
+    fn from(result: Result<(), EvalError>) -> Self {
+        match result {
+            Ok(()) => Outcome::Ok,
+            Err(EvalError::Decode(_)) => Outcome::UnknownOpcode,
+            Err(EvalError::StackUnderflow) => Outcome::StackUnderflow,
+            Err(EvalError::RcaCall(_)) => Outcome::MachineCode,
+        }
+    }
+}
+
+impl From<Result<(), Fault>> for Outcome {
+    fn from(result: Result<(), Fault>) -> Self {
+        match result {
+            Ok(()) => Outcome::Ok,
+            Err(Fault::UnknownOpcode(_)) => Outcome::UnknownOpcode,
+            Err(Fault::StackUnderflow) => Outcome::StackUnderflow,
+            Err(Fault::MachineCode(_)) => Outcome::MachineCode,
+        }
+    }
+}
+
+/// Lists every field on which the emulator and the reference disagree.
+fn diff(state: &State, machine: &Machine) -> Vec<String> {
+    let mut diffs = Vec::new();
+
+    for (i, reg) in REGISTERS.iter().enumerate() {
+        if state.registers[*reg] != machine.v[i] {
+            diffs.push(format!(
+                "{:?}: {:#04X} != {:#04X}",
+                reg, state.registers[*reg], machine.v[i]
+            ));
+        }
+    }
+
+    if state.i_reg.0 != machine.i {
+        diffs.push(format!("I: {:#05X} != {:#05X}", state.i_reg.0, machine.i));
+    }
+    if state.pc.0 != machine.pc {
+        diffs.push(format!("PC: {:#05X} != {:#05X}", state.pc.0, machine.pc));
+    }
+
+    let stack: Vec<u16> = state.call_stack.iter().map(|addr| addr.0).collect();
+    if stack != machine.stack {
+        diffs.push(format!("stack: {:X?} != {:X?}", stack, machine.stack));
+    }
+
+    if state.timer != machine.delay {
+        diffs.push(format!("delay: {} != {}", state.timer, machine.delay));
+    }
+    if state.sound_timer != machine.sound {
+        diffs.push(format!("sound: {} != {}", state.sound_timer, machine.sound));
+    }
+
+    for (addr, (a, b)) in state.memory.iter().zip(machine.memory.iter()).enumerate() {
+        if a != b {
+            diffs.push(format!("memory[{:#05X}]: {:#04X} != {:#04X}", addr, a, b));
+        }
+    }
+
+    for idx in 0..64 * 32 {
+        let lit = state.bit_gfx[idx / 8] & (0x80 >> (idx % 8)) != 0;
+        if lit != machine.display[idx / 64][idx % 64] {
+            diffs.push(format!(
+                "pixel ({}, {}): {} != {}",
+                idx % 64,
+                idx / 64,
+                lit,
+                !lit
+            ));
+        }
+    }
+
+    diffs
+}
+
+// Input layout: V0-VF, I (big endian), pressed buttons (bitmask), delay and
+// sound timers, then the program loaded at 0x200.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 22 {
+        return;
+    }
+
+    let (header, program) = data.split_at(22);
+    let program = &program[..program.len().min(4096 - 0x200)];
+
+    let mut state = State::default();
+    let mut machine = Machine::new();
+
+    for (i, reg) in REGISTERS.iter().enumerate() {
+        state.registers[*reg] = header[i];
+        machine.v[i] = header[i];
+    }
+
+    state.i_reg = u16::from_be_bytes([header[16], header[17]]).into();
+    machine.i = state.i_reg.0;
+
+    let buttons = u16::from_be_bytes([header[18], header[19]]);
+    for i in 0..16 {
+        let pressed = buttons & (1 << i) != 0;
+        state.buttons[Button::n(i as u8).unwrap()] = pressed;
+        machine.keys[i] = pressed;
+    }
+
+    state.timer = header[20];
+    state.sound_timer = header[21];
+    machine.delay = header[20];
+    machine.sound = header[21];
+
+    state.memory[0x200..0x200 + program.len()].copy_from_slice(program);
+    machine.memory[0x200..0x200 + program.len()].copy_from_slice(program);
+
+    for step in 0..MAX_STEPS {
+        let pc = machine.pc;
+        let opcode = machine.opcode();
+        let before = machine.clone();
+
+        let decoded = state.fetch();
+        let ours = Outcome::from(decoded.and_then(|instr| instr.eval(&mut state)));
+
+        let random = if opcode >> 12 == 0xC {
+            state.registers[REGISTERS[usize::from(opcode >> 8 & 0xF)]]
+        } else {
+            0
+        };
+        let theirs = Outcome::from(machine.step(random));
+
+        if ours != theirs {
+            panic!(
+                "step {}: {:04X} at {:#05X} ({:?}) returned {:?}, reference returned {:?}",
+                step, opcode, pc, decoded, ours, theirs
+            );
+        }
+
+        if ours != Outcome::Ok {
+            break;
+        }
+
+        let diffs = diff(&state, &machine);
+        if !diffs.is_empty() {
+            panic!(
+                "step {}: {:04X} at {:#05X} ({:?}) diverged (emulator != reference)\n\
+                 registers before: {:02X?}, I = {:#05X}\n{}",
+                step,
+                opcode,
+                pc,
+                decoded,
+                before.v,
+                before.i,
+                diffs.join("\n")
+            );
+        }
+
+        state.tick_timers();
+        machine.tick_timers();
+    }
+});
@@ -0,0 +1,198 @@
+//! A deliberately naive CHIP-8 interpreter written straight from Cowgod's
+//! technical reference, sharing no code with the emulator. It decodes raw
+//! opcodes by nibble instead of going through `chip8::parser`.
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Fault {
+    UnknownOpcode(u16),
+    StackUnderflow,
+    MachineCode(u16),
+}
+
+#[derive(Clone)]
+pub struct Machine {
+    pub memory: [u8; 4096],
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub stack: Vec<u16>,
+    pub delay: u8,
+    pub sound: u8,
+    pub display: [[bool; 64]; 32],
+    pub keys: [bool; 16],
+}
+
+impl Machine {
+    pub fn new() -> Self {
+        Machine {
+            memory: [0; 4096],
+            v: [0; 16],
+            i: 0,
+            pc: 0x200,
+            stack: Vec::new(),
+            delay: 0,
+            sound: 0,
+            display: [[false; 64]; 32],
+            keys: [false; 16],
+        }
+    }
+
+    pub fn opcode(&self) -> u16 {
+        let hi = self.memory[(self.pc & 0xFFF) as usize];
+        let lo = self.memory[((self.pc + 1) & 0xFFF) as usize];
+        (hi as u16) << 8 | lo as u16
+    }
+
+    fn byte(&self, offset: u16) -> u8 {
+        self.memory[(self.i.wrapping_add(offset) & 0xFFF) as usize]
+    }
+
+    fn byte_mut(&mut self, offset: u16) -> &mut u8 {
+        &mut self.memory[(self.i.wrapping_add(offset) & 0xFFF) as usize]
+    }
+
+    /// Executes one instruction. `random` is the byte CXNN masks, supplied by
+    /// the caller so both implementations can agree on it.
+    pub fn step(&mut self, random: u8) -> Result<(), Fault> {
+        let op = self.opcode();
+        let x = ((op >> 8) & 0xF) as usize;
+        let y = ((op >> 4) & 0xF) as usize;
+        let n = op & 0xF;
+        let nn = (op & 0xFF) as u8;
+        let nnn = op & 0xFFF;
+
+        self.pc = (self.pc + 2) & 0xFFF;
+
+        match op >> 12 {
+            0x0 if op == 0x00E0 => self.display = [[false; 64]; 32],
+            0x0 if op == 0x00EE => self.pc = self.stack.pop().ok_or(Fault::StackUnderflow)?,
+            0x0 => return Err(Fault::MachineCode(nnn)),
+            0x1 => self.pc = nnn,
+            0x2 => {
+                self.stack.push(self.pc);
+                self.pc = nnn;
+            }
+            0x3 => {
+                if self.v[x] == nn {
+                    self.pc = (self.pc + 2) & 0xFFF;
+                }
+            }
+            0x4 => {
+                if self.v[x] != nn {
+                    self.pc = (self.pc + 2) & 0xFFF;
+                }
+            }
+            0x5 if n == 0 => {
+                if self.v[x] == self.v[y] {
+                    self.pc = (self.pc + 2) & 0xFFF;
+                }
+            }
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => match n {
+                0x0 => self.v[x] = self.v[y],
+                0x1 => self.v[x] |= self.v[y],
+                0x2 => self.v[x] &= self.v[y],
+                0x3 => self.v[x] ^= self.v[y],
+                0x4 => {
+                    let sum = self.v[x] as u16 + self.v[y] as u16;
+                    self.v[x] = sum as u8;
+                    self.v[0xF] = (sum > 0xFF) as u8;
+                }
+                0x5 => {
+                    let not_borrow = self.v[x] >= self.v[y];
+                    self.v[x] = self.v[x].wrapping_sub(self.v[y]);
+                    self.v[0xF] = not_borrow as u8;
+                }
+                0x6 => {
+                    let lsb = self.v[x] & 1;
+                    self.v[x] >>= 1;
+                    self.v[0xF] = lsb;
+                }
+                0x7 => {
+                    let not_borrow = self.v[y] >= self.v[x];
+                    self.v[x] = self.v[y].wrapping_sub(self.v[x]);
+                    self.v[0xF] = not_borrow as u8;
+                }
+                0xE => {
+                    let msb = self.v[x] >> 7;
+                    self.v[x] <<= 1;
+                    self.v[0xF] = msb;
+                }
+                _ => return Err(Fault::UnknownOpcode(op)),
+            },
+            0x9 if n == 0 => {
+                if self.v[x] != self.v[y] {
+                    self.pc = (self.pc + 2) & 0xFFF;
+                }
+            }
+            0xA => self.i = nnn,
+            0xB => self.pc = (nnn + self.v[0] as u16) & 0xFFF,
+            0xC => self.v[x] = random & nn,
+            0xD => {
+                let mut collision = false;
+
+                for row in 0..n {
+                    let sprite = self.byte(row);
+                    let py = (self.v[y] as usize + row as usize) % 32;
+
+                    for col in 0..8 {
+                        if sprite & (0x80 >> col) != 0 {
+                            let px = (self.v[x] as usize + col) % 64;
+                            collision |= self.display[py][px];
+                            self.display[py][px] ^= true;
+                        }
+                    }
+                }
+
+                self.v[0xF] = collision as u8;
+            }
+            0xE if nn == 0x9E => {
+                if self.keys[(self.v[x] & 0xF) as usize] {
+                    self.pc = (self.pc + 2) & 0xFFF;
+                }
+            }
+            0xE if nn == 0xA1 => {
+                if !self.keys[(self.v[x] & 0xF) as usize] {
+                    self.pc = (self.pc + 2) & 0xFFF;
+                }
+            }
+            0xF => match nn {
+                0x07 => self.v[x] = self.delay,
+                0x0A => match self.keys.iter().position(|&k| k) {
+                    Some(key) => self.v[x] = key as u8,
+                    None => self.pc = self.pc.wrapping_sub(2) & 0xFFF,
+                },
+                0x15 => self.delay = self.v[x],
+                0x18 => self.sound = self.v[x],
+                0x1E => self.i = self.i.wrapping_add(self.v[x] as u16),
+                0x29 => self.i = (self.v[x] & 0xF) as u16 * 5,
+                0x33 => {
+                    let value = self.v[x];
+                    *self.byte_mut(0) = value / 100;
+                    *self.byte_mut(1) = value / 10 % 10;
+                    *self.byte_mut(2) = value % 10;
+                }
+                0x55 => {
+                    for r in 0..=x {
+                        *self.byte_mut(r as u16) = self.v[r];
+                    }
+                }
+                0x65 => {
+                    for r in 0..=x {
+                        self.v[r] = self.byte(r as u16);
+                    }
+                }
+                _ => return Err(Fault::UnknownOpcode(op)),
+            },
+            _ => return Err(Fault::UnknownOpcode(op)),
+        }
+
+        Ok(())
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.delay = self.delay.saturating_sub(1);
+        self.sound = self.sound.saturating_sub(1);
+    }
+}
@@ -0,0 +1,41 @@
+#![no_main]
+
+use chip8::types::{Button, State};
+use libfuzzer_sys::fuzz_target;
+
+const STEPS_PER_FRAME: usize = 16;
+
+// Input layout: a frame count, one big-endian button bitmask per frame, then
+// the ROM loaded at 0x200. Errors end the run; only panics are failures.
+fuzz_target!(|data: &[u8]| {
+    let (&frames, rest) = match data.split_first() {
+        Some(split) => split,
+        None => return,
+    };
+
+    let inputs_len = (usize::from(frames) * 2).min(rest.len());
+    let (inputs, rom) = rest.split_at(inputs_len);
+    let rom = &rom[..rom.len().min(4096 - 0x200)];
+
+    let mut state = State::default();
+    state.memory[0x200..0x200 + rom.len()].copy_from_slice(rom);
+
+    for input in inputs.chunks(2) {
+        let mask = match *input {
+            [hi, lo] => u16::from_be_bytes([hi, lo]),
+            _ => 0,
+        };
+
+        for i in 0..16 {
+            state.buttons[Button::n(i).unwrap()] = mask & (1 << i) != 0;
+        }
+
+        for _ in 0..STEPS_PER_FRAME {
+            if state.step().is_err() {
+                return;
+            }
+        }
+
+        state.tick_timers();
+    }
+});
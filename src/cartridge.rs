@@ -0,0 +1,249 @@
+//! Octo cartridges: GIFs with a game's source and options hidden in their
+//! pixels.
+//!
+//! The frames' palette indices carry two bits of payload each in their low
+//! bits, four pixels to a byte, most significant bits first. The payload is a
+//! big-endian 32-bit length followed by that many bytes of UTF-8 JSON holding
+//! `program` (Octo source) and `options`.
+
+use crate::octo::{self, Program};
+use crate::types::Quirks;
+use serde_json::{json, Map, Value};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::path::Path;
+
+const FRAME_WIDTH: u16 = 128;
+const FRAME_HEIGHT: u16 = 64;
+
+/// The settings Octo saves alongside a program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    /// Instructions per 60 Hz frame.
+    pub tickrate: Option<u32>,
+    pub quirks: Quirks,
+    pub background_color: Option<u32>,
+    pub fill_color: Option<u32>,
+    pub fill_color2: Option<u32>,
+    pub blend_color: Option<u32>,
+    pub buzz_color: Option<u32>,
+    pub quiet_color: Option<u32>,
+}
+
+impl Default for Options {
+    /// Octo's defaults, which turn every quirk off.
+    fn default() -> Self {
+        Options {
+            tickrate: None,
+            quirks: Quirks {
+                shift: false,
+                load_store: false,
+                vf_order: false,
+                clip: false,
+                jump: false,
+                logic: false,
+                vblank: false,
+            },
+            background_color: None,
+            fill_color: None,
+            fill_color2: None,
+            blend_color: None,
+            buzz_color: None,
+            quiet_color: None,
+        }
+    }
+}
+
+/// Octo's names for each quirk.
+fn quirks_mut(quirks: &mut Quirks) -> [(&'static str, &mut bool); 7] {
+    [
+        ("shiftQuirks", &mut quirks.shift),
+        ("loadStoreQuirks", &mut quirks.load_store),
+        ("vfOrderQuirks", &mut quirks.vf_order),
+        ("clipQuirks", &mut quirks.clip),
+        ("jumpQuirks", &mut quirks.jump),
+        ("logicQuirks", &mut quirks.logic),
+        ("vBlankQuirks", &mut quirks.vblank),
+    ]
+}
+
+impl Options {
+    /// Octo's names for each color.
+    fn colors_mut(&mut self) -> [(&'static str, &mut Option<u32>); 6] {
+        [
+            ("backgroundColor", &mut self.background_color),
+            ("fillColor", &mut self.fill_color),
+            ("fillColor2", &mut self.fill_color2),
+            ("blendColor", &mut self.blend_color),
+            ("buzzColor", &mut self.buzz_color),
+            ("quietColor", &mut self.quiet_color),
+        ]
+    }
+
+    fn from_json(json: &Map<String, Value>) -> Result<Self, String> {
+        let mut options = Options::default();
+
+        if let Some(tickrate) = json.get("tickrate") {
+            options.tickrate = Some(parse_tickrate(tickrate)?);
+        }
+
+        for (name, quirk) in quirks_mut(&mut options.quirks).iter_mut() {
+            if let Some(value) = json.get(*name) {
+                **quirk = value
+                    .as_bool()
+                    .ok_or_else(|| format!("invalid {} {}", name, value))?;
+            }
+        }
+
+        for (name, color) in options.colors_mut().iter_mut() {
+            if let Some(value) = json.get(*name) {
+                **color = Some(
+                    value
+                        .as_str()
+                        .and_then(parse_color)
+                        .ok_or_else(|| format!("invalid {} {}", name, value))?,
+                );
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn to_json(&self) -> Value {
+        let mut json = Map::new();
+
+        if let Some(tickrate) = self.tickrate {
+            json.insert("tickrate".into(), tickrate.into());
+        }
+
+        let mut options = self.clone();
+
+        for (name, quirk) in quirks_mut(&mut options.quirks).iter() {
+            json.insert(name.to_string(), (**quirk).into());
+        }
+
+        for (name, color) in options.colors_mut().iter() {
+            if let Some(color) = color {
+                json.insert(name.to_string(), format!("#{:06X}", color).into());
+            }
+        }
+
+        Value::Object(json)
+    }
+}
+
+/// Parses a tickrate, which like `--speed` must be at least 1.
+pub fn parse_tickrate(value: &Value) -> Result<u32, String> {
+    match value.as_u64().map(u32::try_from) {
+        Some(Ok(0)) => Err("tickrate must be at least 1".into()),
+        Some(Ok(tickrate)) => Ok(tickrate),
+        _ => Err(format!("invalid tickrate {}", value)),
+    }
+}
+
+/// Parses a `#RRGGBB` color into 0RGB.
+pub fn parse_color(s: &str) -> Option<u32> {
+    let hex = s.trim_start_matches('#');
+    if hex.len() != 6 {
+        return None;
+    }
+
+    u32::from_str_radix(hex, 16).ok()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cartridge {
+    /// The program's Octo source.
+    pub source: String,
+    pub options: Options,
+}
+
+impl Cartridge {
+    pub fn read<R: Read>(r: R) -> Result<Self, String> {
+        let mut reader = gif::Decoder::new(r)
+            .read_info()
+            .map_err(|err| err.to_string())?;
+
+        let mut payload = Vec::new();
+        let mut bits = 0;
+        let mut byte = 0u8;
+
+        while let Some(frame) = reader.read_next_frame().map_err(|err| err.to_string())? {
+            for pixel in frame.buffer.iter() {
+                byte = byte << 2 | pixel & 3;
+                bits += 2;
+
+                if bits == 8 {
+                    payload.push(byte);
+                    bits = 0;
+                }
+            }
+        }
+
+        if payload.len() < 4 {
+            return Err("not an Octo cartridge".into());
+        }
+
+        let len = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+        let json = payload
+            .get(4..4 + len)
+            .ok_or_else(|| "not an Octo cartridge".to_string())?;
+
+        let json: Value = serde_json::from_slice(json).map_err(|err| err.to_string())?;
+
+        let source = json
+            .get("program")
+            .and_then(Value::as_str)
+            .ok_or_else(|| "cartridge has no program".to_string())?;
+
+        let options = match json.get("options") {
+            Some(Value::Object(options)) => Options::from_json(options)?,
+            Some(_) => return Err("invalid cartridge options".into()),
+            None => Options::default(),
+        };
+
+        Ok(Cartridge {
+            source: source.into(),
+            options,
+        })
+    }
+
+    /// Writes the cartridge as a GIF of plain noise, without Octo's label art.
+    pub fn write<W: Write>(&self, w: W) -> Result<(), String> {
+        let json = json!({
+            "program": self.source,
+            "options": self.options.to_json(),
+        })
+        .to_string();
+
+        let mut payload = (json.len() as u32).to_be_bytes().to_vec();
+        payload.extend_from_slice(json.as_bytes());
+
+        let pixels: Vec<u8> = payload
+            .iter()
+            .flat_map(|byte| (0..4).rev().map(move |i| byte >> (2 * i) & 3))
+            .collect();
+
+        let palette = [
+            0x00, 0x00, 0x00, 0x55, 0x55, 0x55, 0xAA, 0xAA, 0xAA, 0xFF, 0xFF, 0xFF,
+        ];
+        let mut encoder = gif::Encoder::new(w, FRAME_WIDTH, FRAME_HEIGHT, &palette)
+            .map_err(|err| err.to_string())?;
+
+        let size = usize::from(FRAME_WIDTH) * usize::from(FRAME_HEIGHT);
+        for chunk in pixels.chunks(size) {
+            let mut frame = chunk.to_vec();
+            frame.resize(size, 0);
+
+            let frame = gif::Frame::from_indexed_pixels(FRAME_WIDTH, FRAME_HEIGHT, &frame, None);
+            encoder.write_frame(&frame).map_err(|err| err.to_string())?;
+        }
+
+        Ok(())
+    }
+
+    /// Assembles the cartridge's source, naming `file` in the line map.
+    pub fn assemble(&self, file: impl AsRef<Path>) -> Result<Program, String> {
+        octo::assemble(&self.source, file)
+    }
+}
@@ -0,0 +1,679 @@
+//! Static control-flow analysis. Starting at the load address, follows jumps,
+//! calls, returns and skips to split the ROM into basic blocks and
+//! subroutines. `JP V0` targets come from tracking the values V0 can hold.
+
+use crate::symbols::{Symbolic, Symbols};
+use crate::types::*;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// How many values V0 may hold before it's treated as unknown.
+const MAX_VALUES: usize = 32;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum EdgeKind {
+    Fallthrough,
+    Jump,
+    /// The taken side of a skip.
+    Skip,
+    /// A target of `JP V0`.
+    Indexed,
+    Call,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    pub start: Address,
+    pub instructions: Vec<(Address, Instruction)>,
+    /// Edges out of the block, including calls made from its middle.
+    pub successors: Vec<(Address, EdgeKind)>,
+}
+
+impl Block {
+    /// The address just past the last instruction.
+    pub fn end(&self) -> Address {
+        self.start.wrapping_add(2 * self.instructions.len() as u16)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Subroutine {
+    pub entry: Address,
+    /// Starts of the blocks reachable from `entry` without following calls.
+    pub blocks: BTreeSet<Address>,
+    pub calls: BTreeSet<Address>,
+    pub returns: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// ROM bytes that no path reaches and no `LD I` points at.
+    Unreachable(Range<u16>),
+    InvalidInstruction(Address),
+    /// A `JP V0` where V0 couldn't be narrowed down.
+    UnresolvedJump(Address),
+    /// A jump from `from` landing on the second byte of an instruction.
+    MisalignedJump {
+        from: Address,
+        to: Address,
+    },
+    /// A `RET` reachable from the entry point, with nothing to return to.
+    ReturnWithoutCall(Address),
+    /// A subroutine with no reachable `RET`, which leaks a stack entry for
+    /// every call.
+    NoReturn(Address),
+    Recursion(Address),
+}
+
+/// The values V0 may hold at an instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Values {
+    Known(BTreeSet<u8>),
+    Unknown,
+}
+
+impl Values {
+    fn exact(n: u8) -> Self {
+        Values::Known(std::iter::once(n).collect())
+    }
+
+    fn from_iter(values: impl Iterator<Item = u8>) -> Self {
+        let values: BTreeSet<u8> = values.collect();
+
+        if values.len() > MAX_VALUES {
+            Values::Unknown
+        } else {
+            Values::Known(values)
+        }
+    }
+
+    fn map(&self, f: impl Fn(u8) -> u8) -> Self {
+        match self {
+            Values::Known(values) => Values::from_iter(values.iter().cloned().map(f)),
+            Values::Unknown => Values::Unknown,
+        }
+    }
+
+    /// Merges `other` in, returning whether anything changed.
+    fn join(&mut self, other: &Values) -> bool {
+        let joined = match (&*self, other) {
+            (Values::Known(a), Values::Known(b)) => Values::from_iter(a.union(b).cloned()),
+            _ => Values::Unknown,
+        };
+
+        let changed = joined != *self;
+        *self = joined;
+        changed
+    }
+
+    /// V0 after `instr`, given its values before.
+    fn transfer(&self, instr: &Instruction) -> Self {
+        use Instruction::*;
+        use Register::V0;
+
+        match *instr {
+            SetImm(V0, n) => Values::exact(n),
+            AddImm(V0, n) => self.map(|v| v.wrapping_add(n)),
+            LShiftReg(V0, _) => self.map(|v| v << 1),
+            RShiftReg(V0, _) => self.map(|v| v >> 1),
+            Rand(V0, mask) => Values::from_iter((0..=255).filter(|v| v & !mask == 0)),
+            SetReg(V0, V0) => self.clone(),
+            SetReg(V0, _)
+            | OrReg(V0, _)
+            | AndReg(V0, _)
+            | XorReg(V0, _)
+            | AddReg(V0, _)
+            | SubReg(V0, _)
+            | RevSubReg(V0, _)
+            | GetTimer(V0)
+            | WaitPress(V0)
+            | RegLoad(_) => Values::Unknown,
+            _ => self.clone(),
+        }
+    }
+}
+
+pub(crate) fn is_skip(instr: &Instruction) -> bool {
+    use Instruction::*;
+
+    matches!(
+        instr,
+        SkipEqImm(..)
+            | SkipNeqImm(..)
+            | SkipEqReg(..)
+            | SkipNeqReg(..)
+            | SkipPressed(_)
+            | SkipUnpressed(_)
+    )
+}
+
+/// Edges out of `instr` at `addr`, apart from `JP V0` targets.
+fn successors(addr: Address, instr: &Instruction) -> Vec<(Address, EdgeKind)> {
+    use Instruction::*;
+
+    let next = addr.wrapping_add(2);
+
+    match instr {
+        Goto(target) => vec![(*target, EdgeKind::Jump)],
+        Call(target) => vec![(*target, EdgeKind::Call), (next, EdgeKind::Fallthrough)],
+        Return | IndexedJump(_) => vec![],
+        _ if is_skip(instr) => vec![
+            (next, EdgeKind::Fallthrough),
+            (next.wrapping_add(2), EdgeKind::Skip),
+        ],
+        _ => vec![(next, EdgeKind::Fallthrough)],
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Cfg {
+    pub entry: Address,
+    pub blocks: BTreeMap<Address, Block>,
+    pub subroutines: BTreeMap<Address, Subroutine>,
+    /// Addresses loaded into I by reachable code, most likely sprites.
+    pub data: BTreeSet<Address>,
+    pub issues: Vec<Issue>,
+}
+
+impl Cfg {
+    /// Analyzes the program in `memory`, which was loaded into `rom` and
+    /// starts at its beginning.
+    pub fn new(memory: &[u8], rom: Range<usize>) -> Self {
+        let entry = Address(rom.start as u16);
+
+        let mut instructions = BTreeMap::new();
+        let mut edges: BTreeMap<Address, Vec<(Address, EdgeKind)>> = BTreeMap::new();
+        let mut invalid = BTreeSet::new();
+        let mut unresolved = BTreeSet::new();
+
+        let mut queue = vec![entry];
+
+        loop {
+            while let Some(addr) = queue.pop() {
+                if instructions.contains_key(&addr) || invalid.contains(&addr) {
+                    continue;
+                }
+
+                let instr = match crate::parser::decode(&memory[addr.index()..]) {
+                    Ok((_, instr)) => instr,
+                    Err(_) => {
+                        invalid.insert(addr);
+                        continue;
+                    }
+                };
+
+                let out = successors(addr, &instr);
+                queue.extend(out.iter().map(|(to, _)| *to));
+                edges.entry(addr).or_default().extend(out);
+                instructions.insert(addr, instr);
+            }
+
+            // Resolving a `JP V0` can reach code that changes V0 at another
+            // one, so repeat until nothing new turns up.
+            let v0 = Cfg::values(entry, &instructions, &edges);
+
+            for (addr, instr) in &instructions {
+                let base = match instr {
+                    Instruction::IndexedJump(base) => *base,
+                    _ => continue,
+                };
+
+                match &v0[addr] {
+                    Values::Known(values) => {
+                        let out = edges.entry(*addr).or_default();
+
+                        for value in values {
+                            let target = (base.wrapping_add(u16::from(*value)), EdgeKind::Indexed);
+                            if !out.contains(&target) {
+                                out.push(target);
+                                queue.push(target.0);
+                            }
+                        }
+                    }
+                    Values::Unknown => {
+                        unresolved.insert(*addr);
+                    }
+                }
+            }
+
+            if queue.is_empty() {
+                break;
+            }
+        }
+
+        let blocks = Cfg::blocks(entry, &instructions, &edges);
+        let subroutines = Cfg::subroutines(entry, &blocks);
+
+        let data = instructions
+            .values()
+            .filter_map(|instr| match instr {
+                Instruction::SetAddr(addr) => Some(*addr),
+                _ => None,
+            })
+            .collect();
+
+        let mut cfg = Cfg {
+            entry,
+            blocks,
+            subroutines,
+            data,
+            issues: Vec::new(),
+        };
+
+        cfg.issues
+            .extend(invalid.into_iter().map(Issue::InvalidInstruction));
+        cfg.issues
+            .extend(unresolved.into_iter().map(Issue::UnresolvedJump));
+
+        for (from, out) in &edges {
+            for (to, _) in out {
+                if instructions.contains_key(&to.wrapping_sub(1)) {
+                    cfg.issues.push(Issue::MisalignedJump {
+                        from: *from,
+                        to: *to,
+                    });
+                }
+            }
+        }
+
+        cfg.check_stack();
+        cfg.check_unreachable(&instructions, rom);
+        cfg
+    }
+
+    /// Works out what V0 may hold before each instruction.
+    fn values(
+        entry: Address,
+        instructions: &BTreeMap<Address, Instruction>,
+        edges: &BTreeMap<Address, Vec<(Address, EdgeKind)>>,
+    ) -> BTreeMap<Address, Values> {
+        let mut values = BTreeMap::new();
+        values.insert(entry, Values::exact(0));
+
+        let mut queue: VecDeque<_> = std::iter::once(entry).collect();
+
+        while let Some(addr) = queue.pop_front() {
+            let instr = match instructions.get(&addr) {
+                Some(instr) => instr,
+                None => continue,
+            };
+
+            let after = values[&addr].transfer(instr);
+
+            for (to, kind) in &edges[&addr] {
+                // The subroutine could have done anything to V0.
+                let out = match (instr, kind) {
+                    (Instruction::Call(_), EdgeKind::Fallthrough) => Values::Unknown,
+                    _ => after.clone(),
+                };
+
+                let changed = match values.get_mut(to) {
+                    Some(existing) => existing.join(&out),
+                    None => {
+                        values.insert(*to, out);
+                        true
+                    }
+                };
+
+                if changed {
+                    queue.push_back(*to);
+                }
+            }
+        }
+
+        values
+    }
+
+    fn blocks(
+        entry: Address,
+        instructions: &BTreeMap<Address, Instruction>,
+        edges: &BTreeMap<Address, Vec<(Address, EdgeKind)>>,
+    ) -> BTreeMap<Address, Block> {
+        let mut leaders = BTreeSet::new();
+        leaders.insert(entry);
+
+        for (from, out) in edges {
+            for (to, kind) in out {
+                let after_branch = *kind == EdgeKind::Fallthrough && is_skip(&instructions[from]);
+
+                if *kind != EdgeKind::Fallthrough || after_branch {
+                    leaders.insert(*to);
+                }
+            }
+        }
+
+        // Anything without a fallthrough into it starts a block too, e.g.
+        // code after a `RET` that's only reached by jumping.
+        for addr in instructions.keys() {
+            let falls_in = edges
+                .get(&addr.wrapping_sub(2))
+                .into_iter()
+                .flatten()
+                .any(|edge| *edge == (*addr, EdgeKind::Fallthrough));
+
+            if !falls_in {
+                leaders.insert(*addr);
+            }
+        }
+
+        let mut blocks = BTreeMap::new();
+
+        for start in &leaders {
+            let start = *start;
+            if !instructions.contains_key(&start) {
+                continue;
+            }
+
+            let mut block = Block {
+                start,
+                instructions: Vec::new(),
+                successors: Vec::new(),
+            };
+
+            let mut addr = start;
+
+            loop {
+                let instr = instructions[&addr];
+                block.instructions.push((addr, instr));
+
+                let next = addr.wrapping_add(2);
+                let falls_through = edges[&addr].contains(&(next, EdgeKind::Fallthrough));
+
+                if falls_through && instructions.contains_key(&next) && !leaders.contains(&next) {
+                    let calls = edges[&addr]
+                        .iter()
+                        .filter(|(_, kind)| *kind == EdgeKind::Call);
+                    block.successors.extend(calls);
+                    addr = next;
+                } else {
+                    block.successors.extend(&edges[&addr]);
+                    break;
+                }
+            }
+
+            blocks.insert(start, block);
+        }
+
+        blocks
+    }
+
+    fn subroutines(
+        entry: Address,
+        blocks: &BTreeMap<Address, Block>,
+    ) -> BTreeMap<Address, Subroutine> {
+        let mut entries = BTreeSet::new();
+        entries.insert(entry);
+
+        for block in blocks.values() {
+            for (to, kind) in &block.successors {
+                if *kind == EdgeKind::Call {
+                    entries.insert(*to);
+                }
+            }
+        }
+
+        entries
+            .into_iter()
+            .filter(|entry| blocks.contains_key(entry))
+            .map(|entry| {
+                let mut sub = Subroutine {
+                    entry,
+                    blocks: BTreeSet::new(),
+                    calls: BTreeSet::new(),
+                    returns: false,
+                };
+
+                let mut queue = vec![entry];
+
+                while let Some(start) = queue.pop() {
+                    let block = match blocks.get(&start) {
+                        Some(block) if sub.blocks.insert(start) => block,
+                        _ => continue,
+                    };
+
+                    if let Some((_, Instruction::Return)) = block.instructions.last() {
+                        sub.returns = true;
+                    }
+
+                    for (to, kind) in &block.successors {
+                        if *kind == EdgeKind::Call {
+                            sub.calls.insert(*to);
+                        } else {
+                            queue.push(*to);
+                        }
+                    }
+                }
+
+                (entry, sub)
+            })
+            .collect()
+    }
+
+    fn check_stack(&mut self) {
+        if let Some(main) = self.subroutines.get(&self.entry) {
+            for start in &main.blocks {
+                if let Some((addr, Instruction::Return)) = self.blocks[start].instructions.last() {
+                    self.issues.push(Issue::ReturnWithoutCall(*addr));
+                }
+            }
+        }
+
+        for (entry, sub) in &self.subroutines {
+            if *entry == self.entry {
+                continue;
+            }
+
+            if !sub.returns {
+                self.issues.push(Issue::NoReturn(*entry));
+            }
+
+            // Look for a path of calls leading back to this subroutine.
+            let mut seen = BTreeSet::new();
+            let mut queue: Vec<_> = sub.calls.iter().cloned().collect();
+
+            while let Some(callee) = queue.pop() {
+                if callee == *entry {
+                    self.issues.push(Issue::Recursion(*entry));
+                    break;
+                }
+
+                if seen.insert(callee) {
+                    if let Some(callee) = self.subroutines.get(&callee) {
+                        queue.extend(callee.calls.iter().cloned());
+                    }
+                }
+            }
+        }
+    }
+
+    fn check_unreachable(
+        &mut self,
+        instructions: &BTreeMap<Address, Instruction>,
+        rom: Range<usize>,
+    ) {
+        let covered = |addr: u16| {
+            let addr = Address(addr);
+            instructions.contains_key(&addr) || instructions.contains_key(&addr.wrapping_sub(1))
+        };
+
+        let mut addr = rom.start as u16;
+        let end = rom.end as u16;
+
+        while addr < end {
+            if covered(addr) {
+                addr += 1;
+                continue;
+            }
+
+            let start = addr;
+            while addr < end && !covered(addr) && !self.data.contains(&Address(addr)) {
+                addr += 1;
+            }
+
+            if addr > start {
+                self.issues.push(Issue::Unreachable(start..addr));
+            }
+
+            // Anything from an `LD I` target up to the next code is data.
+            while addr < end && !covered(addr) {
+                addr += 1;
+            }
+        }
+    }
+
+    /// Which subroutine a block is drawn in, when several share it.
+    fn owner(&self, start: Address) -> Option<Address> {
+        self.subroutines
+            .values()
+            .find(|sub| sub.blocks.contains(&start))
+            .map(|sub| sub.entry)
+    }
+
+    pub fn write_report<W: Write>(&self, symbols: &Symbols, out: &mut W) -> io::Result<()> {
+        let count: usize = self.blocks.values().map(|b| b.instructions.len()).sum();
+        writeln!(
+            out,
+            "{} instructions in {} blocks, {} subroutines",
+            count,
+            self.blocks.len(),
+            self.subroutines.len()
+        )?;
+
+        writeln!(out)?;
+        writeln!(out, "Subroutines:")?;
+        writeln!(out, "    {:<20} {:>6}  calls", "entry", "blocks")?;
+        for sub in self.subroutines.values() {
+            let calls: Vec<_> = sub
+                .calls
+                .iter()
+                .map(|target| Symbolic(target, symbols).to_string())
+                .collect();
+
+            let line = format!(
+                "    {:<20} {:>6}  {}",
+                Symbolic(&sub.entry, symbols).to_string(),
+                sub.blocks.len(),
+                calls.join(", ")
+            );
+            writeln!(out, "{}", line.trim_end())?;
+        }
+
+        writeln!(out)?;
+        if self.issues.is_empty() {
+            writeln!(out, "No issues found.")?;
+        } else {
+            writeln!(out, "Issues:")?;
+            for issue in &self.issues {
+                writeln!(out, "    {}", Symbolic(issue, symbols))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes the graph in Graphviz DOT format, with a cluster per subroutine.
+    pub fn write_dot<W: Write>(&self, symbols: &Symbols, out: &mut W) -> io::Result<()> {
+        let escape = |s: String| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let id = |addr: &Address| format!("b{:03X}", addr.0);
+
+        writeln!(out, "digraph cfg {{")?;
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+
+        for sub in self.subroutines.values() {
+            writeln!(out)?;
+            writeln!(out, "    subgraph cluster_{:03X} {{", sub.entry.0)?;
+            writeln!(
+                out,
+                "        label=\"{}\";",
+                escape(Symbolic(&sub.entry, symbols).to_string())
+            )?;
+
+            for start in &sub.blocks {
+                if self.owner(*start) != Some(sub.entry) {
+                    continue;
+                }
+
+                let mut label = String::new();
+                for (addr, instr) in &self.blocks[start].instructions {
+                    label += &format!(
+                        "{}: {}\\l",
+                        escape(Symbolic(addr, symbols).to_string()),
+                        escape(Symbolic(instr, symbols).to_string())
+                    );
+                }
+
+                writeln!(out, "        {} [label=\"{}\"];", id(start), label)?;
+            }
+
+            writeln!(out, "    }}")?;
+        }
+
+        writeln!(out)?;
+        for block in self.blocks.values() {
+            for (to, kind) in &block.successors {
+                if !self.blocks.contains_key(to) {
+                    continue;
+                }
+
+                let style = match kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Jump => " [style=bold]",
+                    EdgeKind::Skip => " [label=\"skip\"]",
+                    EdgeKind::Indexed => " [style=dotted]",
+                    EdgeKind::Call => " [style=dashed, color=blue]",
+                };
+
+                writeln!(out, "    {} -> {}{};", id(&block.start), id(to), style)?;
+            }
+        }
+
+        writeln!(out, "}}")
+    }
+}
+
+impl fmt::Display for Symbolic<'_, Issue> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Symbolic(issue, symbols) = *self;
+        let sym = |addr| Symbolic(addr, symbols);
+
+        match issue {
+            Issue::Unreachable(range) => write!(
+                f,
+                "unreachable code at {}-{}",
+                sym(&Address(range.start)),
+                sym(&Address(range.end - 1))
+            ),
+            Issue::InvalidInstruction(addr) => write!(f, "invalid instruction at {}", sym(addr)),
+            Issue::UnresolvedJump(addr) => {
+                write!(f, "couldn't resolve JP V0 at {}", sym(addr))
+            }
+            Issue::MisalignedJump { from, to } => write!(
+                f,
+                "{} jumps into the middle of the instruction at {}",
+                sym(from),
+                sym(&to.wrapping_sub(1))
+            ),
+            Issue::ReturnWithoutCall(addr) => {
+                write!(f, "RET at {} is reachable without a call", sym(addr))
+            }
+            Issue::NoReturn(entry) => write!(
+                f,
+                "subroutine {} never returns, leaking a stack entry",
+                sym(entry)
+            ),
+            Issue::Recursion(entry) => write!(
+                f,
+                "subroutine {} is recursive and may overflow the stack",
+                sym(entry)
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Symbolic(self, &Symbols::new()).fmt(f)
+    }
+}
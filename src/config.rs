@@ -0,0 +1,346 @@
+//! The user's settings, from `$XDG_CONFIG_HOME/chip8-rs/config.toml`.
+//!
+//! The top level holds defaults for every ROM, and sections under `roms`
+//! override them for one ROM, named by the SHA-1 of its contents or by its
+//! file name:
+//!
+//! ```toml
+//! scale = 8
+//! screenshot_scale = 10
+//! volume = 0.5
+//! speed = 20
+//! quirks = "schip,no-jump"
+//! theme = "phosphor"
+//! foreground = "#FFCC00"
+//! background = "#996600"
+//! foreground2 = "#FF6600"
+//! blend = "#662200"
+//! filter = "decay:0.5"
+//! layout = "azerty"
+//!
+//! # Bindings on top of the layout, from host keys to buttons 0 to 15.
+//! [keys]
+//! Up = 5
+//! NumPad5 = 5
+//! Space = 0xC
+//!
+//! [roms."pong.rom"]
+//! speed = 10
+//!
+//! [roms.1830eb401ba8789a477dfcf294873a5479ebcfe8.keys]
+//! W = 1
+//! ```
+//!
+//! Every setting is optional. The colors replace the theme's: `foreground` is
+//! the first XO-CHIP plane, `foreground2` the second, and `blend` both. A
+//! section for a ROM's hash takes precedence over one for its file name.
+
+use crate::cartridge::parse_color;
+use crate::keymap::{key_name, parse_key, KeyMap, Layout};
+use crate::palette::{Palette, Theme};
+use crate::phosphor::Filter;
+use crate::romdb;
+use crate::screenshot;
+use crate::types::{Button, Quirks};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use toml::value::{Table, Value};
+
+/// The window scales minifb supports.
+pub const SCALES: [u32; 6] = [1, 2, 4, 8, 16, 32];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    pub scale: Option<u32>,
+    pub screenshot_scale: Option<u32>,
+    /// From 0 to 1.
+    pub volume: Option<f32>,
+    /// Instructions per 60 Hz frame.
+    pub speed: Option<u32>,
+    pub quirks: Option<Quirks>,
+    pub theme: Option<Theme>,
+    pub foreground: Option<u32>,
+    pub background: Option<u32>,
+    pub foreground2: Option<u32>,
+    pub blend: Option<u32>,
+    pub filter: Option<Filter>,
+    pub layout: Option<Layout>,
+    /// Bindings on top of the layout.
+    pub keys: Vec<(minifb::Key, Button)>,
+}
+
+fn integer(name: &str, value: &Value) -> Result<u32, String> {
+    value
+        .as_integer()
+        .filter(|n| (0..=i64::from(u32::MAX)).contains(n))
+        .map(|n| n as u32)
+        .ok_or_else(|| format!("invalid {} {}", name, value))
+}
+
+fn string<'a>(name: &str, value: &'a Value) -> Result<&'a str, String> {
+    value
+        .as_str()
+        .ok_or_else(|| format!("invalid {} {}", name, value))
+}
+
+fn color(name: &str, value: &Value) -> Result<u32, String> {
+    parse_color(string(name, value)?).ok_or_else(|| format!("invalid {} {}", name, value))
+}
+
+impl Settings {
+    fn from_toml(table: &Table) -> Result<Self, String> {
+        let mut settings = Settings::default();
+
+        for (name, value) in table {
+            match name.as_str() {
+                "scale" => {
+                    let scale = integer(name, value)?;
+                    if !SCALES.contains(&scale) {
+                        return Err(format!(
+                            "invalid scale {}: expected 1, 2, 4, 8, 16 or 32",
+                            scale
+                        ));
+                    }
+                    settings.scale = Some(scale);
+                }
+                "screenshot_scale" => match integer(name, value)? {
+                    scale @ 1..=screenshot::MAX_SCALE => settings.screenshot_scale = Some(scale),
+                    _ => {
+                        return Err(format!(
+                            "screenshot_scale must be from 1 to {}",
+                            screenshot::MAX_SCALE
+                        ))
+                    }
+                },
+                "volume" => {
+                    let volume = value
+                        .as_float()
+                        .or_else(|| value.as_integer().map(|n| n as f64))
+                        .filter(|volume| (0.0..=1.0).contains(volume))
+                        .ok_or_else(|| format!("invalid volume {}: expected 0 to 1", value))?;
+                    settings.volume = Some(volume as f32);
+                }
+                "speed" => match integer(name, value)? {
+                    0 => return Err("speed must be at least 1".into()),
+                    speed => settings.speed = Some(speed),
+                },
+                "quirks" => {
+                    let quirks = string(name, value)?;
+                    settings.quirks = Some(
+                        quirks
+                            .parse()
+                            .map_err(|err| format!("invalid quirks {:?}: {}", quirks, err))?,
+                    );
+                }
+                "theme" => settings.theme = Some(string(name, value)?.parse()?),
+                "foreground" => settings.foreground = Some(color(name, value)?),
+                "background" => settings.background = Some(color(name, value)?),
+                "foreground2" => settings.foreground2 = Some(color(name, value)?),
+                "blend" => settings.blend = Some(color(name, value)?),
+                "filter" => settings.filter = Some(string(name, value)?.parse()?),
+                "layout" => {
+                    settings.layout = Some(string(name, value)?.parse()?);
+                }
+                "keys" => {
+                    let bindings = value
+                        .as_table()
+                        .ok_or_else(|| format!("invalid keys {}", value))?;
+
+                    for (name, button) in bindings {
+                        let key =
+                            parse_key(name).ok_or_else(|| format!("unknown key {:?}", name))?;
+                        let button = button
+                            .as_integer()
+                            .filter(|n| (0..16).contains(n))
+                            .and_then(|n| Button::n(n as u8))
+                            .ok_or_else(|| format!("invalid button {}", button))?;
+                        settings.keys.push((key, button));
+                    }
+                }
+                _ => return Err(format!("unknown setting {:?}", name)),
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// The theme, or the classic one, with the colors on top.
+    pub fn palette(&self) -> Palette {
+        let Palette([background, foreground, foreground2, blend]) =
+            self.theme.unwrap_or(Theme::Classic).palette();
+
+        Palette([
+            self.background.unwrap_or(background),
+            self.foreground.unwrap_or(foreground),
+            self.foreground2.unwrap_or(foreground2),
+            self.blend.unwrap_or(blend),
+        ])
+    }
+
+    /// The layout, or QWERTY, with the bindings on top.
+    pub fn key_map(&self) -> KeyMap {
+        let mut map = KeyMap::layout(self.layout.unwrap_or(Layout::Qwerty));
+        for &(key, button) in &self.keys {
+            map.bind(key, button);
+        }
+        map
+    }
+
+    /// Takes the settings `other` has, including its key bindings, which
+    /// replace ours for the same keys.
+    pub fn merge(&mut self, other: &Settings) {
+        self.scale = other.scale.or(self.scale);
+        self.screenshot_scale = other.screenshot_scale.or(self.screenshot_scale);
+        self.volume = other.volume.or(self.volume);
+        self.speed = other.speed.or(self.speed);
+        self.quirks = other.quirks.or(self.quirks);
+        self.theme = other.theme.or(self.theme);
+        self.foreground = other.foreground.or(self.foreground);
+        self.background = other.background.or(self.background);
+        self.foreground2 = other.foreground2.or(self.foreground2);
+        self.blend = other.blend.or(self.blend);
+        self.filter = other.filter.or(self.filter);
+        self.layout = other.layout.or(self.layout);
+        self.keys
+            .retain(|(key, _)| other.keys.iter().all(|(other, _)| other != key));
+        self.keys.extend_from_slice(&other.keys);
+    }
+}
+
+/// Writes the settings back out as TOML.
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(scale) = self.scale {
+            writeln!(f, "scale = {}", scale)?;
+        }
+        if let Some(scale) = self.screenshot_scale {
+            writeln!(f, "screenshot_scale = {}", scale)?;
+        }
+        if let Some(volume) = self.volume {
+            writeln!(f, "volume = {:?}", volume)?;
+        }
+        if let Some(speed) = self.speed {
+            writeln!(f, "speed = {}", speed)?;
+        }
+        if let Some(mut quirks) = self.quirks {
+            // XO-CHIP has every quirk off, so this names exactly these quirks.
+            let mut spec = String::from("xochip");
+            for (name, on) in quirks.flags_mut().iter() {
+                if **on {
+                    spec.push(',');
+                    spec.push_str(name);
+                }
+            }
+            writeln!(f, "quirks = {:?}", spec)?;
+        }
+        if let Some(theme) = self.theme {
+            writeln!(f, "theme = \"{}\"", theme)?;
+        }
+        let colors = [
+            ("foreground", self.foreground),
+            ("background", self.background),
+            ("foreground2", self.foreground2),
+            ("blend", self.blend),
+        ];
+        for (name, color) in &colors {
+            if let Some(color) = color {
+                writeln!(f, "{} = \"#{:06X}\"", name, color & 0xFFFFFF)?;
+            }
+        }
+
+        if let Some(filter) = self.filter {
+            writeln!(f, "filter = \"{}\"", filter)?;
+        }
+        if let Some(layout) = self.layout {
+            writeln!(f, "layout = \"{}\"", layout)?;
+        }
+
+        if !self.keys.is_empty() {
+            writeln!(f, "\n[keys]")?;
+            for (key, button) in &self.keys {
+                writeln!(f, "{} = {}", key_name(*key), *button as u8)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub defaults: Settings,
+    /// Overrides keyed by lowercase SHA-1 or file name.
+    pub roms: HashMap<String, Settings>,
+}
+
+/// `$XDG_CONFIG_HOME/chip8-rs`, falling back to `~/.config/chip8-rs`.
+pub fn dir() -> Option<PathBuf> {
+    let config = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(config.join("chip8-rs"))
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        Some(dir()?.join("config.toml"))
+    }
+
+    /// The overrides for `rom`, which was loaded from a file called
+    /// `file_name`.
+    pub fn rom(&self, file_name: &str, rom: &[u8]) -> Settings {
+        let mut settings = Settings::default();
+
+        for name in &[file_name.to_string(), romdb::hash(rom)] {
+            if let Some(overrides) = self.roms.get(name) {
+                settings.merge(overrides);
+            }
+        }
+
+        settings
+    }
+}
+
+impl std::str::FromStr for Config {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut table = match s.parse::<Value>().map_err(|err| err.to_string())? {
+            Value::Table(table) => table,
+            _ => return Err("config isn't a table".into()),
+        };
+
+        let mut roms = HashMap::new();
+        match table.remove("roms") {
+            Some(Value::Table(sections)) => {
+                for (name, section) in sections {
+                    let section = section
+                        .as_table()
+                        .ok_or_else(|| format!("roms.{}: section isn't a table", name))?;
+                    let settings = Settings::from_toml(section)
+                        .map_err(|err| format!("roms.{}: {}", name, err))?;
+
+                    // Hashes are matched in lowercase, file names as written.
+                    let is_hash = name.len() == 40 && name.chars().all(|c| c.is_ascii_hexdigit());
+                    let name = if is_hash {
+                        name.to_ascii_lowercase()
+                    } else {
+                        name
+                    };
+                    roms.insert(name, settings);
+                }
+            }
+            Some(sections) => return Err(format!("invalid roms {}", sections)),
+            None => {}
+        }
+
+        Ok(Config {
+            defaults: Settings::from_toml(&table)?,
+            roms,
+        })
+    }
+}
@@ -0,0 +1,161 @@
+use crate::eval::AccessKind;
+use crate::types::*;
+use std::io::{self, Write};
+use std::ops::Range;
+
+const FETCHED: u8 = 1 << 0;
+const READ: u8 = 1 << 1;
+const WRITTEN: u8 = 1 << 2;
+
+/// Which bytes of memory were fetched as instructions, read as data by
+/// `Draw`/`RegLoad`, or written by `BCD`/`RegDump`.
+pub struct Coverage {
+    flags: [u8; 4096],
+    rom: Range<usize>,
+}
+
+impl Coverage {
+    /// `rom` is the range the program was loaded into, used for the summary.
+    pub fn new(rom: Range<usize>) -> Self {
+        Coverage {
+            flags: [0; 4096],
+            rom,
+        }
+    }
+
+    /// Records `instr`, which has been fetched but not yet evaluated.
+    pub fn record(&mut self, state: &State, instr: &Instruction) {
+        self.flags[state.pc.index()] |= FETCHED;
+        self.flags[state.pc.wrapping_add(1).index()] |= FETCHED;
+
+        if let Some(access) = instr.memory_access(state) {
+            let flag = match access.kind {
+                AccessKind::Read => READ,
+                AccessKind::Write => WRITTEN,
+            };
+
+            for addr in access.addresses() {
+                self.flags[addr.index()] |= flag;
+            }
+        }
+    }
+
+    pub fn fetched(&self, addr: Address) -> bool {
+        self.flags[addr.index()] & FETCHED != 0
+    }
+
+    pub fn read(&self, addr: Address) -> bool {
+        self.flags[addr.index()] & READ != 0
+    }
+
+    pub fn written(&self, addr: Address) -> bool {
+        self.flags[addr.index()] & WRITTEN != 0
+    }
+
+    fn ranges(&self, flag: u8) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+
+        for (addr, flags) in self.flags.iter().enumerate() {
+            if flags & flag == 0 {
+                continue;
+            }
+
+            match ranges.last_mut() {
+                Some(range) if range.end == addr => range.end += 1,
+                _ => ranges.push(addr..addr + 1),
+            }
+        }
+
+        ranges
+    }
+
+    fn write_summary<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let rom = &self.flags[self.rom.clone()];
+        let count = |flag: u8| rom.iter().filter(|flags| *flags & flag != 0).count();
+        let percent = |n: usize| 100.0 * n as f64 / rom.len().max(1) as f64;
+
+        let touched = count(FETCHED | READ | WRITTEN);
+        writeln!(
+            out,
+            "ROM {:#05X}-{:#05X}: {} of {} bytes touched ({:.1}%)",
+            self.rom.start,
+            self.rom.end.saturating_sub(1),
+            touched,
+            rom.len(),
+            percent(touched)
+        )?;
+
+        for (name, flag) in &[("fetched", FETCHED), ("read", READ), ("written", WRITTEN)] {
+            let n = count(*flag);
+            writeln!(out, "    {:<8} {:>5} ({:.1}%)", name, n, percent(n))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a hex dump of `memory` with two marker characters under each
+    /// byte: `C` if it was fetched as code, then `R`, `W` or `B` (both) for
+    /// data accesses. Rows that are zero and untouched are skipped.
+    pub fn write_hex<W: Write>(&self, memory: &[u8], out: &mut W) -> io::Result<()> {
+        self.write_summary(out)?;
+        writeln!(out)?;
+
+        let mut skipped = false;
+
+        for (row, (bytes, flags)) in memory.chunks(16).zip(self.flags.chunks(16)).enumerate() {
+            if bytes.iter().all(|b| *b == 0) && flags.iter().all(|f| *f == 0) {
+                if !skipped {
+                    writeln!(out, "*")?;
+                    skipped = true;
+                }
+                continue;
+            }
+            skipped = false;
+
+            write!(out, "{:03X} ", row * 16)?;
+            for byte in bytes {
+                write!(out, " {:02X}", byte)?;
+            }
+            writeln!(out)?;
+
+            write!(out, "    ")?;
+            for flags in flags {
+                let code = if flags & FETCHED != 0 { 'C' } else { '.' };
+                let data = match (flags & READ != 0, flags & WRITTEN != 0) {
+                    (true, true) => 'B',
+                    (true, false) => 'R',
+                    (false, true) => 'W',
+                    (false, false) => '.',
+                };
+                write!(out, " {}{}", code, data)?;
+            }
+            writeln!(out)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the touched address ranges as JSON, each as an inclusive
+    /// `[first, last]` pair.
+    pub fn write_json<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let ranges = |flag| {
+            self.ranges(flag)
+                .iter()
+                .map(|range| format!("[{}, {}]", range.start, range.end - 1))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        writeln!(out, "{{")?;
+        writeln!(
+            out,
+            "  \"rom\": [{}, {}],",
+            self.rom.start,
+            self.rom.end.saturating_sub(1)
+        )?;
+        writeln!(out, "  \"fetched\": [{}],", ranges(FETCHED))?;
+        writeln!(out, "  \"read\": [{}],", ranges(READ))?;
+        writeln!(out, "  \"written\": [{}]", ranges(WRITTEN))?;
+        writeln!(out, "}}")
+    }
+}
@@ -0,0 +1,663 @@
+//! A Debug Adapter Protocol server, so editors can debug ROMs at the source
+//! level using the line map written by the assembler.
+
+use crate::debug::{Breakpoint, Debugger, Frontend, Resume};
+use crate::expr::Condition;
+use crate::linemap::LineMap;
+use crate::symbols::Symbolic;
+use crate::types::*;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+const THREAD_ID: u64 = 1;
+
+const REGISTERS: u64 = 1;
+const TIMERS: u64 = 2;
+const STACK: u64 = 3;
+
+/// What the client asked to run, from its `launch` request.
+#[derive(Debug, Clone)]
+pub struct Launch {
+    pub program: PathBuf,
+    pub symbols: Option<PathBuf>,
+}
+
+/// How far a `next`, `stepIn` or `stepOut` has to go before stopping.
+#[derive(Debug, Clone)]
+enum Stepping {
+    In {
+        line: Option<(PathBuf, u32)>,
+    },
+    Over {
+        line: Option<(PathBuf, u32)>,
+        depth: usize,
+    },
+    Out {
+        depth: usize,
+    },
+}
+
+pub struct DapServer {
+    messages: Receiver<Value>,
+    writer: TcpStream,
+    seq: u64,
+    line_map: LineMap,
+    stop_on_entry: bool,
+    /// Breakpoints set through `setBreakpoints`, by source path.
+    breakpoints: HashMap<PathBuf, Vec<Breakpoint>>,
+    /// Whether the client is waiting on a `stopped` event.
+    running: bool,
+    stepping: Option<Stepping>,
+    /// Where the last step started, so a step that leaves PC in place, such
+    /// as FX0A waiting for a key, doesn't count as reaching a breakpoint.
+    step_from: Option<Address>,
+    pause_requested: bool,
+    /// Set once the client disconnects, to be reported by the next `serve`.
+    disconnected: Option<Resume>,
+}
+
+fn read_message<R: BufRead>(reader: &mut R) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        if let Some(n) = header.strip_prefix("Content-Length:") {
+            length = n.trim().parse().ok();
+        }
+    }
+
+    let length =
+        length.ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "missing Content-Length"))?;
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|err| io::Error::new(ErrorKind::InvalidData, err))
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::new();
+
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, b)| n | u32::from(*b) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}
+
+fn base64_decode(s: &str) -> Option<Vec<u8>> {
+    let digits = s
+        .trim_end_matches('=')
+        .bytes()
+        .map(|c| BASE64.iter().position(|d| *d == c).map(|n| n as u32))
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut out = Vec::with_capacity(digits.len() * 3 / 4);
+
+    for chunk in digits.chunks(4) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0, |n, (i, d)| n | d << (18 - 6 * i));
+
+        for i in 0..chunk.len().saturating_sub(1) {
+            out.push((n >> (16 - 8 * i)) as u8);
+        }
+    }
+
+    Some(out)
+}
+
+/// Parses a `memoryReference`, which we hand out as hex addresses.
+fn parse_address(reference: &str) -> Option<usize> {
+    usize::from_str_radix(reference.trim_start_matches("0x"), 16).ok()
+}
+
+/// The address a memory request's `memoryReference` and `offset` point at,
+/// or `None` if it isn't one.
+fn memory_address(args: &Value) -> Option<usize> {
+    let addr = parse_address(args["memoryReference"].as_str().unwrap_or(""))?;
+    let addr = i64::try_from(addr).ok()?;
+    usize::try_from(addr.checked_add(args["offset"].as_i64().unwrap_or(0))?).ok()
+}
+
+fn variable(name: &str, value: String) -> Value {
+    json!({ "name": name, "value": value, "variablesReference": 0 })
+}
+
+fn pointer(name: &str, addr: Address) -> Value {
+    json!({
+        "name": name,
+        "value": addr.to_string(),
+        "variablesReference": 0,
+        "memoryReference": addr.to_string(),
+    })
+}
+
+impl DapServer {
+    /// Takes over a freshly accepted connection, answering requests until
+    /// the client has sent `launch`.
+    pub fn new(stream: TcpStream) -> io::Result<(Self, Launch)> {
+        stream.set_nodelay(true)?;
+
+        let (sender, messages) = mpsc::channel();
+        let reader = stream.try_clone()?;
+
+        thread::spawn(move || {
+            let mut reader = BufReader::new(reader);
+            while let Ok(Some(message)) = read_message(&mut reader) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut server = DapServer {
+            messages,
+            writer: stream,
+            seq: 1,
+            line_map: LineMap::new(),
+            stop_on_entry: false,
+            breakpoints: HashMap::new(),
+            running: false,
+            stepping: None,
+            step_from: None,
+            pause_requested: false,
+            disconnected: None,
+        };
+
+        let launch = server.launch()?;
+        Ok((server, launch))
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        message["seq"] = self.seq.into();
+        self.seq += 1;
+
+        let message = message.to_string();
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            message.len(),
+            message
+        )?;
+        self.writer.flush()
+    }
+
+    fn respond(&mut self, request: &Value, body: Value) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    fn fail(&mut self, request: &Value, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn receive(&mut self) -> io::Result<Value> {
+        self.messages
+            .recv()
+            .map_err(|_| io::Error::new(ErrorKind::ConnectionAborted, "client disconnected"))
+    }
+
+    fn launch(&mut self) -> io::Result<Launch> {
+        loop {
+            let request = self.receive()?;
+
+            match request["command"].as_str().unwrap_or("") {
+                "initialize" => self.respond(
+                    &request,
+                    json!({
+                        "supportsConfigurationDoneRequest": true,
+                        "supportsConditionalBreakpoints": true,
+                        "supportsReadMemoryRequest": true,
+                        "supportsWriteMemoryRequest": true,
+                    }),
+                )?,
+                "launch" => {
+                    let args = &request["arguments"];
+
+                    let program = match args["program"].as_str() {
+                        Some(program) => PathBuf::from(program),
+                        None => {
+                            self.fail(&request, "launch needs a program")?;
+                            continue;
+                        }
+                    };
+
+                    if let Some(path) = args["lineMap"].as_str() {
+                        let map = fs::read_to_string(path)
+                            .map_err(|err| err.to_string())
+                            .and_then(|map| map.parse::<LineMap>());
+
+                        match map {
+                            Ok(mut map) => {
+                                map.resolve(Path::new(path).parent().unwrap_or(Path::new("")));
+                                self.line_map = map;
+                            }
+                            Err(err) => {
+                                self.fail(&request, &format!("Couldn't load line map: {}", err))?;
+                                continue;
+                            }
+                        }
+                    }
+
+                    self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+
+                    self.respond(&request, Value::Null)?;
+                    self.event("initialized", Value::Null)?;
+
+                    return Ok(Launch {
+                        program,
+                        symbols: args["symbols"].as_str().map(PathBuf::from),
+                    });
+                }
+                _ => self.fail(&request, "not launched yet")?,
+            }
+        }
+    }
+
+    fn line(&self, addr: Address) -> Option<(PathBuf, u32)> {
+        self.line_map
+            .line(addr)
+            .map(|(file, line)| (file.to_path_buf(), line))
+    }
+
+    fn step_done(&self, state: &State) -> bool {
+        let left = |line: &Option<(PathBuf, u32)>| {
+            let now = self.line(state.pc);
+            line.is_none() || (now.is_some() && now != *line)
+        };
+
+        match &self.stepping {
+            None => true,
+            Some(Stepping::In { line }) => left(line),
+            Some(Stepping::Over { line, depth }) => {
+                state.call_stack.len() < *depth || state.call_stack.len() == *depth && left(line)
+            }
+            Some(Stepping::Out { depth }) => state.call_stack.len() < *depth,
+        }
+    }
+
+    fn set_breakpoints(&mut self, request: &Value, debugger: &mut Debugger) -> io::Result<()> {
+        let args = &request["arguments"];
+        let path = PathBuf::from(args["source"]["path"].as_str().unwrap_or(""));
+
+        if let Some(old) = self.breakpoints.remove(&path) {
+            debugger.breakpoints.retain(|b| !old.contains(b));
+        }
+
+        let mut set = Vec::new();
+        let mut results = Vec::new();
+
+        for requested in args["breakpoints"].as_array().into_iter().flatten() {
+            let line = requested["line"].as_u64().unwrap_or(0) as u32;
+            let condition = requested["condition"]
+                .as_str()
+                .map(|condition| Condition::parse(condition, &debugger.symbols))
+                .transpose();
+
+            let result = match (self.line_map.address(&path, line), condition) {
+                (Some((addr, line)), Ok(condition)) => {
+                    set.push(Breakpoint {
+                        address: Some(addr),
+                        condition,
+                    });
+                    json!({ "verified": true, "line": line })
+                }
+                (None, _) => json!({ "verified": false, "message": "no code on this line" }),
+                (_, Err(err)) => json!({ "verified": false, "message": err }),
+            };
+
+            results.push(result);
+        }
+
+        debugger.breakpoints.extend(set.iter().cloned());
+        self.breakpoints.insert(path, set);
+
+        self.respond(request, json!({ "breakpoints": results }))
+    }
+
+    fn stack_trace(
+        &mut self,
+        request: &Value,
+        state: &State,
+        debugger: &Debugger,
+    ) -> io::Result<()> {
+        // The innermost frame is at PC, the rest at the calls that got there.
+        let pcs = std::iter::once(state.pc)
+            .chain(state.call_stack.iter().rev().map(|ret| ret.wrapping_sub(2)));
+
+        let frames: Vec<Value> = pcs
+            .enumerate()
+            .map(|(id, pc)| {
+                let mut frame = json!({
+                    "id": id,
+                    "name": Symbolic(&pc, &debugger.symbols).to_string(),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": pc.to_string(),
+                });
+
+                if let Some((file, line)) = self.line(pc) {
+                    let name = file.file_name().unwrap_or_default().to_string_lossy();
+                    frame["source"] = json!({ "name": name, "path": file });
+                    frame["line"] = line.into();
+                    frame["column"] = 1.into();
+                }
+
+                frame
+            })
+            .collect();
+
+        let total = frames.len();
+        self.respond(
+            request,
+            json!({ "stackFrames": frames, "totalFrames": total }),
+        )
+    }
+
+    fn variables(&mut self, request: &Value, state: &State) -> io::Result<()> {
+        let variables: Vec<Value> = match request["arguments"]["variablesReference"].as_u64() {
+            Some(REGISTERS) => state
+                .registers
+                .iter()
+                .map(|(reg, value)| variable(&reg.to_string(), format!("{:#04X}", value)))
+                .chain(vec![pointer("I", state.i_reg), pointer("PC", state.pc)])
+                .collect(),
+            Some(TIMERS) => vec![
+                variable("DT", format!("{:#04X}", state.timer)),
+                variable("ST", format!("{:#04X}", state.sound_timer)),
+            ],
+            Some(STACK) => state
+                .call_stack
+                .iter()
+                .enumerate()
+                .map(|(i, ret)| pointer(&i.to_string(), *ret))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        self.respond(request, json!({ "variables": variables }))
+    }
+
+    fn read_memory(&mut self, request: &Value, state: &State) -> io::Result<()> {
+        let args = &request["arguments"];
+
+        match memory_address(args) {
+            Some(start) => {
+                let start = start.min(state.memory.len());
+                let count = args["count"].as_u64().unwrap_or(0);
+                let end = usize::try_from(count)
+                    .map_or(usize::MAX, |count| start.saturating_add(count))
+                    .min(state.memory.len());
+
+                self.respond(
+                    request,
+                    json!({
+                        "address": format!("{:#05X}", start),
+                        "data": base64_encode(&state.memory[start..end]),
+                        "unreadableBytes": count - (end - start) as u64,
+                    }),
+                )
+            }
+            _ => self.fail(request, "invalid memory reference"),
+        }
+    }
+
+    fn write_memory(&mut self, request: &Value, state: &mut State) -> io::Result<()> {
+        let args = &request["arguments"];
+
+        let start = memory_address(args);
+        let data = base64_decode(args["data"].as_str().unwrap_or(""));
+
+        match (start, data) {
+            (Some(start), Some(data)) if matches!(start.checked_add(data.len()), Some(end) if end <= state.memory.len()) =>
+            {
+                state.memory[start..start + data.len()].copy_from_slice(&data);
+
+                self.respond(request, json!({ "bytesWritten": data.len() }))
+            }
+            _ => self.fail(request, "invalid memory write"),
+        }
+    }
+
+    /// Answers `request`, returning how to resume if it was a request to.
+    fn handle(
+        &mut self,
+        request: &Value,
+        state: &mut State,
+        debugger: &mut Debugger,
+    ) -> io::Result<Option<Resume>> {
+        let line = self.line(state.pc);
+        let depth = state.call_stack.len();
+
+        let stepping = match request["command"].as_str().unwrap_or("") {
+            "configurationDone" => {
+                self.respond(request, Value::Null)?;
+
+                if self.stop_on_entry {
+                    self.event(
+                        "stopped",
+                        json!({ "reason": "entry", "threadId": THREAD_ID, "allThreadsStopped": true }),
+                    )?;
+                    return Ok(None);
+                }
+
+                None
+            }
+            "continue" => {
+                self.respond(request, json!({ "allThreadsContinued": true }))?;
+                None
+            }
+            "next" => Some(Stepping::Over { line, depth }),
+            "stepIn" => Some(Stepping::In { line }),
+            "stepOut" => Some(Stepping::Out { depth }),
+            "pause" => {
+                self.pause_requested = self.running;
+                self.respond(request, Value::Null)?;
+                return Ok(None);
+            }
+            "disconnect" => {
+                self.respond(request, Value::Null)?;
+
+                return Ok(Some(
+                    if request["arguments"]["terminateDebuggee"] == false {
+                        Resume::Detach
+                    } else {
+                        Resume::Kill
+                    },
+                ));
+            }
+            "setBreakpoints" => {
+                self.set_breakpoints(request, debugger)?;
+                return Ok(None);
+            }
+            "threads" => {
+                self.respond(
+                    request,
+                    json!({ "threads": [{ "id": THREAD_ID, "name": "chip8" }] }),
+                )?;
+                return Ok(None);
+            }
+            "stackTrace" => {
+                self.stack_trace(request, state, debugger)?;
+                return Ok(None);
+            }
+            "scopes" => {
+                self.respond(
+                    request,
+                    json!({ "scopes": [
+                        { "name": "Registers", "variablesReference": REGISTERS, "expensive": false },
+                        { "name": "Timers", "variablesReference": TIMERS, "expensive": false },
+                        { "name": "Stack", "variablesReference": STACK, "expensive": false },
+                    ] }),
+                )?;
+                return Ok(None);
+            }
+            "variables" => {
+                self.variables(request, state)?;
+                return Ok(None);
+            }
+            "readMemory" => {
+                self.read_memory(request, state)?;
+                return Ok(None);
+            }
+            "writeMemory" => {
+                self.write_memory(request, state)?;
+                return Ok(None);
+            }
+            command => {
+                self.fail(request, &format!("unsupported request {:?}", command))?;
+                return Ok(None);
+            }
+        };
+
+        if stepping.is_some() {
+            self.respond(request, Value::Null)?;
+        }
+
+        // Only configurationDone, continue and the steps get here.
+        if self.running {
+            return Ok(None);
+        }
+
+        self.running = true;
+        self.stepping = stepping;
+        self.step_from = Some(state.pc);
+
+        Ok(Some(if self.stepping.is_some() {
+            Resume::Step
+        } else {
+            Resume::Continue
+        }))
+    }
+}
+
+impl Frontend for DapServer {
+    /// Answers whatever arrived while running, without blocking.
+    fn interrupted(&mut self, state: &mut State, debugger: &mut Debugger) -> io::Result<bool> {
+        loop {
+            let request = match self.messages.try_recv() {
+                Ok(request) => request,
+                Err(TryRecvError::Empty) => return Ok(self.pause_requested),
+                Err(TryRecvError::Disconnected) => {
+                    self.disconnected = Some(Resume::Kill);
+                    return Ok(true);
+                }
+            };
+
+            if let Some(resume @ Resume::Detach) | Some(resume @ Resume::Kill) =
+                self.handle(&request, state, debugger)?
+            {
+                self.disconnected = Some(resume);
+                return Ok(true);
+            }
+        }
+    }
+
+    fn serve(&mut self, state: &mut State, debugger: &mut Debugger) -> io::Result<Resume> {
+        if let Some(resume) = self.disconnected.take() {
+            return Ok(resume);
+        }
+
+        let breakpoint =
+            !debugger.breakpoints_hit(state).is_empty() && self.step_from.take() != Some(state.pc);
+
+        if self.stepping.is_some()
+            && !self.step_done(state)
+            && !breakpoint
+            && !self.interrupted(state, debugger)?
+        {
+            self.step_from = Some(state.pc);
+            return Ok(Resume::Step);
+        }
+
+        if let Some(resume) = self.disconnected.take() {
+            return Ok(resume);
+        }
+
+        if self.running {
+            let reason = if self.pause_requested {
+                "pause"
+            } else if breakpoint {
+                "breakpoint"
+            } else if self.stepping.is_some() {
+                "step"
+            } else {
+                "data breakpoint"
+            };
+
+            self.event(
+                "stopped",
+                json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }),
+            )?;
+
+            self.running = false;
+            self.stepping = None;
+            self.pause_requested = false;
+        }
+
+        loop {
+            let request = match self.messages.recv() {
+                Ok(request) => request,
+                Err(_) => return Ok(Resume::Kill),
+            };
+
+            if let Some(resume) = self.handle(&request, state, debugger)? {
+                return Ok(resume);
+            }
+        }
+    }
+}
+
+impl Drop for DapServer {
+    fn drop(&mut self) {
+        let _ = self.event("terminated", Value::Null);
+    }
+}
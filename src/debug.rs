@@ -0,0 +1,392 @@
+use crate::eval::{AccessKind, EvalError};
+use crate::expr::Condition;
+use crate::symbols::{Symbolic, Symbols};
+use crate::types::*;
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Access {
+    Fetch,
+    Read,
+    Write,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access,
+}
+
+impl WatchKind {
+    fn matches(self, access: Access) -> bool {
+        match self {
+            WatchKind::Read => access != Access::Write,
+            WatchKind::Write => access == Access::Write,
+            WatchKind::Access => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Watchpoint {
+    pub range: RangeInclusive<u16>,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    /// Parses `ADDR[-ADDR][:r|w|rw]`, e.g. `2F0-2F2:w` or `score:w`.
+    pub fn parse(s: &str, symbols: &Symbols) -> Result<Self, String> {
+        let (range, kind) = match s.find(':') {
+            Some(split) => (&s[..split], &s[split + 1..]),
+            None => (s, "rw"),
+        };
+
+        let kind = match kind {
+            "r" => WatchKind::Read,
+            "w" => WatchKind::Write,
+            "rw" | "a" => WatchKind::Access,
+            _ => return Err(format!("unknown watchpoint kind {:?}", kind)),
+        };
+
+        let range = symbols.parse_range(range)?;
+
+        Ok(Watchpoint {
+            range: range.start().0..=range.end().0,
+            kind,
+        })
+    }
+}
+
+impl FromStr for Watchpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Watchpoint::parse(s, &Symbols::new())
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "r",
+            WatchKind::Write => "w",
+            WatchKind::Access => "rw",
+        };
+
+        write!(
+            f,
+            "{:03X}-{:03X}:{}",
+            self.range.start(),
+            self.range.end(),
+            kind
+        )
+    }
+}
+
+/// Pauses before the instruction at `address` runs, if `condition` holds.
+/// Either may be left out, but not both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Breakpoint {
+    pub address: Option<Address>,
+    pub condition: Option<Condition>,
+}
+
+impl Breakpoint {
+    pub fn hit(&self, state: &State) -> bool {
+        if let Some(addr) = self.address {
+            if addr != state.pc {
+                return false;
+            }
+        }
+
+        match &self.condition {
+            Some(condition) => condition.holds(state),
+            None => true,
+        }
+    }
+}
+
+impl Breakpoint {
+    /// Parses `ADDR`, `ADDR if EXPR` or `if EXPR`.
+    pub fn parse(s: &str, symbols: &Symbols) -> Result<Self, String> {
+        let s = s.trim();
+
+        let (address, condition) = if let Some(condition) = s.strip_prefix("if ") {
+            (None, Some(condition))
+        } else {
+            match s.find(" if ") {
+                Some(split) => (Some(&s[..split]), Some(&s[split + 4..])),
+                None => (Some(s), None),
+            }
+        };
+
+        Ok(Breakpoint {
+            address: address
+                .map(|addr| symbols.parse_address(addr))
+                .transpose()?,
+            condition: condition
+                .map(|condition| Condition::parse(condition, symbols))
+                .transpose()?,
+        })
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Breakpoint::parse(s, &Symbols::new())
+    }
+}
+
+impl fmt::Display for Symbolic<'_, Breakpoint> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Symbolic(breakpoint, symbols) = *self;
+
+        match (&breakpoint.address, &breakpoint.condition) {
+            (Some(addr), Some(condition)) => {
+                write!(f, "{} if {}", Symbolic(addr, symbols), condition)
+            }
+            (Some(addr), None) => write!(f, "{}", Symbolic(addr, symbols)),
+            (None, Some(condition)) => write!(f, "if {}", condition),
+            (None, None) => write!(f, "always"),
+        }
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Symbolic(self, &Symbols::new()).fmt(f)
+    }
+}
+
+/// Why the debugger wants execution to pause.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stop {
+    Watch {
+        watchpoint: usize,
+        pc: Address,
+        addr: Address,
+        access: Access,
+        old: u8,
+        new: u8,
+    },
+    Breakpoint {
+        breakpoint: usize,
+        pc: Address,
+    },
+    Register {
+        reg: Register,
+        pc: Address,
+        old: u8,
+        new: u8,
+    },
+}
+
+impl fmt::Display for Symbolic<'_, Stop> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Symbolic(stop, symbols) = *self;
+
+        match stop {
+            Stop::Watch {
+                watchpoint,
+                pc,
+                addr,
+                access,
+                old,
+                new,
+            } => {
+                let access = match access {
+                    Access::Fetch => "fetch",
+                    Access::Read => "read",
+                    Access::Write => "write",
+                };
+
+                write!(
+                    f,
+                    "watchpoint {}: {} of {} at pc {}",
+                    watchpoint,
+                    access,
+                    Symbolic(addr, symbols),
+                    Symbolic(pc, symbols)
+                )?;
+
+                if old == new {
+                    write!(f, " ({:#04X})", old)
+                } else {
+                    write!(f, " ({:#04X} -> {:#04X})", old, new)
+                }
+            }
+            Stop::Breakpoint { breakpoint, pc } => write!(
+                f,
+                "breakpoint {} at pc {}",
+                breakpoint,
+                Symbolic(pc, symbols)
+            ),
+            Stop::Register { reg, pc, old, new } => write!(
+                f,
+                "{} changed at pc {} ({:#04X} -> {:#04X})",
+                reg,
+                Symbolic(pc, symbols),
+                old,
+                new
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Symbolic(self, &Symbols::new()).fmt(f)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Debugger {
+    pub watchpoints: Vec<Watchpoint>,
+    pub breakpoints: Vec<Breakpoint>,
+    pub watched_registers: Vec<Register>,
+    /// Used to name addresses in output and to read typed ones.
+    pub symbols: Symbols,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Breakpoints that stop the instruction at `state.pc` from running.
+    pub fn breakpoints_hit(&self, state: &State) -> Vec<Stop> {
+        self.breakpoints
+            .iter()
+            .enumerate()
+            .filter(|(_, breakpoint)| breakpoint.hit(state))
+            .map(|(i, _)| Stop::Breakpoint {
+                breakpoint: i,
+                pc: state.pc,
+            })
+            .collect()
+    }
+
+    /// Evaluates `instr`, just fetched from `state.pc`, and reports every
+    /// watchpoint hit by the fetch or by the instruction's own memory access,
+    /// every watched register it changed, and any breakpoint on the next
+    /// instruction if it moved PC.
+    pub fn eval(&mut self, instr: &Instruction, state: &mut State) -> Result<Vec<Stop>, EvalError> {
+        if self.watchpoints.is_empty()
+            && self.breakpoints.is_empty()
+            && self.watched_registers.is_empty()
+        {
+            return instr.eval(state).map(|()| Vec::new());
+        }
+
+        let pc = state.pc;
+        let registers = state.registers;
+
+        let mut accesses = vec![(pc, Access::Fetch), (pc.wrapping_add(1), Access::Fetch)];
+        if let Some(access) = instr.memory_access(state) {
+            let kind = match access.kind {
+                AccessKind::Read => Access::Read,
+                AccessKind::Write => Access::Write,
+            };
+
+            accesses.extend(access.addresses().map(|addr| (addr, kind)));
+        }
+
+        let old: Vec<u8> = accesses
+            .iter()
+            .map(|(addr, _)| state.memory[addr.index()])
+            .collect();
+
+        instr.eval(state)?;
+
+        let mut stops = Vec::new();
+
+        for ((addr, access), old) in accesses.into_iter().zip(old) {
+            for (i, watchpoint) in self.watchpoints.iter().enumerate() {
+                if !watchpoint.range.contains(&addr.0) || !watchpoint.kind.matches(access) {
+                    continue;
+                }
+
+                // Both bytes of an instruction are one fetch.
+                let fetched = stops.iter().any(|stop| match stop {
+                    Stop::Watch {
+                        watchpoint,
+                        access: Access::Fetch,
+                        ..
+                    } => *watchpoint == i,
+                    _ => false,
+                });
+                if access == Access::Fetch && fetched {
+                    continue;
+                }
+
+                stops.push(Stop::Watch {
+                    watchpoint: i,
+                    pc,
+                    addr,
+                    access,
+                    old,
+                    new: state.memory[addr.index()],
+                });
+            }
+        }
+
+        for reg in &self.watched_registers {
+            if registers[*reg] != state.registers[*reg] {
+                stops.push(Stop::Register {
+                    reg: *reg,
+                    pc,
+                    old: registers[*reg],
+                    new: state.registers[*reg],
+                });
+            }
+        }
+
+        // FX0A waiting for a key and a deferred draw leave PC where it was;
+        // stopping there again would never let a continue make progress.
+        if state.pc != pc {
+            stops.extend(self.breakpoints_hit(state));
+        }
+
+        Ok(stops)
+    }
+}
+
+/// What a frontend asked for once it is done inspecting a stopped machine.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Resume {
+    Continue,
+    Step,
+    /// The client went away; keep running without it.
+    Detach,
+    Kill,
+}
+
+/// A remote client driving the debugger, such as gdb or an editor.
+pub trait Frontend {
+    /// Called between instructions while running, to see whether the client
+    /// wants to pause.
+    fn interrupted(&mut self, state: &mut State, debugger: &mut Debugger) -> io::Result<bool>;
+
+    /// Reports that the machine stopped, then answers requests until the
+    /// client resumes it.
+    fn serve(&mut self, state: &mut State, debugger: &mut Debugger) -> io::Result<Resume>;
+}
+
+/// One-line summary of the CPU state for debugger output.
+pub fn format_registers(state: &State) -> String {
+    let mut line = format!("PC={} I={}", state.pc, state.i_reg);
+
+    for (reg, value) in &state.registers {
+        line += &format!(" {}={:02X}", reg, value);
+    }
+
+    line += &format!(" DT={:02X} ST={:02X}", state.timer, state.sound_timer);
+
+    line
+}
@@ -0,0 +1,584 @@
+//! Turns a ROM back into Octo source. Code found by the CFG analysis becomes
+//! statements, with `loop`/`again` and `if ... begin`/`else`/`end` recovered
+//! from jumps where they nest properly, and everything else becomes data.
+//! Anything Octo can't spell directly is written as raw bytes, so the output
+//! always assembles back to the same binary.
+
+use crate::cfg::{is_skip, Cfg};
+use crate::symbols::Symbols;
+use crate::types::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::ops::Range;
+
+const KEYWORDS: &[&str] = &[
+    "main", "clear", "return", "jump", "jump0", "loop", "again", "while", "if", "then", "begin",
+    "else", "end", "key", "native", "sprite", "bcd", "save", "load", "delay", "buzzer", "random",
+    "hex", "i",
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Item {
+    Code(Instruction),
+    Byte(u8),
+}
+
+/// Address ranges a structure covers, and the parts of it that other
+/// structures may nest inside.
+struct Region {
+    outer: Range<u16>,
+    body: Range<u16>,
+    alternative: Option<Range<u16>>,
+}
+
+impl Region {
+    fn contains(&self, other: &Region) -> bool {
+        let inside = |r: &Range<u16>| r.start <= other.outer.start && other.outer.end <= r.end;
+
+        inside(&self.body) || matches!(&self.alternative, Some(r) if inside(r))
+    }
+
+    fn nests_with(&self, other: &Region) -> bool {
+        let disjoint = self.outer.end <= other.outer.start || other.outer.end <= self.outer.start;
+
+        disjoint || self.contains(other) || other.contains(self)
+    }
+}
+
+fn reg(reg: Register) -> String {
+    format!("v{:x}", reg as u8)
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    let valid_start = matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_');
+    let is_register = name.len() == 2 && name.to_ascii_lowercase().starts_with('v');
+
+    valid_start
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !is_register
+        && !KEYWORDS.contains(&name)
+}
+
+struct Decompiler {
+    items: BTreeMap<u16, Item>,
+    labels: BTreeMap<u16, String>,
+    /// Labels only named because something jumps to them.
+    jump_labels: BTreeSet<u16>,
+    entries: BTreeSet<u16>,
+    sprites: BTreeSet<u16>,
+    /// `again` addresses, by the address their loop starts at.
+    loops: BTreeMap<u16, Vec<u16>>,
+    agains: BTreeSet<u16>,
+    /// Skips starting `if ... begin`.
+    begins: BTreeSet<u16>,
+    elses: BTreeSet<u16>,
+    ends: BTreeMap<u16, usize>,
+    end: u16,
+}
+
+impl Decompiler {
+    fn new(memory: &[u8], rom: Range<usize>, symbols: &Symbols) -> Self {
+        let cfg = Cfg::new(memory, rom.clone());
+        let code: BTreeMap<_, _> = cfg
+            .blocks
+            .values()
+            .flat_map(|block| block.instructions.iter())
+            .map(|(addr, instr)| (addr.0, *instr))
+            .collect();
+
+        let mut items = BTreeMap::new();
+        let mut addr = rom.start as u16;
+        let end = rom.end as u16;
+
+        while addr < end {
+            match code.get(&addr) {
+                Some(instr) if addr + 2 <= end => {
+                    items.insert(addr, Item::Code(*instr));
+                    addr += 2;
+                }
+                _ => {
+                    items.insert(addr, Item::Byte(memory[usize::from(addr)]));
+                    addr += 1;
+                }
+            }
+        }
+
+        let mut decompiler = Decompiler {
+            items,
+            labels: BTreeMap::new(),
+            jump_labels: BTreeSet::new(),
+            entries: cfg.subroutines.keys().map(|addr| addr.0).collect(),
+            sprites: cfg.data.iter().map(|addr| addr.0).collect(),
+            loops: BTreeMap::new(),
+            agains: BTreeSet::new(),
+            begins: BTreeSet::new(),
+            elses: BTreeSet::new(),
+            ends: BTreeMap::new(),
+            end,
+        };
+
+        decompiler.name_labels(&cfg, symbols);
+        decompiler.find_structures();
+        decompiler
+    }
+
+    fn name_labels(&mut self, cfg: &Cfg, symbols: &Symbols) {
+        let mut targets = BTreeMap::new();
+
+        for item in self.items.values() {
+            let (addr, kind) = match item {
+                Item::Code(Instruction::Call(addr)) => (addr, "sub"),
+                Item::Code(Instruction::SetAddr(addr)) => (addr, "data"),
+                Item::Code(Instruction::Goto(addr))
+                | Item::Code(Instruction::IndexedJump(addr)) => (addr, "label"),
+                _ => continue,
+            };
+
+            let existing = targets.entry(addr.0).or_insert(kind);
+            if kind != "label" {
+                *existing = kind;
+            }
+        }
+
+        for addr in &self.entries {
+            targets.insert(*addr, "sub");
+        }
+
+        for (name, addr) in symbols.iter() {
+            if self.items.contains_key(&addr.0) && is_identifier(name) {
+                targets.entry(addr.0).or_insert("label");
+            }
+        }
+
+        targets.insert(cfg.entry.0, "main");
+
+        let mut used = BTreeSet::new();
+
+        for (addr, kind) in targets {
+            if !self.items.contains_key(&addr) {
+                continue;
+            }
+
+            let symbol = symbols
+                .locate(Address(addr))
+                .filter(|(name, offset)| *offset == 0 && is_identifier(name))
+                .map(|(name, _)| name.to_string());
+
+            let name = match (kind, symbol) {
+                ("main", _) => "main".to_string(),
+                (_, Some(name)) if !used.contains(&name) => name,
+                (kind, _) => {
+                    if kind == "label" {
+                        self.jump_labels.insert(addr);
+                    }
+                    format!("{}_{:03X}", kind, addr)
+                }
+            };
+
+            used.insert(name.clone());
+            self.labels.insert(addr, name);
+        }
+    }
+
+    fn code(&self, addr: u16) -> Option<Instruction> {
+        match self.items.get(&addr) {
+            Some(Item::Code(instr)) => Some(*instr),
+            _ => None,
+        }
+    }
+
+    fn is_skip_at(&self, addr: u16) -> bool {
+        matches!(self.code(addr), Some(instr) if is_skip(&instr))
+    }
+
+    /// Whether `addr` is somewhere an `end` can go.
+    fn is_boundary(&self, addr: u16) -> bool {
+        addr == self.end || self.items.contains_key(&addr)
+    }
+
+    fn crosses_entry(&self, range: Range<u16>) -> bool {
+        self.entries
+            .range(range.start + 1..range.end)
+            .next()
+            .is_some()
+    }
+
+    fn find_structures(&mut self) {
+        enum Kind {
+            Loop { start: u16, again: u16 },
+            Begin { skip: u16, end: u16 },
+            Else { skip: u16, jump: u16, end: u16 },
+        }
+
+        let mut candidates = Vec::new();
+
+        for (&addr, item) in &self.items {
+            let target = match item {
+                Item::Code(Instruction::Goto(target)) => target.0,
+                _ => continue,
+            };
+
+            if target <= addr
+                && self.items.contains_key(&target)
+                && addr >= 2
+                && !self.is_skip_at(addr - 2)
+                && !self.crosses_entry(target..addr + 1)
+            {
+                let region = Region {
+                    outer: target..addr + 2,
+                    body: target..addr,
+                    alternative: None,
+                };
+                candidates.push((
+                    region,
+                    Kind::Loop {
+                        start: target,
+                        again: addr,
+                    },
+                ));
+            }
+
+            let skip = addr.wrapping_sub(2);
+            let body = addr + 2;
+
+            if target >= body
+                && self.is_skip_at(skip)
+                && !(skip >= 2 && self.is_skip_at(skip - 2))
+                && !self.labels.contains_key(&addr)
+                && self.is_boundary(target)
+                && !self.crosses_entry(skip..target)
+            {
+                let else_at = target - 2;
+
+                let else_end = match self.code(else_at) {
+                    Some(Instruction::Goto(end))
+                        if else_at >= body
+                            && end.0 > target
+                            && self.is_boundary(end.0)
+                            && !self.is_skip_at(else_at.wrapping_sub(2))
+                            && !self.crosses_entry(skip..end.0) =>
+                    {
+                        Some(end.0)
+                    }
+                    _ => None,
+                };
+
+                if let Some(end) = else_end {
+                    let region = Region {
+                        outer: skip..end,
+                        body: body..else_at,
+                        alternative: Some(target..end),
+                    };
+                    candidates.push((
+                        region,
+                        Kind::Else {
+                            skip,
+                            jump: else_at,
+                            end,
+                        },
+                    ));
+                }
+
+                let region = Region {
+                    outer: skip..target,
+                    body: body..target,
+                    alternative: None,
+                };
+                candidates.push((region, Kind::Begin { skip, end: target }));
+            }
+        }
+
+        // Prefer small structures, which are the most likely to be real, and
+        // an `else` over a plain `begin` on the same skip.
+        candidates.sort_by_key(|(region, kind)| match kind {
+            Kind::Else { skip, .. } => region.body.end + 2 - skip,
+            _ => region.outer.end - region.outer.start,
+        });
+
+        let mut accepted: Vec<Region> = Vec::new();
+        let mut claimed = BTreeSet::new();
+
+        for (region, kind) in candidates {
+            let jumps = match kind {
+                Kind::Loop { again, .. } => vec![again],
+                Kind::Begin { skip, .. } => vec![skip + 2],
+                Kind::Else { skip, jump, .. } => vec![skip + 2, jump],
+            };
+
+            if jumps.iter().any(|jump| claimed.contains(jump))
+                || !accepted.iter().all(|other| other.nests_with(&region))
+            {
+                continue;
+            }
+
+            claimed.extend(jumps);
+            accepted.push(region);
+
+            match kind {
+                Kind::Loop { start, again } => {
+                    self.loops.entry(start).or_default().push(again);
+                    self.agains.insert(again);
+                }
+                Kind::Begin { skip, end } => {
+                    self.begins.insert(skip);
+                    *self.ends.entry(end).or_default() += 1;
+                }
+                Kind::Else { skip, jump, end } => {
+                    self.begins.insert(skip);
+                    self.elses.insert(jump);
+                    *self.ends.entry(end).or_default() += 1;
+                }
+            }
+        }
+
+        for agains in self.loops.values_mut() {
+            agains.sort_by(|a, b| b.cmp(a));
+        }
+
+        // Jumps that became `again` or `else` don't need their labels.
+        let mut referenced = BTreeSet::new();
+
+        for (&addr, item) in &self.items {
+            match item {
+                Item::Code(Instruction::Goto(target))
+                    if !self.agains.contains(&addr)
+                        && !self.elses.contains(&addr)
+                        && !self.begins.contains(&addr.wrapping_sub(2)) =>
+                {
+                    referenced.insert(target.0);
+                }
+                Item::Code(Instruction::IndexedJump(target)) => {
+                    referenced.insert(target.0);
+                }
+                _ => {}
+            }
+        }
+
+        let unused: Vec<_> = self.jump_labels.difference(&referenced).cloned().collect();
+        for addr in unused {
+            self.labels.remove(&addr);
+        }
+    }
+
+    fn target(&self, addr: Address) -> String {
+        match self.labels.get(&addr.0) {
+            Some(label) => label.clone(),
+            None => format!("0x{:03X}", addr.0),
+        }
+    }
+
+    /// The condition under which a skip at `addr` doesn't skip.
+    fn condition(instr: &Instruction, begin: bool) -> String {
+        use Instruction::*;
+
+        let (eq, ne, key, nkey) = if begin {
+            ("==", "!=", "key", "-key")
+        } else {
+            ("!=", "==", "-key", "key")
+        };
+
+        match *instr {
+            SkipEqImm(r, n) => format!("{} {} 0x{:02X}", reg(r), eq, n),
+            SkipNeqImm(r, n) => format!("{} {} 0x{:02X}", reg(r), ne, n),
+            SkipEqReg(r1, r2) => format!("{} {} {}", reg(r1), eq, reg(r2)),
+            SkipNeqReg(r1, r2) => format!("{} {} {}", reg(r1), ne, reg(r2)),
+            SkipPressed(r) => format!("{} {}", reg(r), key),
+            SkipUnpressed(r) => format!("{} {}", reg(r), nkey),
+            _ => unreachable!(),
+        }
+    }
+
+    /// The Octo statement for `instr`, if there is one.
+    fn statement(&self, instr: &Instruction) -> Option<String> {
+        use Instruction::*;
+
+        Some(match *instr {
+            RcaCall(_) => return None,
+            ClearDisplay => "clear".to_string(),
+            Return => "return".to_string(),
+            Goto(addr) => format!("jump {}", self.target(addr)),
+            Call(addr) => self.labels.get(&addr.0)?.clone(),
+            SetImm(r, n) => format!("{} := 0x{:02X}", reg(r), n),
+            AddImm(r, n) => format!("{} += 0x{:02X}", reg(r), n),
+            SetReg(r1, r2) => format!("{} := {}", reg(r1), reg(r2)),
+            OrReg(r1, r2) => format!("{} |= {}", reg(r1), reg(r2)),
+            AndReg(r1, r2) => format!("{} &= {}", reg(r1), reg(r2)),
+            XorReg(r1, r2) => format!("{} ^= {}", reg(r1), reg(r2)),
+            AddReg(r1, r2) => format!("{} += {}", reg(r1), reg(r2)),
+            SubReg(r1, r2) => format!("{} -= {}", reg(r1), reg(r2)),
+            RShiftReg(r1, r2) => format!("{} >>= {}", reg(r1), reg(r2)),
+            RevSubReg(r1, r2) => format!("{} =- {}", reg(r1), reg(r2)),
+            LShiftReg(r1, r2) => format!("{} <<= {}", reg(r1), reg(r2)),
+            SetAddr(addr) => format!("i := {}", self.target(addr)),
+            IndexedJump(addr) => format!("jump0 {}", self.target(addr)),
+            Rand(r, n) => format!("{} := random 0x{:02X}", reg(r), n),
+            Draw(r1, r2, h) => format!("sprite {} {} {}", reg(r1), reg(r2), h),
+            GetTimer(r) => format!("{} := delay", reg(r)),
+            WaitPress(r) => format!("{} := key", reg(r)),
+            SetTimer(r) => format!("delay := {}", reg(r)),
+            SetSoundTimer(r) => format!("buzzer := {}", reg(r)),
+            AddAddr(r) => format!("i += {}", reg(r)),
+            SpriteAddr(r) => format!("i := hex {}", reg(r)),
+            BCD(r) => format!("bcd {}", reg(r)),
+            RegDump(r) => format!("save {}", reg(r)),
+            RegLoad(r) => format!("load {}", reg(r)),
+            _ => return None,
+        })
+    }
+
+    /// Whether the item at `addr` can follow `then`: a plain statement with
+    /// nothing else attached to its address.
+    fn can_follow_then(&self, addr: u16) -> bool {
+        let plain = match self.code(addr) {
+            Some(instr) => !is_skip(&instr) && self.statement(&instr).is_some(),
+            None => false,
+        };
+
+        plain
+            && !self.labels.contains_key(&addr)
+            && !self.loops.contains_key(&addr)
+            && !self.ends.contains_key(&addr)
+            && !self.agains.contains(&addr)
+            && !self.elses.contains(&addr)
+    }
+
+    fn write<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let raw = |instr: &Instruction| {
+            let [hi, lo] = instr.encode().to_be_bytes();
+            format!("0x{:02X} 0x{:02X}", hi, lo)
+        };
+
+        let mut depth = 0;
+        let mut skip_next = false;
+        let mut then = false;
+        let mut data = Vec::new();
+        let mut sprite = false;
+
+        if let Some((&start, _)) = self.items.iter().next() {
+            if start != 0x200 {
+                writeln!(out, ":org 0x{:03X}", start)?;
+            }
+        }
+
+        for (&addr, item) in &self.items {
+            let marked = self.labels.contains_key(&addr)
+                || self.ends.contains_key(&addr)
+                || self.loops.contains_key(&addr)
+                || matches!(item, Item::Code(_));
+
+            if marked && !data.is_empty() {
+                write_data(&mut data, sprite, depth, out)?;
+            }
+
+            for _ in 0..self.ends.get(&addr).cloned().unwrap_or(0) {
+                depth -= 1;
+                writeln!(out, "{}end", "\t".repeat(depth + 1))?;
+            }
+
+            if let Some(label) = self.labels.get(&addr) {
+                let first = addr == *self.items.keys().next().unwrap();
+                if !first && (self.entries.contains(&addr) || self.sprites.contains(&addr)) {
+                    writeln!(out)?;
+                }
+                writeln!(out, ": {}", label)?;
+                sprite = self.sprites.contains(&addr);
+            }
+
+            for _ in self.loops.get(&addr).into_iter().flatten() {
+                writeln!(out, "{}loop", "\t".repeat(depth + 1))?;
+                depth += 1;
+            }
+
+            let instr = match item {
+                Item::Byte(byte) => {
+                    data.push(*byte);
+                    continue;
+                }
+                Item::Code(instr) => instr,
+            };
+
+            sprite = false;
+
+            if skip_next {
+                skip_next = false;
+                continue;
+            }
+
+            let indent = if then {
+                " ".to_string()
+            } else {
+                "\t".repeat(depth + 1)
+            };
+
+            if self.begins.contains(&addr) {
+                writeln!(out, "{}if {} begin", indent, Self::condition(instr, true))?;
+                depth += 1;
+                skip_next = true;
+            } else if self.elses.contains(&addr) {
+                writeln!(out, "{}else", "\t".repeat(depth))?;
+            } else if self.agains.contains(&addr) {
+                depth -= 1;
+                writeln!(out, "{}again", "\t".repeat(depth + 1))?;
+            } else if is_skip(instr) && self.can_follow_then(addr + 2) {
+                write!(out, "{}if {} then", indent, Self::condition(instr, false))?;
+                then = true;
+                continue;
+            } else {
+                match self.statement(instr) {
+                    Some(statement) => writeln!(out, "{}{}", indent, statement)?,
+                    None => writeln!(out, "{}{}", indent, raw(instr))?,
+                }
+            }
+
+            then = false;
+        }
+
+        if !data.is_empty() {
+            write_data(&mut data, sprite, depth, out)?;
+        }
+
+        for _ in 0..self.ends.get(&self.end).cloned().unwrap_or(0) {
+            depth -= 1;
+            writeln!(out, "{}end", "\t".repeat(depth + 1))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes out and clears bytes that aren't code, a row per line for sprites.
+fn write_data<W: Write>(
+    data: &mut Vec<u8>,
+    sprite: bool,
+    depth: usize,
+    out: &mut W,
+) -> io::Result<()> {
+    let indent = "\t".repeat(depth + 1);
+
+    if sprite {
+        for byte in data.iter() {
+            writeln!(out, "{}0b{:08b}", indent, byte)?;
+        }
+    } else {
+        for chunk in data.chunks(8) {
+            let bytes: Vec<_> = chunk.iter().map(|b| format!("0x{:02X}", b)).collect();
+            writeln!(out, "{}{}", indent, bytes.join(" "))?;
+        }
+    }
+
+    data.clear();
+    Ok(())
+}
+
+/// Writes the program loaded into `rom` as Octo source, naming labels after
+/// `symbols` where they're valid identifiers.
+pub fn decompile<W: Write>(
+    memory: &[u8],
+    rom: Range<usize>,
+    symbols: &Symbols,
+    out: &mut W,
+) -> io::Result<()> {
+    Decompiler::new(memory, rom, symbols).write(out)
+}
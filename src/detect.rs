@@ -0,0 +1,353 @@
+//! Guesses which platform an unknown ROM targets, and which quirks it
+//! expects, from the instructions it uses.
+
+use crate::cfg::{Cfg, Issue};
+use crate::parser;
+use crate::symbols::{Symbolic, Symbols};
+use crate::types::*;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// Instructions only some platforms have.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Feature {
+    /// 00FE and 00FF.
+    HiRes,
+    /// 00CN, 00FB and 00FC.
+    Scroll,
+    /// 00FD.
+    Exit,
+    /// DXY0.
+    LargeSprite,
+    /// FX30.
+    LargeFont,
+    /// FX75 and FX85.
+    Flags,
+    /// 00DN.
+    ScrollUp,
+    /// 5XY2 and 5XY3.
+    RegisterRange,
+    /// F000 NNNN.
+    LongIndex,
+    /// FN01.
+    Planes,
+    /// F002 and FX3A.
+    Audio,
+}
+
+impl Feature {
+    fn from_word(word: u16) -> Option<Self> {
+        let x = (word >> 8) & 0xF;
+
+        match word {
+            0x00FE | 0x00FF => Some(Feature::HiRes),
+            0x00C0..=0x00CF | 0x00FB | 0x00FC => Some(Feature::Scroll),
+            0x00FD => Some(Feature::Exit),
+            0x00D0..=0x00DF => Some(Feature::ScrollUp),
+            0xF000 => Some(Feature::LongIndex),
+            0xF002 => Some(Feature::Audio),
+            _ => match (word >> 12, word & 0xFF) {
+                (0xD, n) if n & 0xF == 0 => Some(Feature::LargeSprite),
+                (0x5, n) if n & 0xF == 2 || n & 0xF == 3 => Some(Feature::RegisterRange),
+                (0xF, 0x30) => Some(Feature::LargeFont),
+                (0xF, 0x75) | (0xF, 0x85) if x < 8 => Some(Feature::Flags),
+                (0xF, 0x01) => Some(Feature::Planes),
+                (0xF, 0x3A) => Some(Feature::Audio),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn platform(self) -> Platform {
+        match self {
+            Feature::HiRes
+            | Feature::Scroll
+            | Feature::Exit
+            | Feature::LargeSprite
+            | Feature::LargeFont
+            | Feature::Flags => Platform::SuperChip,
+            Feature::ScrollUp
+            | Feature::RegisterRange
+            | Feature::LongIndex
+            | Feature::Planes
+            | Feature::Audio => Platform::XoChip,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Feature::HiRes => "high resolution (00FE/00FF)",
+            Feature::Scroll => "scrolling (00CN/00FB/00FC)",
+            Feature::Exit => "exit (00FD)",
+            Feature::LargeSprite => "16x16 sprites (DXY0)",
+            Feature::LargeFont => "large font (FX30)",
+            Feature::Flags => "flag registers (FX75/FX85)",
+            Feature::ScrollUp => "scrolling up (00DN)",
+            Feature::RegisterRange => "register ranges (5XY2/5XY3)",
+            Feature::LongIndex => "long index (F000 NNNN)",
+            Feature::Planes => "bitplanes (FN01)",
+            Feature::Audio => "audio (F002/FX3A)",
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Hit {
+    pub feature: Feature,
+    pub addr: Address,
+    /// Whether control flow reaches it, rather than it perhaps being data.
+    pub reachable: bool,
+}
+
+/// Code that suggests how a quirk should be set.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Hint {
+    /// 8XY6/8XYE with VY other than VX and V0, meant to shift VY.
+    ShiftsVy(Address),
+    /// 8X06/8X0E, shifting VX in place as SCHIP does.
+    ShiftsInPlace(Address),
+    /// Two FX55s or two FX65s without setting I in between, meant to fill
+    /// consecutive memory.
+    IncrementsI(Address),
+    /// FX55 and FX65 without setting I in between, meant to read back what
+    /// was written.
+    KeepsI(Address),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Confidence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Confidence::Low => "low",
+            Confidence::Medium => "medium",
+            Confidence::High => "high",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Detection {
+    pub platform: Platform,
+    pub confidence: Confidence,
+    pub quirks: Quirks,
+    pub hits: Vec<Hit>,
+    pub hints: Vec<Hint>,
+}
+
+/// How sure `hits` make us that the ROM needs `platform`.
+fn confidence(hits: &[Hit], platform: Platform) -> Option<Confidence> {
+    let hits: Vec<_> = hits
+        .iter()
+        .filter(|hit| hit.feature.platform() == platform)
+        .collect();
+    let features: BTreeSet<_> = hits.iter().map(|hit| hit.feature).collect();
+
+    if hits.iter().any(|hit| hit.reachable) {
+        Some(Confidence::High)
+    } else if hits.len() >= 3 || features.len() >= 2 {
+        Some(Confidence::Medium)
+    } else if !hits.is_empty() {
+        Some(Confidence::Low)
+    } else {
+        None
+    }
+}
+
+fn hints(cfg: &Cfg) -> Vec<Hint> {
+    use Instruction::*;
+
+    let mut hints = Vec::new();
+
+    for block in cfg.blocks.values() {
+        let mut last_load_store = None;
+
+        for (addr, instr) in &block.instructions {
+            match instr {
+                RShiftReg(x, y) | LShiftReg(x, y) if x != y => hints.push(if *y == Register::V0 {
+                    Hint::ShiftsInPlace(*addr)
+                } else {
+                    Hint::ShiftsVy(*addr)
+                }),
+                RegDump(_) | RegLoad(_) => {
+                    let dump = matches!(instr, RegDump(_));
+                    match last_load_store {
+                        Some(last) if last == dump => hints.push(Hint::IncrementsI(*addr)),
+                        Some(_) => hints.push(Hint::KeepsI(*addr)),
+                        None => {}
+                    }
+                }
+                _ => {}
+            }
+
+            match instr {
+                RegDump(_) => last_load_store = Some(true),
+                RegLoad(_) => last_load_store = Some(false),
+                SetAddr(_) | AddAddr(_) | SpriteAddr(_) => last_load_store = None,
+                _ => {}
+            }
+        }
+    }
+
+    hints.sort_by_key(|hint| match hint {
+        Hint::ShiftsVy(addr)
+        | Hint::ShiftsInPlace(addr)
+        | Hint::IncrementsI(addr)
+        | Hint::KeepsI(addr) => *addr,
+    });
+    hints
+}
+
+/// Scans the ROM loaded at `rom` in `memory`.
+pub fn detect(memory: &[u8], rom: Range<usize>) -> Detection {
+    let cfg = Cfg::new(memory, rom.clone());
+
+    // Control flow stops at instructions the parser doesn't know, which
+    // includes most of the ones we're looking for.
+    let reachable: BTreeSet<Address> = cfg
+        .blocks
+        .values()
+        .flat_map(|block| block.instructions.iter().map(|(addr, _)| *addr))
+        .chain(cfg.issues.iter().filter_map(|issue| match issue {
+            Issue::InvalidInstruction(addr) => Some(*addr),
+            _ => None,
+        }))
+        .collect();
+
+    let addrs: BTreeSet<Address> = (rom.start..rom.end.saturating_sub(1))
+        .step_by(2)
+        .map(|addr| Address(addr as u16))
+        .chain(reachable.iter().copied())
+        .collect();
+
+    let mut hits = Vec::new();
+    let mut operand = None;
+
+    for addr in addrs {
+        if Some(addr) == operand {
+            continue;
+        }
+
+        let bytes = [memory[addr.index()], memory[addr.wrapping_add(1).index()]];
+        let word = u16::from_be_bytes(bytes);
+
+        let feature = match parser::instr(&bytes) {
+            Ok((_, Instruction::RcaCall(_))) | Err(_) => Feature::from_word(word),
+            Ok((_, Instruction::Draw(_, _, 0))) => Some(Feature::LargeSprite),
+            Ok(_) => None,
+        };
+
+        if let Some(feature) = feature {
+            if feature == Feature::LongIndex {
+                operand = Some(addr.wrapping_add(2));
+            }
+
+            hits.push(Hit {
+                feature,
+                addr,
+                reachable: reachable.contains(&addr),
+            });
+        }
+    }
+
+    let candidates = [Platform::XoChip, Platform::SuperChip];
+    let (platform, confidence) = candidates
+        .iter()
+        .filter_map(|&platform| Some((platform, confidence(&hits, platform)?)))
+        .find(|(_, confidence)| *confidence > Confidence::Low)
+        .unwrap_or_else(|| {
+            let clean = hits.is_empty()
+                && !cfg
+                    .issues
+                    .iter()
+                    .any(|issue| matches!(issue, Issue::InvalidInstruction(_)));
+
+            let confidence = if clean {
+                Confidence::High
+            } else {
+                Confidence::Medium
+            };
+            (Platform::Chip8, confidence)
+        });
+
+    let hints = hints(&cfg);
+    let mut quirks = platform.quirks();
+
+    let has = |hint: fn(&Hint) -> bool| hints.iter().any(hint);
+
+    let vy = has(|hint| matches!(hint, Hint::ShiftsVy(_)));
+    let in_place = has(|hint| matches!(hint, Hint::ShiftsInPlace(_)));
+    if vy != in_place {
+        quirks.shift = in_place;
+    }
+
+    let increments = has(|hint| matches!(hint, Hint::IncrementsI(_)));
+    let keeps = has(|hint| matches!(hint, Hint::KeepsI(_)));
+    if increments != keeps {
+        quirks.load_store = keeps;
+    }
+
+    Detection {
+        platform,
+        confidence,
+        quirks,
+        hits,
+        hints,
+    }
+}
+
+impl Detection {
+    pub fn write_report<W: Write>(&self, symbols: &Symbols, out: &mut W) -> io::Result<()> {
+        writeln!(
+            out,
+            "Platform: {} ({} confidence)",
+            self.platform, self.confidence
+        )?;
+
+        let features: BTreeSet<_> = self.hits.iter().map(|hit| hit.feature).collect();
+        for feature in features {
+            let hits: Vec<_> = self
+                .hits
+                .iter()
+                .filter(|hit| hit.feature == feature)
+                .collect();
+            let reachable = hits.iter().filter(|hit| hit.reachable).count();
+
+            writeln!(
+                out,
+                "    {} used {} times, {} reachable, first at {}",
+                feature,
+                hits.len(),
+                reachable,
+                Symbolic(&hits[0].addr, symbols)
+            )?;
+        }
+
+        writeln!(out, "Quirks: {}", self.quirks)?;
+
+        for hint in &self.hints {
+            let (addr, text) = match hint {
+                Hint::ShiftsVy(addr) => (addr, "shifts VY into VX, suggesting shift is off"),
+                Hint::ShiftsInPlace(addr) => (addr, "shifts VX alone, suggesting shift is on"),
+                Hint::IncrementsI(addr) => {
+                    (addr, "relies on I advancing, suggesting load_store is off")
+                }
+                Hint::KeepsI(addr) => {
+                    (addr, "relies on I staying put, suggesting load_store is on")
+                }
+            };
+            writeln!(out, "    {} {}", Symbolic(addr, symbols), text)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,111 @@
+use crate::cfg::Cfg;
+use crate::symbols::{Symbolic, Symbols};
+use crate::types::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::ops::Range;
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#05X}", self.0)
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "V{:X}", *self as u8)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+
+        match self {
+            RcaCall(addr) => write!(f, "SYS {}", addr),
+            ClearDisplay => write!(f, "CLS"),
+            Return => write!(f, "RET"),
+            Goto(addr) => write!(f, "JP {}", addr),
+            Call(addr) => write!(f, "CALL {}", addr),
+            SkipEqImm(reg, n) => write!(f, "SE {}, {:#04X}", reg, n),
+            SkipNeqImm(reg, n) => write!(f, "SNE {}, {:#04X}", reg, n),
+            SkipEqReg(r1, r2) => write!(f, "SE {}, {}", r1, r2),
+            SetImm(reg, n) => write!(f, "LD {}, {:#04X}", reg, n),
+            AddImm(reg, n) => write!(f, "ADD {}, {:#04X}", reg, n),
+            SetReg(r1, r2) => write!(f, "LD {}, {}", r1, r2),
+            OrReg(r1, r2) => write!(f, "OR {}, {}", r1, r2),
+            AndReg(r1, r2) => write!(f, "AND {}, {}", r1, r2),
+            XorReg(r1, r2) => write!(f, "XOR {}, {}", r1, r2),
+            AddReg(r1, r2) => write!(f, "ADD {}, {}", r1, r2),
+            SubReg(r1, r2) => write!(f, "SUB {}, {}", r1, r2),
+            RShiftReg(r1, r2) => write!(f, "SHR {}, {}", r1, r2),
+            RevSubReg(r1, r2) => write!(f, "SUBN {}, {}", r1, r2),
+            LShiftReg(r1, r2) => write!(f, "SHL {}, {}", r1, r2),
+            SkipNeqReg(r1, r2) => write!(f, "SNE {}, {}", r1, r2),
+            SetAddr(addr) => write!(f, "LD I, {}", addr),
+            IndexedJump(addr) => write!(f, "JP V0, {}", addr),
+            Rand(reg, n) => write!(f, "RND {}, {:#04X}", reg, n),
+            Draw(r1, r2, h) => write!(f, "DRW {}, {}, {}", r1, r2, h),
+            SkipPressed(reg) => write!(f, "SKP {}", reg),
+            SkipUnpressed(reg) => write!(f, "SKNP {}", reg),
+            GetTimer(reg) => write!(f, "LD {}, DT", reg),
+            WaitPress(reg) => write!(f, "LD {}, K", reg),
+            SetTimer(reg) => write!(f, "LD DT, {}", reg),
+            SetSoundTimer(reg) => write!(f, "LD ST, {}", reg),
+            AddAddr(reg) => write!(f, "ADD I, {}", reg),
+            SpriteAddr(reg) => write!(f, "LD F, {}", reg),
+            BCD(reg) => write!(f, "LD B, {}", reg),
+            RegDump(reg) => write!(f, "LD [I], {}", reg),
+            RegLoad(reg) => write!(f, "LD {}, [I]", reg),
+        }
+    }
+}
+
+/// Writes a listing of the ROM at `rom`, disassembling what control flow
+/// reaches and showing everything else as bytes.
+pub fn write_listing<W: Write>(
+    memory: &[u8],
+    rom: Range<usize>,
+    symbols: &Symbols,
+    out: &mut W,
+) -> io::Result<()> {
+    let cfg = Cfg::new(memory, rom.clone());
+    let code: BTreeMap<Address, Instruction> = cfg
+        .blocks
+        .values()
+        .flat_map(|block| block.instructions.iter().copied())
+        .collect();
+
+    let mut addr = rom.start;
+    while addr < rom.end {
+        let address = Address(addr as u16);
+        if let Some((name, 0)) = symbols.locate(address) {
+            writeln!(out, "{}:", name)?;
+        }
+
+        match code.get(&address) {
+            Some(instr) if addr + 1 < rom.end => {
+                writeln!(
+                    out,
+                    "{}  {:02X}{:02X}  {}",
+                    address,
+                    memory[addr],
+                    memory[addr + 1],
+                    Symbolic(instr, symbols)
+                )?;
+                addr += 2;
+            }
+            _ => {
+                writeln!(
+                    out,
+                    "{}  {:02X}    DB {:#04X}",
+                    address, memory[addr], memory[addr]
+                )?;
+                addr += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use crate::types::*;
+
+fn addr(op: u16, addr: Address) -> u16 {
+    op << 12 | (addr.0 & 0xFFF)
+}
+
+fn reg_imm(op: u16, reg: Register, n: u8) -> u16 {
+    op << 12 | (reg as u16) << 8 | n as u16
+}
+
+fn reg_reg(op: u16, r1: Register, r2: Register, n: u8) -> u16 {
+    op << 12 | (r1 as u16) << 8 | (r2 as u16) << 4 | (n & 0xF) as u16
+}
+
+impl Instruction {
+    pub fn encode(&self) -> u16 {
+        use Instruction::*;
+
+        match *self {
+            RcaCall(a) => addr(0x0, a),
+            ClearDisplay => 0x00E0,
+            Return => 0x00EE,
+            Goto(a) => addr(0x1, a),
+            Call(a) => addr(0x2, a),
+            SkipEqImm(reg, n) => reg_imm(0x3, reg, n),
+            SkipNeqImm(reg, n) => reg_imm(0x4, reg, n),
+            SkipEqReg(r1, r2) => reg_reg(0x5, r1, r2, 0x0),
+            SetImm(reg, n) => reg_imm(0x6, reg, n),
+            AddImm(reg, n) => reg_imm(0x7, reg, n),
+            SetReg(r1, r2) => reg_reg(0x8, r1, r2, 0x0),
+            OrReg(r1, r2) => reg_reg(0x8, r1, r2, 0x1),
+            AndReg(r1, r2) => reg_reg(0x8, r1, r2, 0x2),
+            XorReg(r1, r2) => reg_reg(0x8, r1, r2, 0x3),
+            AddReg(r1, r2) => reg_reg(0x8, r1, r2, 0x4),
+            SubReg(r1, r2) => reg_reg(0x8, r1, r2, 0x5),
+            RShiftReg(r1, r2) => reg_reg(0x8, r1, r2, 0x6),
+            RevSubReg(r1, r2) => reg_reg(0x8, r1, r2, 0x7),
+            LShiftReg(r1, r2) => reg_reg(0x8, r1, r2, 0xE),
+            SkipNeqReg(r1, r2) => reg_reg(0x9, r1, r2, 0x0),
+            SetAddr(a) => addr(0xA, a),
+            IndexedJump(a) => addr(0xB, a),
+            Rand(reg, n) => reg_imm(0xC, reg, n),
+            Draw(r1, r2, h) => reg_reg(0xD, r1, r2, h),
+            SkipPressed(reg) => reg_imm(0xE, reg, 0x9E),
+            SkipUnpressed(reg) => reg_imm(0xE, reg, 0xA1),
+            GetTimer(reg) => reg_imm(0xF, reg, 0x07),
+            WaitPress(reg) => reg_imm(0xF, reg, 0x0A),
+            SetTimer(reg) => reg_imm(0xF, reg, 0x15),
+            SetSoundTimer(reg) => reg_imm(0xF, reg, 0x18),
+            AddAddr(reg) => reg_imm(0xF, reg, 0x1E),
+            SpriteAddr(reg) => reg_imm(0xF, reg, 0x29),
+            BCD(reg) => reg_imm(0xF, reg, 0x33),
+            RegDump(reg) => reg_imm(0xF, reg, 0x55),
+            RegLoad(reg) => reg_imm(0xF, reg, 0x65),
+        }
+    }
+}
@@ -1,6 +1,8 @@
+use crate::parser::{self, DecodeError};
 use crate::types::*;
 use bitvec::prelude::Bits;
 use bitvec::prelude::*;
+use rand::Rng;
 
 fn bcd(n: u8) -> [u8; 3] {
     fn bcd_inner(i: u8, n: u8, xs: &mut [u8; 3]) {
@@ -16,7 +18,31 @@ fn bcd(n: u8) -> [u8; 3] {
     xs
 }
 
-#[derive(Debug)]
+/// Writes an 8XYN result and its flag in the order `Quirks::vf_order` asks for.
+fn set_with_flag(state: &mut State, reg: Register, val: u8, flag: u8) {
+    if state.quirks.vf_order {
+        state.registers[Register::VF] = flag;
+        state.registers[reg] = val;
+    } else {
+        state.registers[reg] = val;
+        state.registers[Register::VF] = flag;
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EvalError {
+    Decode(DecodeError),
+    StackUnderflow,
+    RcaCall(Address),
+}
+
+impl From<DecodeError> for EvalError {
+    fn from(err: DecodeError) -> Self {
+        EvalError::Decode(err)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Instruction {
     RcaCall(Address),              // 0NNN
     ClearDisplay,                  // 00E0
@@ -56,30 +82,81 @@ pub enum Instruction {
     RegLoad(Register),             // FX65
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    pub start: Address,
+    pub len: u16,
+}
+
+impl MemoryAccess {
+    pub fn addresses(&self) -> impl Iterator<Item = Address> {
+        let start = self.start;
+        (0..self.len).map(move |i| start.wrapping_add(i))
+    }
+}
+
 impl Instruction {
-    pub fn eval(&self, state: &mut State) {
+    /// The memory `eval` will touch when run against `state`, other than
+    /// fetching the instruction itself.
+    pub fn memory_access(&self, state: &State) -> Option<MemoryAccess> {
         use Instruction::*;
 
-        state.pc += 2.into();
+        let (kind, len) = match self {
+            Draw(_, _, h) => (AccessKind::Read, u16::from(*h)),
+            RegLoad(reg) => (AccessKind::Read, *reg as u16 + 1),
+            BCD(_) => (AccessKind::Write, 3),
+            RegDump(reg) => (AccessKind::Write, *reg as u16 + 1),
+            _ => return None,
+        };
+
+        Some(MemoryAccess {
+            kind,
+            start: state.i_reg,
+            len,
+        })
+    }
+
+    pub fn eval(&self, state: &mut State) -> Result<(), EvalError> {
+        use Instruction::*;
+
+        state.pc = state.pc.wrapping_add(2);
 
         match self {
             SetImm(reg, n) => state.registers[*reg] = *n,
             SetAddr(addr) => state.i_reg = *addr,
             Draw(x, y, h) => {
-                let x = usize::from(state.registers[*x]);
-                let y = usize::from(state.registers[*y]);
+                if state.quirks.vblank && state.drawn {
+                    state.pc = state.pc.wrapping_sub(2);
+                    return Ok(());
+                }
+                state.drawn = true;
+
+                let x = usize::from(state.registers[*x] % 64);
+                let y = usize::from(state.registers[*y] % 32);
                 let h: usize = (*h).into();
 
-                let sprite_begin: usize = u16::from(state.i_reg).into();
-                let sprite_end = sprite_begin + h;
-                let sprite = &state.memory[sprite_begin..sprite_end];
+                let mut sprite = [0u8; 15];
+                for (i, row) in sprite[..h].iter_mut().enumerate() {
+                    *row = state.memory[state.i_reg.wrapping_add(i as u16).index()];
+                }
 
                 let gfx_bits = (&mut state.bit_gfx[..]).as_mut_bitslice::<BigEndian>();
 
                 let mut collision = false;
 
-                for (yi, row) in sprite.into_iter().enumerate() {
+                for (yi, row) in sprite[..h].iter().enumerate() {
                     for (xi, bit) in row.as_bitslice::<BigEndian>().into_iter().enumerate() {
+                        if state.quirks.clip && (x + xi >= 64 || y + yi >= 32) {
+                            continue;
+                        }
+
                         if bit {
                             let idx = ((x + xi) % 64) + ((y + yi) % 32) * 64;
                             let old = gfx_bits.get(idx).unwrap();
@@ -105,9 +182,9 @@ impl Instruction {
                 let hundreds = bcd[2];
                 let tens = bcd[1];
                 let ones = bcd[0];
-                state.memory[state.i_reg.0 as usize + 0] = hundreds;
-                state.memory[state.i_reg.0 as usize + 1] = tens;
-                state.memory[state.i_reg.0 as usize + 2] = ones;
+                state.memory[state.i_reg.index()] = hundreds;
+                state.memory[state.i_reg.wrapping_add(1).index()] = tens;
+                state.memory[state.i_reg.wrapping_add(2).index()] = ones;
             }
             RegLoad(reg) => {
                 for (i, (_, reg)) in state
@@ -116,7 +193,11 @@ impl Instruction {
                     .filter(|r| r.0 <= *reg)
                     .enumerate()
                 {
-                    *reg = state.memory[state.i_reg.0 as usize + i];
+                    *reg = state.memory[state.i_reg.wrapping_add(i as u16).index()];
+                }
+
+                if !state.quirks.load_store {
+                    state.i_reg = state.i_reg.wrapping_add(*reg as u16 + 1);
                 }
             }
             RegDump(reg) => {
@@ -126,107 +207,148 @@ impl Instruction {
                     .filter(|r| r.0 <= *reg)
                     .enumerate()
                 {
-                    state.memory[state.i_reg.0 as usize + i] = *reg;
+                    state.memory[state.i_reg.wrapping_add(i as u16).index()] = *reg;
+                }
+
+                if !state.quirks.load_store {
+                    state.i_reg = state.i_reg.wrapping_add(*reg as u16 + 1);
                 }
             }
             SpriteAddr(reg) => {
-                assert!(
-                    state.registers[*reg] <= 0xF,
-                    "{:X} not in font",
-                    state.registers[*reg]
-                );
-                state.i_reg = (5u16 * (state.registers[*reg] as u16)).into();
-            }
-            AddImm(reg, n) => {
-                let (val, carry) = state.registers[*reg].overflowing_add(*n);
-                state.registers[*reg] = val;
-                state.registers[Register::VF] = if carry { 1 } else { 0 };
-            }
-            Return => state.pc = state.call_stack.pop().expect("Returned with empty stack!"),
+                state.i_reg = (5u16 * (state.registers[*reg] & 0xF) as u16).into();
+            }
+            AddImm(reg, n) => state.registers[*reg] = state.registers[*reg].wrapping_add(*n),
+            Return => state.pc = state.call_stack.pop().ok_or(EvalError::StackUnderflow)?,
             SetTimer(reg) => state.timer = state.registers[*reg],
             SetSoundTimer(reg) => state.sound_timer = state.registers[*reg],
             GetTimer(reg) => state.registers[*reg] = state.timer,
             SkipEqImm(reg, n) => {
                 if state.registers[*reg] == *n {
-                    state.pc += 2.into();
+                    state.pc = state.pc.wrapping_add(2);
                 }
             }
             SkipEqReg(r1, r2) => {
                 if state.registers[*r1] == state.registers[*r2] {
-                    state.pc += 2.into();
+                    state.pc = state.pc.wrapping_add(2);
                 }
             }
             SkipNeqImm(reg, n) => {
                 if state.registers[*reg] != *n {
-                    state.pc += 2.into();
+                    state.pc = state.pc.wrapping_add(2);
                 }
             }
             SkipNeqReg(r1, r2) => {
                 if state.registers[*r1] != state.registers[*r2] {
-                    state.pc += 2.into();
+                    state.pc = state.pc.wrapping_add(2);
                 }
             }
             Goto(addr) => state.pc = *addr,
-            Rand(reg, mask) => state.registers[*reg] = rand::random::<u8>() & mask,
+            Rand(reg, mask) => state.registers[*reg] = state.rng.gen::<u8>() & mask,
             SkipUnpressed(reg) => {
-                let button = Button::n(state.registers[*reg]).unwrap();
+                let button = Button::n(state.registers[*reg] & 0xF).unwrap();
 
                 if !state.buttons[button] {
-                    state.pc += 2.into()
+                    state.pc = state.pc.wrapping_add(2)
                 }
             }
             SkipPressed(reg) => {
-                let button = Button::n(state.registers[*reg]).unwrap();
+                let button = Button::n(state.registers[*reg] & 0xF).unwrap();
 
                 if state.buttons[button] {
-                    state.pc += 2.into()
+                    state.pc = state.pc.wrapping_add(2)
+                }
+            }
+            AndReg(r1, r2) => {
+                state.registers[*r1] &= state.registers[*r2];
+                if state.quirks.logic {
+                    state.registers[Register::VF] = 0;
+                }
+            }
+            OrReg(r1, r2) => {
+                state.registers[*r1] |= state.registers[*r2];
+                if state.quirks.logic {
+                    state.registers[Register::VF] = 0;
+                }
+            }
+            XorReg(r1, r2) => {
+                state.registers[*r1] ^= state.registers[*r2];
+                if state.quirks.logic {
+                    state.registers[Register::VF] = 0;
                 }
             }
-            AndReg(r1, r2) => state.registers[*r1] &= state.registers[*r2],
-            OrReg(r1, r2) => state.registers[*r1] |= state.registers[*r2],
-            XorReg(r1, r2) => state.registers[*r1] ^= state.registers[*r2],
             LShiftReg(r1, r2) => {
-                let msb = state.registers[*r1] >> 7;
-                state.registers[*r2] = state.registers[*r1] << 1;
-                state.registers[Register::VF] = msb;
+                let src = if state.quirks.shift { *r1 } else { *r2 };
+                let val = state.registers[src];
+                set_with_flag(state, *r1, val << 1, val >> 7);
             }
             RShiftReg(r1, r2) => {
-                let lsb = state.registers[*r1] & 1;
-                state.registers[*r2] = state.registers[*r1] >> 1;
-                state.registers[Register::VF] = lsb;
+                let src = if state.quirks.shift { *r1 } else { *r2 };
+                let val = state.registers[src];
+                set_with_flag(state, *r1, val >> 1, val & 1);
             }
             SetReg(r1, r2) => state.registers[*r1] = state.registers[*r2],
             AddReg(r1, r2) => {
                 let (val, carry) = state.registers[*r1].overflowing_add(state.registers[*r2]);
-                state.registers[*r1] = val;
-                state.registers[Register::VF] = if carry { 1 } else { 0 };
+                set_with_flag(state, *r1, val, if carry { 1 } else { 0 });
             }
             SubReg(r1, r2) => {
                 let (val, carry) = state.registers[*r1].overflowing_sub(state.registers[*r2]);
-                state.registers[*r1] = val;
-                state.registers[Register::VF] = if !carry { 1 } else { 0 };
+                set_with_flag(state, *r1, val, if !carry { 1 } else { 0 });
             }
             RevSubReg(r1, r2) => {
                 let (val, carry) = state.registers[*r2].overflowing_sub(state.registers[*r1]);
-                state.registers[*r1] = val;
-                state.registers[Register::VF] = if !carry { 1 } else { 0 };
+                set_with_flag(state, *r1, val, if !carry { 1 } else { 0 });
             }
             IndexedJump(offset) => {
-                state.pc = Address::from(state.registers[Register::V0] as u16) + *offset
+                let reg = if state.quirks.jump {
+                    Register::n((offset.0 >> 8) as u8 & 0xF).unwrap()
+                } else {
+                    Register::V0
+                };
+                state.pc = offset.wrapping_add(state.registers[reg] as u16)
             }
             WaitPress(reg) => {
                 for (button, pressed) in &state.buttons {
                     if *pressed {
                         state.registers[*reg] = button as u8;
-                        return;
+                        return Ok(());
                     }
                 }
 
-                state.pc -= 2.into();
+                state.pc = state.pc.wrapping_sub(2);
+            }
+            AddAddr(reg) => {
+                state.i_reg = Address(state.i_reg.0.wrapping_add(state.registers[*reg] as u16))
             }
-            AddAddr(reg) => state.i_reg += (state.registers[*reg] as u16).into(),
             ClearDisplay => state.bit_gfx = [0u8; 256],
-            RcaCall(_) => panic!("RCA calls not supported!"),
+            RcaCall(addr) => return Err(EvalError::RcaCall(*addr)),
         }
+
+        Ok(())
+    }
+}
+
+impl State {
+    pub fn fetch(&self) -> Result<Instruction, EvalError> {
+        let bytes = [
+            self.memory[self.pc.index()],
+            self.memory[self.pc.wrapping_add(1).index()],
+        ];
+
+        Ok(parser::decode(&bytes)?.1)
+    }
+
+    pub fn step(&mut self) -> Result<Instruction, EvalError> {
+        let instr = self.fetch()?;
+        instr.eval(self)?;
+        Ok(instr)
+    }
+
+    /// Called once per 60 Hz frame.
+    pub fn tick_timers(&mut self) {
+        self.frame += 1;
+        self.drawn = false;
+        self.timer = self.timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
     }
 }
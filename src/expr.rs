@@ -0,0 +1,293 @@
+//! A small expression language over `State`, used for conditional
+//! breakpoints, e.g. `pc == 0x2A4 && v3 > 10` or `[i + 1] != 0`.
+
+use crate::symbols::Symbols;
+use crate::types::*;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Var {
+    Pc,
+    I,
+    Reg(Register),
+    Dt,
+    St,
+    Sp,
+    Frame,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum BinOp {
+    Or,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Expr {
+    Num(i64),
+    Var(Var),
+    /// A byte of memory, `[addr]`.
+    Mem(Box<Expr>),
+    Not(Box<Expr>),
+    Neg(Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    fn eval(&self, state: &State) -> i64 {
+        match self {
+            Expr::Num(n) => *n,
+            Expr::Var(var) => match var {
+                Var::Pc => state.pc.0.into(),
+                Var::I => state.i_reg.0.into(),
+                Var::Reg(reg) => state.registers[*reg].into(),
+                Var::Dt => state.timer.into(),
+                Var::St => state.sound_timer.into(),
+                Var::Sp => state.call_stack.len() as i64,
+                Var::Frame => state.frame as i64,
+            },
+            Expr::Mem(addr) => {
+                let addr = Address(addr.eval(state) as u16);
+                state.memory[addr.index()].into()
+            }
+            Expr::Not(e) => (e.eval(state) == 0) as i64,
+            Expr::Neg(e) => e.eval(state).wrapping_neg(),
+            Expr::Binary(BinOp::Or, a, b) => (a.eval(state) != 0 || b.eval(state) != 0) as i64,
+            Expr::Binary(BinOp::And, a, b) => (a.eval(state) != 0 && b.eval(state) != 0) as i64,
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (a.eval(state), b.eval(state));
+
+                match op {
+                    BinOp::BitOr => a | b,
+                    BinOp::BitXor => a ^ b,
+                    BinOp::BitAnd => a & b,
+                    BinOp::Eq => (a == b) as i64,
+                    BinOp::Ne => (a != b) as i64,
+                    BinOp::Lt => (a < b) as i64,
+                    BinOp::Le => (a <= b) as i64,
+                    BinOp::Gt => (a > b) as i64,
+                    BinOp::Ge => (a >= b) as i64,
+                    BinOp::Add => a.wrapping_add(b),
+                    BinOp::Sub => a.wrapping_sub(b),
+                    BinOp::Or | BinOp::And => unreachable!(),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Num(i64),
+    Ident(String),
+    Op(&'static str),
+}
+
+const OPERATORS: [&str; 18] = [
+    "||", "&&", "==", "!=", "<=", ">=", "|", "^", "&", "<", ">", "+", "-", "!", "(", ")", "[", "]",
+];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = s.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_') {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            let word = &rest[..end];
+
+            if word.starts_with(|c: char| c.is_ascii_digit()) {
+                let n = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+                    Some(hex) => i64::from_str_radix(hex, 16),
+                    None => word.parse(),
+                };
+                tokens.push(Token::Num(
+                    n.map_err(|_| format!("invalid number {:?}", word))?,
+                ));
+            } else {
+                tokens.push(Token::Ident(word.to_string()));
+            }
+
+            rest = &rest[end..];
+        } else {
+            return Err(format!("unexpected character in {:?}", rest));
+        }
+
+        rest = rest.trim_start();
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    symbols: &'a Symbols,
+}
+
+impl Parser<'_> {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn expect(&mut self, op: &str) -> Result<(), String> {
+        if self.peek_op() == Some(op) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected {:?}", op))
+        }
+    }
+
+    /// Parses a left-associative chain of the operators at `level` and above.
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        const LEVELS: [&[(&str, BinOp)]; 8] = [
+            &[("||", BinOp::Or)],
+            &[("&&", BinOp::And)],
+            &[("|", BinOp::BitOr)],
+            &[("^", BinOp::BitXor)],
+            &[("&", BinOp::BitAnd)],
+            &[("==", BinOp::Eq), ("!=", BinOp::Ne)],
+            &[
+                ("<", BinOp::Lt),
+                ("<=", BinOp::Le),
+                (">", BinOp::Gt),
+                (">=", BinOp::Ge),
+            ],
+            &[("+", BinOp::Add), ("-", BinOp::Sub)],
+        ];
+
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+
+        while let Some(&(_, op)) = self
+            .peek_op()
+            .and_then(|token| LEVELS[level].iter().find(|(name, _)| *name == token))
+        {
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+
+        match token {
+            Some(Token::Num(n)) => Ok(Expr::Num(n)),
+            Some(Token::Ident(name)) => {
+                let var = match name.to_ascii_lowercase().as_str() {
+                    "pc" => Var::Pc,
+                    "i" => Var::I,
+                    "dt" => Var::Dt,
+                    "st" => Var::St,
+                    "sp" => Var::Sp,
+                    "frame" => Var::Frame,
+                    _ => match name.parse() {
+                        Ok(reg) => Var::Reg(reg),
+                        Err(_) => {
+                            return self
+                                .symbols
+                                .get(&name)
+                                .map(|addr| Expr::Num(addr.0.into()))
+                                .ok_or_else(|| format!("unknown variable {:?}", name))
+                        }
+                    },
+                };
+
+                Ok(Expr::Var(var))
+            }
+            Some(Token::Op("!")) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Op("-")) => Ok(Expr::Neg(Box::new(self.unary()?))),
+            Some(Token::Op("(")) => {
+                let e = self.binary(0)?;
+                self.expect(")")?;
+                Ok(e)
+            }
+            Some(Token::Op("[")) => {
+                let e = self.binary(0)?;
+                self.expect("]")?;
+                Ok(Expr::Mem(Box::new(e)))
+            }
+            Some(Token::Op(op)) => Err(format!("unexpected {:?}", op)),
+            None => Err("unexpected end of expression".to_string()),
+        }
+    }
+}
+
+/// A parsed expression, kept alongside the text it was parsed from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    source: String,
+    expr: Expr,
+}
+
+impl Condition {
+    pub fn value(&self, state: &State) -> i64 {
+        self.expr.eval(state)
+    }
+
+    pub fn holds(&self, state: &State) -> bool {
+        self.value(state) != 0
+    }
+
+    /// Parses `s`, reading any names that aren't variables as symbols.
+    pub fn parse(s: &str, symbols: &Symbols) -> Result<Self, String> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+            symbols,
+        };
+
+        let expr = parser.binary(0)?;
+
+        if parser.pos < parser.tokens.len() {
+            return Err(format!("unexpected {:?}", parser.tokens[parser.pos]));
+        }
+
+        Ok(Condition {
+            source: s.trim().to_string(),
+            expr,
+        })
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Condition::parse(s, &Symbols::new())
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
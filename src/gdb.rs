@@ -0,0 +1,372 @@
+//! A GDB remote serial protocol stub.
+//!
+//! Registers are exposed in the order V0-VF, I, PC, DT, ST, with I and PC
+//! sent big-endian like the rest of CHIP-8. Clients that don't know the
+//! machine can fetch the layout from `target.xml`.
+
+use crate::debug::{Breakpoint, Debugger, Frontend, Resume};
+use crate::types::*;
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+pub const SIGINT: u8 = 2;
+pub const SIGTRAP: u8 = 5;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" type="uint8"/>
+    <reg name="v1" bitsize="8" type="uint8"/>
+    <reg name="v2" bitsize="8" type="uint8"/>
+    <reg name="v3" bitsize="8" type="uint8"/>
+    <reg name="v4" bitsize="8" type="uint8"/>
+    <reg name="v5" bitsize="8" type="uint8"/>
+    <reg name="v6" bitsize="8" type="uint8"/>
+    <reg name="v7" bitsize="8" type="uint8"/>
+    <reg name="v8" bitsize="8" type="uint8"/>
+    <reg name="v9" bitsize="8" type="uint8"/>
+    <reg name="va" bitsize="8" type="uint8"/>
+    <reg name="vb" bitsize="8" type="uint8"/>
+    <reg name="vc" bitsize="8" type="uint8"/>
+    <reg name="vd" bitsize="8" type="uint8"/>
+    <reg name="ve" bitsize="8" type="uint8"/>
+    <reg name="vf" bitsize="8" type="uint8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="dt" bitsize="8" type="uint8"/>
+    <reg name="st" bitsize="8" type="uint8"/>
+  </feature>
+</target>
+"#;
+
+pub struct GdbStub {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    /// Whether the client is waiting on a stop reply from `c` or `s`.
+    running: bool,
+    signal: u8,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+fn parse_hex(s: &str) -> Option<usize> {
+    usize::from_str_radix(s, 16).ok()
+}
+
+/// Parses `addr,len`.
+fn parse_range(s: &str) -> Option<(usize, usize)> {
+    let split = s.find(',')?;
+    Some((parse_hex(&s[..split])?, parse_hex(&s[split + 1..])?))
+}
+
+/// Escapes bytes that can't appear raw in a packet body.
+fn escape(data: &str) -> String {
+    let mut out = String::with_capacity(data.len());
+
+    for c in data.chars() {
+        if let '$' | '#' | '}' | '*' = c {
+            out.push('}');
+            out.push((c as u8 ^ 0x20) as char);
+        } else {
+            out.push(c);
+        }
+    }
+
+    out
+}
+
+fn registers(state: &State) -> Vec<u8> {
+    let mut bytes: Vec<u8> = state.registers.values().cloned().collect();
+    bytes.extend_from_slice(&state.i_reg.0.to_be_bytes());
+    bytes.extend_from_slice(&state.pc.0.to_be_bytes());
+    bytes.push(state.timer);
+    bytes.push(state.sound_timer);
+    bytes
+}
+
+/// Size in bytes of register `n`, or `None` if there is no such register.
+fn register_size(n: usize) -> Option<usize> {
+    match n {
+        0..=15 | 18 | 19 => Some(1),
+        16 | 17 => Some(2),
+        _ => None,
+    }
+}
+
+fn set_register(state: &mut State, n: usize, bytes: &[u8]) -> bool {
+    if register_size(n) != Some(bytes.len()) {
+        return false;
+    }
+
+    match n {
+        0..=15 => state.registers[Register::n(n as u8).unwrap()] = bytes[0],
+        16 => state.i_reg = Address(u16::from_be_bytes([bytes[0], bytes[1]])),
+        17 => state.pc = Address(u16::from_be_bytes([bytes[0], bytes[1]])),
+        18 => state.timer = bytes[0],
+        19 => state.sound_timer = bytes[0],
+        _ => unreachable!(),
+    }
+
+    true
+}
+
+impl GdbStub {
+    /// Takes over a freshly accepted connection. The machine starts out
+    /// stopped, waiting for the client.
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+
+        Ok(GdbStub {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+            running: false,
+            signal: SIGTRAP,
+        })
+    }
+
+    /// Reads the next packet body, acknowledging it. Returns `None` once the
+    /// client disconnects.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        let mut byte = [0];
+
+        loop {
+            // Skip acks and interrupts until the start of a packet.
+            loop {
+                if self.reader.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'$' {
+                    break;
+                }
+            }
+
+            let mut body = Vec::new();
+            loop {
+                if self.reader.read(&mut byte)? == 0 {
+                    return Ok(None);
+                }
+                if byte[0] == b'#' {
+                    break;
+                }
+                body.push(byte[0]);
+            }
+
+            let mut checksum = [0; 2];
+            self.reader.read_exact(&mut checksum)?;
+
+            let expected = std::str::from_utf8(&checksum)
+                .ok()
+                .and_then(|s| u8::from_str_radix(s, 16).ok());
+            let actual = body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+
+            if expected == Some(actual) {
+                self.writer.write_all(b"+")?;
+                return Ok(Some(String::from_utf8_lossy(&body).into_owned()));
+            }
+
+            self.writer.write_all(b"-")?;
+        }
+    }
+
+    fn write_packet(&mut self, body: &str) -> io::Result<()> {
+        let checksum = body.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(self.writer, "${}#{:02x}", body, checksum)?;
+        self.writer.flush()
+    }
+
+    fn query(&self, packet: &str) -> String {
+        const FEATURES: &str = "qXfer:features:read:target.xml:";
+
+        if packet.starts_with("qSupported") {
+            "PacketSize=4000;qXfer:features:read+;swbreak+".to_string()
+        } else if let Some(range) = packet.strip_prefix(FEATURES) {
+            match parse_range(range) {
+                Some((offset, len)) if offset < TARGET_XML.len() => {
+                    let end = offset.saturating_add(len).min(TARGET_XML.len());
+                    let more = if end < TARGET_XML.len() { 'm' } else { 'l' };
+                    format!("{}{}", more, escape(&TARGET_XML[offset..end]))
+                }
+                Some(_) => "l".to_string(),
+                None => "E01".to_string(),
+            }
+        } else {
+            match packet {
+                "qAttached" => "1".to_string(),
+                "qC" => "QC1".to_string(),
+                "qfThreadInfo" => "m1".to_string(),
+                "qsThreadInfo" => "l".to_string(),
+                _ => String::new(),
+            }
+        }
+    }
+}
+
+impl Frontend for GdbStub {
+    /// Checks, without blocking, for an interrupt (Ctrl-C).
+    fn interrupted(&mut self, _: &mut State, _: &mut Debugger) -> io::Result<bool> {
+        let mut interrupted = self.reader.buffer().contains(&0x03);
+        let buffered = self.reader.buffer().len();
+        self.reader.consume(buffered);
+
+        self.writer.set_nonblocking(true)?;
+        let mut byte = [0];
+        let read = self.writer.read(&mut byte);
+        self.writer.set_nonblocking(false)?;
+
+        match read {
+            Ok(n) => interrupted |= n == 1 && byte[0] == 0x03,
+            Err(ref err) if err.kind() == ErrorKind::WouldBlock => {}
+            Err(err) => return Err(err),
+        }
+
+        if interrupted {
+            self.signal = SIGINT;
+        }
+
+        Ok(interrupted)
+    }
+
+    fn serve(&mut self, state: &mut State, debugger: &mut Debugger) -> io::Result<Resume> {
+        if self.running {
+            let reply = format!("S{:02x}", self.signal);
+            self.write_packet(&reply)?;
+            self.running = false;
+        }
+        self.signal = SIGTRAP;
+
+        loop {
+            let packet = match self.read_packet()? {
+                Some(packet) => packet,
+                None => return Ok(Resume::Detach),
+            };
+
+            let command = packet.get(..1).unwrap_or("");
+            let args = packet.get(1..).unwrap_or("");
+
+            let reply = match command {
+                "?" => format!("S{:02x}", SIGTRAP),
+                "g" => hex(&registers(state)),
+                "G" => match unhex(args) {
+                    Some(ref bytes) if bytes.len() == 22 => {
+                        let mut offset = 0;
+                        for n in 0..20 {
+                            let size = register_size(n).unwrap();
+                            set_register(state, n, &bytes[offset..offset + size]);
+                            offset += size;
+                        }
+                        "OK".to_string()
+                    }
+                    _ => "E01".to_string(),
+                },
+                "p" => match parse_hex(args) {
+                    Some(n) if register_size(n).is_some() => {
+                        let offset: usize = (0..n).map(|n| register_size(n).unwrap()).sum();
+                        hex(&registers(state)[offset..offset + register_size(n).unwrap()])
+                    }
+                    _ => "E01".to_string(),
+                },
+                "P" => {
+                    let set = args.find('=').and_then(|split| {
+                        let n = parse_hex(&args[..split])?;
+                        let bytes = unhex(&args[split + 1..])?;
+                        Some(set_register(state, n, &bytes))
+                    });
+
+                    match set {
+                        Some(true) => "OK".to_string(),
+                        _ => "E01".to_string(),
+                    }
+                }
+                "m" => match parse_range(args) {
+                    Some((addr, len)) if addr < state.memory.len() => {
+                        let end = addr.saturating_add(len).min(state.memory.len());
+                        hex(&state.memory[addr..end])
+                    }
+                    _ => "E01".to_string(),
+                },
+                "M" => {
+                    let written = args.find(':').and_then(|split| {
+                        let (addr, len) = parse_range(&args[..split])?;
+                        let bytes = unhex(&args[split + 1..])?;
+
+                        if bytes.len() != len || addr.saturating_add(len) > state.memory.len() {
+                            return None;
+                        }
+
+                        state.memory[addr..addr + len].copy_from_slice(&bytes);
+                        Some(())
+                    });
+
+                    match written {
+                        Some(()) => "OK".to_string(),
+                        None => "E01".to_string(),
+                    }
+                }
+                "c" | "s" => {
+                    if let Some(addr) = parse_hex(args) {
+                        state.pc = Address(addr as u16);
+                    }
+
+                    self.running = true;
+                    return Ok(if command == "c" {
+                        Resume::Continue
+                    } else {
+                        Resume::Step
+                    });
+                }
+                "Z" | "z" if args.starts_with("0,") => {
+                    let addr = args[2..]
+                        .split(',')
+                        .next()
+                        .and_then(parse_hex)
+                        .map(|addr| Address(addr as u16));
+
+                    match addr {
+                        Some(addr) => {
+                            let breakpoint = Breakpoint {
+                                address: Some(addr),
+                                condition: None,
+                            };
+
+                            if command == "Z" {
+                                if !debugger.breakpoints.contains(&breakpoint) {
+                                    debugger.breakpoints.push(breakpoint);
+                                }
+                            } else {
+                                debugger.breakpoints.retain(|b| *b != breakpoint);
+                            }
+
+                            "OK".to_string()
+                        }
+                        None => "E01".to_string(),
+                    }
+                }
+                "D" => {
+                    self.write_packet("OK")?;
+                    return Ok(Resume::Detach);
+                }
+                "k" => return Ok(Resume::Kill),
+                "H" => "OK".to_string(),
+                "q" => self.query(&packet),
+                _ => String::new(),
+            };
+
+            self.write_packet(&reply)?;
+        }
+    }
+}
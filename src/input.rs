@@ -0,0 +1,25 @@
+//! Recorded keypad input, for running ROMs without a window: one big-endian
+//! 16-bit mask per 60 Hz frame, with bit N set while button N is down. The
+//! `run_rom` fuzz target reads its input the same way.
+
+use crate::types::Button;
+use enum_map::EnumMap;
+
+/// Reads the masks, one per frame.
+pub fn read_masks(bytes: &[u8]) -> Result<Vec<u16>, String> {
+    let masks = bytes.chunks_exact(2);
+    if !masks.remainder().is_empty() {
+        return Err("input ends partway through a frame".into());
+    }
+
+    Ok(masks
+        .map(|mask| u16::from_be_bytes([mask[0], mask[1]]))
+        .collect())
+}
+
+/// Holds down the buttons set in `mask`, releasing the rest.
+pub fn press(mask: u16, buttons: &mut EnumMap<Button, bool>) {
+    for (button, down) in buttons.iter_mut() {
+        *down = mask & 1 << button as u8 != 0;
+    }
+}
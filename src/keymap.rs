@@ -0,0 +1,282 @@
+//! Which host keys press which keypad buttons.
+//!
+//! The COSMAC VIP's keypad is laid out
+//!
+//! ```text
+//! 1 2 3 C
+//! 4 5 6 D
+//! 7 8 9 E
+//! A 0 B F
+//! ```
+//!
+//! and the layouts put it on the same four-by-four block of keys, the left
+//! of the top four rows.
+
+use crate::types::Button;
+use enum_map::EnumMap;
+use minifb::Key;
+use std::fmt;
+
+/// The buttons in keypad order, left to right and top to bottom.
+const KEYPAD: [Button; 16] = {
+    use Button::*;
+
+    [
+        B1, B2, B3, BC, B4, B5, B6, BD, B7, B8, B9, BE, BA, B0, BB, BF,
+    ]
+};
+
+/// Host keyboard layouts, placing the keypad on the same keys physically.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Layout {
+    Qwerty,
+    Azerty,
+    Dvorak,
+}
+
+impl Layout {
+    /// The keys under the keypad, in keypad order.
+    fn keys(self) -> [Key; 16] {
+        use minifb::Key::*;
+
+        match self {
+            Layout::Qwerty => [Key1, Key2, Key3, Key4, Q, W, E, R, A, S, D, F, Z, X, C, V],
+            Layout::Azerty => [Key1, Key2, Key3, Key4, A, Z, E, R, Q, S, D, F, W, X, C, V],
+            Layout::Dvorak => [
+                Key1, Key2, Key3, Key4, Apostrophe, Comma, Period, P, A, O, E, U, Semicolon, Q, J,
+                K,
+            ],
+        }
+    }
+}
+
+impl std::str::FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "qwerty" => Ok(Layout::Qwerty),
+            "azerty" => Ok(Layout::Azerty),
+            "dvorak" => Ok(Layout::Dvorak),
+            _ => Err(format!(
+                "unknown layout {:?}: expected qwerty, azerty or dvorak",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Layout::Qwerty => "qwerty",
+            Layout::Azerty => "azerty",
+            Layout::Dvorak => "dvorak",
+        })
+    }
+}
+
+/// Parses a host key by its minifb name, case-insensitively: `W`, `5`,
+/// `Up`, `Space`, `NumPad5` and so on.
+pub fn parse_key(name: &str) -> Option<Key> {
+    use minifb::Key::*;
+
+    const KEYS: &[Key] = &[
+        Key0,
+        Key1,
+        Key2,
+        Key3,
+        Key4,
+        Key5,
+        Key6,
+        Key7,
+        Key8,
+        Key9,
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Up,
+        Down,
+        Left,
+        Right,
+        Space,
+        Enter,
+        Tab,
+        Backspace,
+        LeftShift,
+        RightShift,
+        LeftCtrl,
+        RightCtrl,
+        Comma,
+        Period,
+        Semicolon,
+        Slash,
+        Apostrophe,
+        Backquote,
+        Backslash,
+        Equal,
+        Minus,
+        LeftBracket,
+        RightBracket,
+        NumPad0,
+        NumPad1,
+        NumPad2,
+        NumPad3,
+        NumPad4,
+        NumPad5,
+        NumPad6,
+        NumPad7,
+        NumPad8,
+        NumPad9,
+    ];
+
+    KEYS.iter()
+        .copied()
+        .find(|&key| key_name(key).eq_ignore_ascii_case(name))
+}
+
+/// The name `parse_key` takes for `key`.
+pub fn key_name(key: Key) -> String {
+    let debug = format!("{:?}", key);
+    debug.trim_start_matches("Key").to_string()
+}
+
+/// Parses bindings like `W=5,A=7,Up=12`, with buttons numbered as in the
+/// config file: decimal, or hex after `0x`.
+pub fn parse_bindings(s: &str) -> Result<Vec<(Key, Button)>, String> {
+    s.split(',')
+        .map(|binding| {
+            let mut parts = binding.splitn(2, '=').map(str::trim);
+            let (key, button) = match (parts.next(), parts.next()) {
+                (Some(key), Some(button)) => (key, button),
+                _ => {
+                    return Err(format!(
+                        "invalid binding {:?}: expected KEY=BUTTON",
+                        binding
+                    ))
+                }
+            };
+
+            let key = parse_key(key).ok_or_else(|| format!("unknown key {:?}", key))?;
+            let number = match button
+                .strip_prefix("0x")
+                .or_else(|| button.strip_prefix("0X"))
+            {
+                Some(hex) => u8::from_str_radix(hex, 16),
+                None => button.parse(),
+            };
+            let button = number
+                .ok()
+                .filter(|&n| n < 16)
+                .and_then(Button::n)
+                .ok_or_else(|| format!("invalid button {:?}", button))?;
+            Ok((key, button))
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyMap {
+    buttons: EnumMap<Button, Vec<Key>>,
+}
+
+impl KeyMap {
+    /// A map with nothing bound.
+    pub fn new() -> Self {
+        KeyMap {
+            buttons: EnumMap::new(),
+        }
+    }
+
+    pub fn layout(layout: Layout) -> Self {
+        let mut map = KeyMap::new();
+        for (&key, &button) in layout.keys().iter().zip(&KEYPAD) {
+            map.bind(key, button);
+        }
+        map
+    }
+
+    /// Makes `key` press `button`, and nothing else. Other keys for `button`
+    /// keep working.
+    pub fn bind(&mut self, key: Key, button: Button) {
+        self.unbind(key);
+        self.buttons[button].push(key);
+    }
+
+    pub fn unbind(&mut self, key: Key) {
+        for (_, keys) in self.buttons.iter_mut() {
+            keys.retain(|&bound| bound != key);
+        }
+    }
+
+    pub fn button(&self, key: Key) -> Option<Button> {
+        self.buttons
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(button, _)| button)
+    }
+
+    pub fn keys(&self, button: Button) -> &[Key] {
+        &self.buttons[button]
+    }
+
+    /// Presses the buttons with any of their keys down.
+    pub fn update(&self, is_down: impl Fn(Key) -> bool, buttons: &mut EnumMap<Button, bool>) {
+        for (button, keys) in self.buttons.iter() {
+            buttons[button] = keys.iter().any(|&key| is_down(key));
+        }
+    }
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap::layout(Layout::Qwerty)
+    }
+}
+
+/// Draws the keypad, listing each button's keys.
+impl fmt::Display for KeyMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let cells: Vec<_> = KEYPAD
+            .iter()
+            .map(|&button| {
+                let keys: Vec<_> = self.keys(button).iter().map(|&k| key_name(k)).collect();
+                format!("{:X}: {}", button as u8, keys.join(" "))
+            })
+            .collect();
+        let width = cells.iter().map(String::len).max().unwrap_or(0);
+
+        for row in cells.chunks(4) {
+            let row: Vec<_> = row
+                .iter()
+                .map(|cell| format!("{:1$}", cell, width))
+                .collect();
+            writeln!(f, "{}", row.join("  ").trim_end())?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,3 +1,29 @@
+pub mod cartridge;
+pub mod cfg;
+pub mod config;
+pub mod coverage;
+pub mod dap;
+pub mod debug;
+pub mod decompile;
+pub mod detect;
+pub mod disasm;
+pub mod encoder;
 pub mod eval;
+pub mod expr;
+pub mod gdb;
+pub mod input;
+pub mod keymap;
+pub mod linemap;
+pub mod octo;
+pub mod overlay;
+pub mod palette;
 pub mod parser;
+pub mod phosphor;
+pub mod profile;
+pub mod record;
+pub mod romdb;
+pub mod screenshot;
+pub mod symbols;
+pub mod trace;
+pub mod tracediff;
 pub mod types;
@@ -0,0 +1,94 @@
+//! Maps instruction addresses back to the source lines they were assembled
+//! from. The file format has one `ADDR FILE:LINE` entry per line, e.g.
+//! `0x2A4 pong.8o:31`; blank lines and `#` comments are ignored.
+
+use crate::types::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LineMap {
+    lines: BTreeMap<Address, (PathBuf, u32)>,
+}
+
+/// Whether two paths name the same source, allowing either to be relative.
+fn same_file(a: &Path, b: &Path) -> bool {
+    a.ends_with(b) || b.ends_with(a)
+}
+
+impl LineMap {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn insert(&mut self, addr: Address, file: impl Into<PathBuf>, line: u32) {
+        self.lines.insert(addr, (file.into(), line));
+    }
+
+    pub fn line(&self, addr: Address) -> Option<(&Path, u32)> {
+        self.lines
+            .get(&addr)
+            .map(|(file, line)| (file.as_path(), *line))
+    }
+
+    /// The first instruction on `line` of `file`, or failing that on the
+    /// closest line after it that has any, with the line it was found on.
+    pub fn address(&self, file: &Path, line: u32) -> Option<(Address, u32)> {
+        self.lines
+            .iter()
+            .filter(|(_, (f, l))| *l >= line && same_file(f, file))
+            .min_by_key(|(addr, (_, l))| (*l, **addr))
+            .map(|(addr, (_, l))| (*addr, *l))
+    }
+
+    /// Makes relative paths relative to `dir` instead, usually the directory
+    /// the map was loaded from.
+    pub fn resolve(&mut self, dir: &Path) {
+        for (file, _) in self.lines.values_mut() {
+            if file.is_relative() {
+                *file = dir.join(&file);
+            }
+        }
+    }
+}
+
+impl FromStr for LineMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut map = LineMap::new();
+
+        for (n, entry) in s.lines().enumerate() {
+            let entry = entry.trim();
+            if entry.is_empty() || entry.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || format!("invalid line map entry on line {}: {:?}", n + 1, entry);
+
+            let split = entry.find(char::is_whitespace).ok_or_else(invalid)?;
+            let (addr, location) = (&entry[..split], entry[split..].trim());
+
+            let addr =
+                u16::from_str_radix(addr.trim_start_matches("0x"), 16).map_err(|_| invalid())?;
+            let colon = location.rfind(':').ok_or_else(invalid)?;
+            let line = location[colon + 1..].parse().map_err(|_| invalid())?;
+
+            map.insert(Address(addr), &location[..colon], line);
+        }
+
+        Ok(map)
+    }
+}
+
+impl fmt::Display for LineMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (addr, (file, line)) in &self.lines {
+            writeln!(f, "{} {}:{}", addr, file.display(), line)?;
+        }
+
+        Ok(())
+    }
+}
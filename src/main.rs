@@ -1,31 +1,1359 @@
-use bitvec::prelude::*;
+use chip8::cartridge::{self, Cartridge};
+use chip8::cfg::Cfg;
+use chip8::config::{self, Config, Settings};
+use chip8::coverage::Coverage;
+use chip8::dap::DapServer;
+use chip8::debug::{self, Breakpoint, Debugger, Frontend, Resume, Watchpoint};
+use chip8::detect::{self, Detection};
+use chip8::eval::EvalError;
+use chip8::gdb::GdbStub;
+use chip8::input;
+use chip8::keymap::{self, Layout};
+use chip8::octo::{self, Program};
+use chip8::overlay::{self, Overlay};
+use chip8::palette::{Palette, Theme};
+use chip8::phosphor::{Filter, Phosphor};
+use chip8::profile::Profiler;
+use chip8::record::{self, Recorder};
+use chip8::romdb::{self, RomDb};
+use chip8::screenshot;
+use chip8::symbols::{Symbolic, Symbols};
+use chip8::trace::{self, Tracer};
+use chip8::tracediff;
+use chip8::types::{Address, Quirks, State, FONT};
 use minifb::Window;
 use minifb::WindowOptions;
+use minifb::{Key, KeyRepeat, Scale};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 use rodio::source::SineWave;
 use rodio::Sink;
 use std::env;
+use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
-use std::time::{Duration, Instant};
-use std::usize;
+use std::io::{self, BufReader, BufWriter};
+use std::net::TcpListener;
+use std::ops::{Range, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-fn main() {
-    let mut state = chip8::types::State::default();
+const USAGE: &str = "\
+Usage: chip8 [COMMAND] [OPTIONS] ROM [FILE...]
 
-    for (i, path) in env::args().skip(1).enumerate() {
-        let mut file = File::open(path).expect("Couldn't open!");
+Commands:
+    run         Run a ROM, the default when no command is given
+    debug       Run a ROM, stopped at its first instruction
+    bench       Run a ROM without a window as fast as possible
+    record      Record a ROM without a window to a GIF or Y4M video
+    info        Show how a ROM will be run
+    config      Print the settings a ROM will run with, for bug reports
+    disasm      Disassemble a ROM
+    asm         Assemble Octo source into a ROM
+    decompile   Decompile a ROM into Octo source
+    analyze     Report on a ROM's control flow and platform
+    tracediff   Find where two traces diverge
+    help        Show this message
 
-        let mut write_at = if i == 0 { 0x200 } else { 0x0 };
+ROMs can be CHIP-8 binaries, Octo source (.8o) or Octo cartridges (.gif).
+Further files are loaded at 0x000.
 
-        while let read @ 1..=usize::MAX = file
-            .read(&mut state.memory[write_at..])
-            .expect("Couldn't read!")
-        {
-            write_at += read;
+Settings come from the ROM database or cartridge, then
+~/.config/chip8-rs/config.toml, then the options below.
+
+Machine options, for run, debug, bench, record, info and config:
+    --speed N             Run N instructions per frame instead of the ROM's
+                          speed, or as fast as possible
+    --quirks LIST         A platform (chip8, schip, xochip) and/or quirks
+                          to turn on (shift, load_store, vf_order, clip,
+                          jump, logic, vblank) or off (no-clip)
+    --theme NAME          Display colors from classic, phosphor, lcd or octo
+    --colors FG,BG[,FG2,BLEND]
+                          Display colors, as #RRGGBB, replacing the theme's,
+                          with XO-CHIP's second plane and both planes after
+    --filter FILTER       Hide flicker by showing pixels lit in the last
+                          frame (persist) or fading them out (decay, or
+                          decay:0.6 to keep 60% of their brightness a frame)
+    --scale N             Scale the window by 1, 2, 4, 8, 16 or 32
+    --volume N            Play sound at a volume from 0 to 1
+    --screenshot-scale N  Scale screenshots and recordings by N, from 1 to
+                          64, 8 by default
+    --layout NAME         Put the keypad on 1234/QWER/ASDF/ZXCV for qwerty,
+                          or the same keys for azerty or dvorak
+    --keys KEY=N,...      Make KEY press button N, from 0 to 15 or 0x0 to
+                          0xF, like W=5,A=7,S=8,D=9,Up=0xC
+    --seed N              Seed the random number generator
+    --load-address ADDR   Load the ROM and start running at ADDR
+    --font FILE           Load the font at 0x000 from FILE
+    --config FILE         Read settings from FILE instead
+    --romdb FILE          Add ROM database entries from FILE
+    --symbols FILE        Name addresses using FILE
+
+Options for run and debug:
+    F11                   Start or stop recording a GIF next to the ROM
+    F12                   Save a screenshot next to the ROM
+    --record FILE         Record to FILE, a .gif or .y4m, from the start
+    --debug-view          Show registers and memory next to the screen
+    --break ADDR          Stop at ADDR
+    --break-if EXPR       Stop when EXPR is true
+    --watch RANGE[:r|w|rw]
+                          Stop when RANGE is accessed
+    --watch-reg VX        Stop when VX changes
+    --gdb ADDR            Wait for gdb to connect to ADDR
+    --dap ADDR            Wait for a debug adapter client on ADDR
+    --trace FILE          Record every instruction to FILE
+    --trace-format FMT    text or binary
+    --trace-pc RANGE      Only trace instructions in RANGE
+    --trace-class LIST    Only trace opcode classes in LIST, like 1,2,D
+    --trace-frames RANGE  Only trace frames in RANGE
+    --profile             Print a profile on exit
+    --profile-folded FILE Write a profile in folded stack format to FILE
+    --coverage FILE       Write coverage as JSON or hex to FILE
+
+Options for bench and record:
+    --frames N            Run N frames, 600 by default
+    -o FILE               Where record writes, a .gif or .y4m
+    --input FILE          Press buttons for record from FILE, a big-endian
+                          16-bit mask per frame with bit N for button N,
+                          running as many frames as it has by default
+
+Other commands:
+    chip8 disasm [--symbols FILE] ROM
+    chip8 asm [-o game.ch8] [--symbols FILE] [--line-map FILE] game.8o
+    chip8 decompile [-o game.8o] [--symbols FILE] ROM
+    chip8 analyze [--dot FILE] [--symbols FILE] ROM
+    chip8 tracediff [--context N] a.trace b.trace
+";
+
+fn usage(synopsis: &str) -> String {
+    format!("usage: chip8 {}\nRun `chip8 help` for more.", synopsis)
+}
+
+fn unknown_option(option: &str) -> String {
+    format!("unknown option {}\nRun `chip8 help` for more.", option)
+}
+
+/// The value following `option`.
+fn next_value(args: &mut impl Iterator<Item = String>, option: &str) -> Result<String, String> {
+    args.next()
+        .ok_or_else(|| format!("{} needs a value", option))
+}
+
+fn parse<T: FromStr>(option: &str, value: &str) -> Result<T, String>
+where
+    T::Err: fmt::Display,
+{
+    value
+        .parse()
+        .map_err(|err| format!("invalid {} {:?}: {}", option, value, err))
+}
+
+fn parse_hex(s: &str) -> Result<u64, String> {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16)
+        .map_err(|_| format!("invalid hex number {:?}", s))
+}
+
+fn parse_range(s: &str, radix: u32) -> Result<RangeInclusive<u64>, String> {
+    let (start, end) = match s.find('-') {
+        Some(split) => (&s[..split], &s[split + 1..]),
+        None => (s, s),
+    };
+
+    let parse = |n: &str| {
+        u64::from_str_radix(n.trim_start_matches("0x"), radix)
+            .map_err(|_| format!("invalid range {:?}", s))
+    };
+
+    Ok(parse(start)?..=parse(end)?)
+}
+
+/// Parses `FG,BG` or `FG,BG,FG2,BLEND`.
+fn parse_colors(s: &str) -> Result<Vec<u32>, String> {
+    let colors: Option<Vec<_>> = s.split(',').map(cartridge::parse_color).collect();
+
+    match colors {
+        Some(colors) if colors.len() == 2 || colors.len() == 4 => Ok(colors),
+        _ => Err(format!(
+            "invalid --colors {:?}: expected FG,BG or FG,BG,FG2,BLEND like #FFFFFF,#000000",
+            s
+        )),
+    }
+}
+
+fn window_scale(scale: u32) -> Scale {
+    match scale {
+        1 => Scale::X1,
+        2 => Scale::X2,
+        4 => Scale::X4,
+        8 => Scale::X8,
+        16 => Scale::X16,
+        _ => Scale::X32,
+    }
+}
+
+/// The current UTC time like `20191019-153012`, for naming files.
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    let (days, time) = (secs / 86400, secs % 86400);
+
+    // Howard Hinnant's civil_from_days, for days since 1970.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+/// A file next to the ROM named after it and the time, like
+/// `pong-20191019-153012.png`.
+fn capture_path(rom: &str, extension: &str) -> PathBuf {
+    let rom = Path::new(rom);
+    let stem = rom
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default();
+
+    rom.with_file_name(format!("{}-{}.{}", stem, timestamp(), extension))
+}
+
+fn screenshot(path: &Path, palette: &Palette, state: &State, scale: u32) -> Result<(), String> {
+    let mut file = File::create(path)
+        .map(BufWriter::new)
+        .map_err(|err| format!("couldn't create {}: {}", path.display(), err))?;
+
+    screenshot::write_png(&mut file, palette, &[&state.bit_gfx], scale)
+        .and_then(|_| file.flush().map_err(|err| err.to_string()))
+        .map_err(|err| format!("couldn't write {}: {}", path.display(), err))
+}
+
+/// A recording being written to a file.
+struct Recording {
+    path: PathBuf,
+    recorder: Recorder<BufWriter<File>>,
+}
+
+impl Recording {
+    /// Records to `path`, in the format its extension names.
+    fn start(path: PathBuf, palette: &Palette, scale: u32) -> Result<Self, String> {
+        let format = record::Format::from_path(&path)?;
+        let file = File::create(&path)
+            .map(BufWriter::new)
+            .map_err(|err| format!("couldn't create {}: {}", path.display(), err))?;
+        let recorder = Recorder::new(file, format, palette, scale)
+            .map_err(|err| format!("couldn't write {}: {}", path.display(), err))?;
+
+        Ok(Recording { path, recorder })
+    }
+
+    fn frame(&mut self, state: &State) -> Result<(), String> {
+        self.recorder
+            .frame(&[&state.bit_gfx])
+            .map_err(|err| format!("couldn't write {}: {}", self.path.display(), err))
+    }
+
+    /// Finishes the file, returning where it was saved.
+    fn finish(self) -> Result<PathBuf, String> {
+        let path = self.path;
+        self.recorder
+            .finish()
+            .map_err(|err| format!("couldn't write {}: {}", path.display(), err))?;
+        Ok(path)
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>, String> {
+    std::fs::read(path).map_err(|err| format!("couldn't read {}: {}", path, err))
+}
+
+fn create(path: &str) -> Result<BufWriter<File>, String> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|err| format!("couldn't create {}: {}", path, err))
+}
+
+fn open_trace(path: &str) -> Result<trace::Reader<BufReader<File>>, String> {
+    let file = File::open(path).map_err(|err| format!("couldn't open {}: {}", path, err))?;
+    trace::Reader::new(BufReader::new(file)).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn tracediff(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut context = 5;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--context" => context = parse(&arg, &next_value(&mut args, &arg)?)?,
+            _ if arg.starts_with("--") => return Err(unknown_option(&arg)),
+            _ => paths.push(arg),
+        }
+    }
+
+    if paths.len() != 2 {
+        return Err(usage("tracediff [--context N] a.trace b.trace"));
+    }
+
+    let divergence = tracediff::diff(open_trace(&paths[0])?, open_trace(&paths[1])?, context)
+        .map_err(|err| format!("couldn't read trace: {}", err))?;
+
+    match divergence {
+        Some(divergence) => {
+            print!("{}", divergence);
+            process::exit(1);
+        }
+        None => println!("Traces are identical."),
+    }
+
+    Ok(())
+}
+
+/// Loads a ROM at 0x200 for the static tools, returning the range it's in.
+/// Checks that `len` bytes from `path` fit in memory at `load_address`.
+fn check_fits(path: &str, len: usize, load_address: usize) -> Result<(), String> {
+    if load_address + len > 4096 {
+        return Err(format!(
+            "{} is {} bytes, too big to load at {:#05X}",
+            path, len, load_address
+        ));
+    }
+
+    Ok(())
+}
+
+fn load_rom(path: &str) -> Result<([u8; 4096], Range<usize>), String> {
+    let rom = read(path)?;
+    check_fits(path, rom.len(), 0x200)?;
+
+    let mut memory = [0; 4096];
+    memory[0x200..0x200 + rom.len()].copy_from_slice(&rom);
+
+    Ok((memory, 0x200..0x200 + rom.len()))
+}
+
+fn read_symbols(path: &str) -> Result<Symbols, String> {
+    std::fs::read_to_string(path)
+        .map_err(|err| format!("couldn't read {}: {}", path, err))?
+        .parse()
+        .map_err(|err| format!("{}: {}", path, err))
+}
+
+/// Parses the arguments of the static tools, which take one ROM.
+fn static_args(
+    mut args: impl Iterator<Item = String>,
+    synopsis: &str,
+    mut option: impl FnMut(&str, String) -> Result<bool, String>,
+) -> Result<String, String> {
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        if arg.starts_with('-') {
+            let value = next_value(&mut args, &arg)?;
+            if !option(&arg, value)? {
+                return Err(unknown_option(&arg));
+            }
+        } else {
+            paths.push(arg);
+        }
+    }
+
+    match paths.len() {
+        1 => Ok(paths.remove(0)),
+        _ => Err(usage(synopsis)),
+    }
+}
+
+fn disasm(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut symbols = Symbols::new();
+
+    let path = static_args(args, "disasm [--symbols FILE] ROM", |option, value| {
+        match option {
+            "--symbols" => symbols = read_symbols(&value)?,
+            _ => return Ok(false),
+        }
+        Ok(true)
+    })?;
+
+    let (memory, rom) = load_rom(&path)?;
+    chip8::disasm::write_listing(&memory, rom, &symbols, &mut io::stdout())
+        .map_err(|err| format!("couldn't write listing: {}", err))
+}
+
+fn analyze(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut dot_path = None;
+    let mut symbols = Symbols::new();
+
+    let path = static_args(
+        args,
+        "analyze [--dot FILE] [--symbols FILE] ROM",
+        |option, value| {
+            match option {
+                "--dot" => dot_path = Some(value),
+                "--symbols" => symbols = read_symbols(&value)?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        },
+    )?;
+
+    let (memory, rom) = load_rom(&path)?;
+    let cfg = Cfg::new(&memory, rom.clone());
+
+    cfg.write_report(&symbols, &mut io::stdout())
+        .and_then(|_| writeln!(io::stdout()))
+        .and_then(|_| detect::detect(&memory, rom).write_report(&symbols, &mut io::stdout()))
+        .map_err(|err| format!("couldn't write report: {}", err))?;
+
+    if let Some(path) = dot_path {
+        let mut file = create(&path)?;
+        cfg.write_dot(&symbols, &mut file)
+            .and_then(|_| file.flush())
+            .map_err(|err| format!("couldn't write {}: {}", path, err))?;
+    }
+
+    Ok(())
+}
+
+fn decompile(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut out_path = None;
+    let mut symbols = Symbols::new();
+
+    let path = static_args(
+        args,
+        "decompile [-o game.8o] [--symbols FILE] ROM",
+        |option, value| {
+            match option {
+                "-o" | "--output" => out_path = Some(value),
+                "--symbols" => symbols = read_symbols(&value)?,
+                _ => return Ok(false),
+            }
+            Ok(true)
+        },
+    )?;
+
+    let (memory, rom) = load_rom(&path)?;
+
+    let mut out: Box<dyn Write> = match &out_path {
+        Some(path) => Box::new(create(path)?),
+        None => Box::new(io::stdout()),
+    };
+
+    chip8::decompile::decompile(&memory, rom, &symbols, &mut out)
+        .and_then(|_| out.flush())
+        .map_err(|err| format!("couldn't write source: {}", err))
+}
+
+/// Assembles Octo source.
+fn assemble(path: &str) -> Result<Program, String> {
+    let source =
+        std::fs::read_to_string(path).map_err(|err| format!("couldn't read {}: {}", path, err))?;
+    octo::assemble(&source, path).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn read_cartridge(path: &str) -> Result<Cartridge, String> {
+    let file = File::open(path).map_err(|err| format!("couldn't open {}: {}", path, err))?;
+    Cartridge::read(BufReader::new(file)).map_err(|err| format!("{}: {}", path, err))
+}
+
+fn asm(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut out_path = None;
+    let mut symbols_path = None;
+    let mut line_map_path = None;
+
+    let path = static_args(
+        args,
+        "asm [-o game.ch8] [--symbols FILE] [--line-map FILE] game.8o",
+        |option, value| {
+            match option {
+                "-o" | "--output" => out_path = Some(value),
+                "--symbols" => symbols_path = Some(value),
+                "--line-map" => line_map_path = Some(value),
+                _ => return Ok(false),
+            }
+            Ok(true)
+        },
+    )?;
+
+    let program = assemble(&path)?;
+    let out_path = out_path.unwrap_or_else(|| {
+        Path::new(&path)
+            .with_extension("ch8")
+            .to_string_lossy()
+            .into_owned()
+    });
+
+    let write = |path: &str, contents: &[u8]| {
+        std::fs::write(path, contents).map_err(|err| format!("couldn't write {}: {}", path, err))
+    };
+
+    write(&out_path, &program.bytes)?;
+
+    if let Some(path) = symbols_path {
+        write(&path, program.symbols.to_json().as_bytes())?;
+    }
+
+    if let Some(path) = line_map_path {
+        write(&path, program.lines.to_string().as_bytes())?;
+    }
+
+    Ok(())
+}
+
+/// How to set up the machine, from the options `run`, `debug`, `bench`,
+/// `record`, `info` and `config` share.
+#[derive(Default)]
+struct MachineOptions {
+    files: Vec<String>,
+    /// Overrides for the config file and the ROM's own settings.
+    settings: Settings,
+    seed: Option<u64>,
+    load_address: Option<u16>,
+    font: Option<String>,
+    config_path: Option<PathBuf>,
+    romdb_paths: Vec<PathBuf>,
+    symbols_path: Option<String>,
+}
+
+impl MachineOptions {
+    /// Takes `arg` and its value if it's one of these options, returning
+    /// whether it was.
+    fn parse(
+        &mut self,
+        arg: &str,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<bool, String> {
+        let settings = &mut self.settings;
+
+        match arg {
+            "--speed" => match parse(arg, &next_value(args, arg)?)? {
+                0 => return Err("--speed must be at least 1".into()),
+                speed => settings.speed = Some(speed),
+            },
+            "--quirks" => settings.quirks = Some(parse(arg, &next_value(args, arg)?)?),
+            "--colors" => {
+                let colors = parse_colors(&next_value(args, arg)?)?;
+                settings.foreground = Some(colors[0]);
+                settings.background = Some(colors[1]);
+                settings.foreground2 = colors.get(2).copied();
+                settings.blend = colors.get(3).copied();
+            }
+            "--filter" => settings.filter = Some(parse(arg, &next_value(args, arg)?)?),
+            "--theme" => settings.theme = Some(parse(arg, &next_value(args, arg)?)?),
+            "--scale" => match parse(arg, &next_value(args, arg)?)? {
+                scale if config::SCALES.contains(&scale) => settings.scale = Some(scale),
+                _ => return Err("--scale must be 1, 2, 4, 8, 16 or 32".into()),
+            },
+            "--screenshot-scale" => match parse(arg, &next_value(args, arg)?)? {
+                scale @ 1..=screenshot::MAX_SCALE => settings.screenshot_scale = Some(scale),
+                _ => {
+                    return Err(format!(
+                        "--screenshot-scale must be from 1 to {}",
+                        screenshot::MAX_SCALE
+                    ))
+                }
+            },
+            "--volume" => match parse(arg, &next_value(args, arg)?)? {
+                volume if (0.0..=1.0).contains(&volume) => settings.volume = Some(volume),
+                _ => return Err("--volume must be from 0 to 1".into()),
+            },
+            "--layout" => settings.layout = Some(parse(arg, &next_value(args, arg)?)?),
+            "--keys" => {
+                let bindings = next_value(args, arg)?;
+                settings.keys.extend(
+                    keymap::parse_bindings(&bindings)
+                        .map_err(|err| format!("invalid --keys {:?}: {}", bindings, err))?,
+                );
+            }
+            "--seed" => self.seed = Some(parse(arg, &next_value(args, arg)?)?),
+            "--load-address" => match parse_hex(&next_value(args, arg)?)? {
+                addr @ 0..=0xFFF => self.load_address = Some(addr as u16),
+                addr => return Err(format!("--load-address {:#X} is outside memory", addr)),
+            },
+            "--font" => self.font = Some(next_value(args, arg)?),
+            "--config" => self.config_path = Some(next_value(args, arg)?.into()),
+            "--romdb" => self.romdb_paths.push(next_value(args, arg)?.into()),
+            "--symbols" => self.symbols_path = Some(next_value(args, arg)?),
+            _ => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    /// The config file named by `--config`, or else the user's if they have
+    /// one.
+    fn config(&self) -> Result<Config, String> {
+        let path = match &self.config_path {
+            Some(path) => path.clone(),
+            None => match Config::path().filter(|path| path.exists()) {
+                Some(path) => path,
+                None => return Ok(Config::default()),
+            },
+        };
+
+        std::fs::read_to_string(&path)
+            .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?
+            .parse()
+            .map_err(|err| format!("{}: {}", path.display(), err))
+    }
+}
+
+/// Where a ROM's settings came from.
+enum Source {
+    Cartridge(cartridge::Options),
+    Database(romdb::Entry),
+    Detected(Detection),
+}
+
+/// A loaded ROM and how to run it.
+struct Machine {
+    state: State,
+    rom: Range<usize>,
+    symbols: Symbols,
+    source: Source,
+    settings: Settings,
+}
+
+const SCALE: u32 = 16;
+const SCREENSHOT_SCALE: u32 = 8;
+const VOLUME: f32 = 0.5;
+
+fn load(options: &MachineOptions) -> Result<Machine, String> {
+    let config = options.config()?;
+    let mut state = State::default();
+
+    let font = match &options.font {
+        Some(path) => read(path)?,
+        None => FONT.to_vec(),
+    };
+    let len = font.len().min(state.memory.len());
+    state.memory[..len].copy_from_slice(&font[..len]);
+
+    // Octo source is assembled on the fly, bringing its labels with it.
+    // Cartridges carry their source along with Octo's settings for it.
+    let path = &options.files[0];
+    let (program, cartridge) = if path.ends_with(".gif") {
+        let cartridge = read_cartridge(path)?;
+        let program = cartridge
+            .assemble(path)
+            .map_err(|err| format!("{}: {}", path, err))?;
+        (Some(program), Some(cartridge))
+    } else if path.ends_with(".8o") {
+        (Some(assemble(path)?), None)
+    } else {
+        (None, None)
+    };
+
+    let load_address = match (options.load_address, &program) {
+        (Some(addr), Some(_)) if addr != 0x200 => {
+            return Err("Octo programs can only be loaded at 0x200".into())
+        }
+        (addr, _) => usize::from(addr.unwrap_or(0x200)),
+    };
+
+    let bytes = match &program {
+        Some(program) => program.bytes.clone(),
+        None => read(path)?,
+    };
+    check_fits(path, bytes.len(), load_address)?;
+
+    let rom = load_address..load_address + bytes.len();
+    state.memory[rom.clone()].copy_from_slice(&bytes);
+    state.pc = Address(load_address as u16);
+
+    for path in &options.files[1..] {
+        let bytes = read(path)?;
+        let len = bytes.len().min(state.memory.len());
+        state.memory[..len].copy_from_slice(&bytes[..len]);
+    }
+
+    let symbols = match (&options.symbols_path, &program) {
+        (Some(path), _) => read_symbols(path)?,
+        (None, Some(program)) => program.symbols.clone(),
+        (None, None) => Symbols::new(),
+    };
+
+    let source = match cartridge {
+        Some(cartridge) => Source::Cartridge(cartridge.options),
+        None => {
+            let mut romdb = RomDb::bundled();
+
+            let user_path = RomDb::user_path().filter(|path| path.exists());
+            for path in user_path.iter().chain(&options.romdb_paths) {
+                let user = std::fs::read_to_string(path)
+                    .map_err(|err| format!("couldn't read {}: {}", path.display(), err))?;
+                romdb.merge(
+                    user.parse()
+                        .map_err(|err| format!("{}: {}", path.display(), err))?,
+                );
+            }
+
+            match romdb.get(&bytes) {
+                Some(entry) => Source::Database(entry),
+                None => Source::Detected(detect::detect(&state.memory, rom.clone())),
+            }
+        }
+    };
+
+    // Settings known to suit the ROM beat the user's defaults, but not their
+    // overrides for it or the command line. Guesses don't beat anything.
+    let mut settings = Settings::default();
+    match &source {
+        Source::Cartridge(options) => {
+            settings.merge(&config.defaults);
+            settings.merge(&Settings {
+                speed: options.tickrate,
+                quirks: Some(options.quirks),
+                foreground: options.fill_color,
+                background: options.background_color,
+                foreground2: options.fill_color2,
+                blend: options.blend_color,
+                ..Settings::default()
+            });
+        }
+        Source::Database(entry) => {
+            settings.merge(&config.defaults);
+            settings.merge(&Settings {
+                speed: entry.tickrate,
+                quirks: Some(entry.quirks),
+                foreground: entry.foreground,
+                background: entry.background,
+                keys: entry.keys.clone(),
+                ..Settings::default()
+            });
+        }
+        Source::Detected(detection) => {
+            settings.quirks = Some(detection.quirks);
+            settings.merge(&config.defaults);
+        }
+    }
+
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    settings.merge(&config.rom(&file_name, &bytes));
+    settings.merge(&options.settings);
+
+    state.quirks = settings.quirks.unwrap_or_default();
+    if let Some(seed) = options.seed {
+        state.rng = StdRng::seed_from_u64(seed);
+    }
+
+    Ok(Machine {
+        state,
+        rom,
+        symbols,
+        source,
+        settings,
+    })
+}
+
+fn eval_error(state: &State, symbols: &Symbols, err: EvalError) -> String {
+    format!(
+        "couldn't execute the instruction at {}: {:?}",
+        Symbolic(&state.pc, symbols),
+        err
+    )
+}
+
+fn info(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut options = MachineOptions::default();
+
+    while let Some(arg) = args.next() {
+        if !options.parse(&arg, &mut args)? {
+            if arg.starts_with("--") {
+                return Err(unknown_option(&arg));
+            }
+            options.files.push(arg);
+        }
+    }
+
+    if options.files.is_empty() {
+        return Err(usage("info [OPTIONS] ROM"));
+    }
+
+    let machine = load(&options)?;
+    let settings = &machine.settings;
+    let rom = &machine.state.memory[machine.rom.clone()];
+
+    println!("File:     {}", options.files[0]);
+    println!("Size:     {} bytes", rom.len());
+    println!("SHA-1:    {}", romdb::hash(rom));
+
+    match &machine.source {
+        Source::Cartridge(_) => println!("Source:   Octo cartridge"),
+        Source::Database(entry) => {
+            println!("Source:   ROM database");
+            if let Some(title) = &entry.title {
+                println!("Title:    {}", title);
+            }
+            println!("Platform: {}", entry.platform);
+        }
+        Source::Detected(detection) => {
+            println!("Source:   guessed from its instructions");
+            println!(
+                "Platform: {} ({} confidence)",
+                detection.platform, detection.confidence
+            );
+        }
+    }
+
+    println!("Quirks:   {}", machine.state.quirks);
+    match settings.speed {
+        Some(speed) => println!("Speed:    {} instructions per frame", speed),
+        None => println!("Speed:    as fast as possible"),
+    }
+    let Palette(colors) = settings.palette();
+    println!(
+        "Colors:   {} theme, #{:06X} on #{:06X}, then #{:06X} and #{:06X}",
+        settings.theme.unwrap_or(Theme::Classic),
+        colors[1],
+        colors[0],
+        colors[2],
+        colors[3]
+    );
+    println!("Filter:   {}", settings.filter.unwrap_or(Filter::None));
+    println!("Keys:     {}", settings.layout.unwrap_or(Layout::Qwerty));
+    for line in settings.key_map().to_string().lines() {
+        println!("          {}", line);
+    }
+
+    if let Source::Detected(detection) = &machine.source {
+        println!();
+        detection
+            .write_report(&machine.symbols, &mut io::stdout())
+            .map_err(|err| format!("couldn't write report: {}", err))?;
+    }
+
+    Ok(())
+}
+
+/// Prints the settings a ROM would run with, or the defaults without one.
+fn config(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut options = MachineOptions::default();
+
+    while let Some(arg) = args.next() {
+        if !options.parse(&arg, &mut args)? {
+            if arg.starts_with("--") {
+                return Err(unknown_option(&arg));
+            }
+            options.files.push(arg);
+        }
+    }
+
+    let mut settings = Settings {
+        scale: Some(SCALE),
+        screenshot_scale: Some(SCREENSHOT_SCALE),
+        volume: Some(VOLUME),
+        quirks: Some(Quirks::default()),
+        theme: Some(Theme::Classic),
+        filter: Some(Filter::None),
+        ..Settings::default()
+    };
+
+    let rom = match options.files.first() {
+        Some(file) => {
+            let machine = load(&options)?;
+            settings.merge(&machine.settings);
+
+            let rom = &machine.state.memory[machine.rom.clone()];
+            Some(format!("{} ({})", file, romdb::hash(rom)))
+        }
+        None => {
+            settings.merge(&options.config()?.defaults);
+            settings.merge(&options.settings);
+            None
+        }
+    };
+
+    match options.config_path.clone().or_else(Config::path) {
+        Some(path) if path.exists() => println!("# From {}", path.display()),
+        Some(path) => println!("# {} doesn't exist", path.display()),
+        None => println!("# No config directory"),
+    }
+    if let Some(rom) = rom {
+        println!("# For {}", rom);
+    }
+
+    if settings.speed.is_none() {
+        println!("# Without a speed, ROMs run as fast as possible.");
+    }
+    print!("{}", settings);
+
+    Ok(())
+}
+
+/// Runs a frame's instructions without a window, with the buttons `state`
+/// has down.
+fn headless_frame(state: &mut State, symbols: &Symbols, speed: u32) -> Result<(), String> {
+    for _ in 0..speed {
+        let instr = state
+            .fetch()
+            .map_err(|err| eval_error(state, symbols, err))?;
+        let pc = state.pc;
+        instr.eval(state).map_err(|err| {
+            state.pc = pc;
+            eval_error(state, symbols, err)
+        })?;
+    }
+    state.tick_timers();
+
+    Ok(())
+}
+
+fn bench(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut options = MachineOptions::default();
+    let mut frames: u64 = 600;
+
+    while let Some(arg) = args.next() {
+        if options.parse(&arg, &mut args)? {
+            continue;
+        }
+
+        match arg.as_str() {
+            "--frames" => frames = parse(&arg, &next_value(&mut args, &arg)?)?,
+            _ if arg.starts_with("--") => return Err(unknown_option(&arg)),
+            _ => options.files.push(arg),
+        }
+    }
+
+    if options.files.is_empty() {
+        return Err(usage("bench [OPTIONS] [--frames N] ROM"));
+    }
+
+    let Machine {
+        mut state,
+        symbols,
+        settings,
+        ..
+    } = load(&options)?;
+    let speed = settings.speed.unwrap_or(1000);
+
+    let start = Instant::now();
+    for _ in 0..frames {
+        headless_frame(&mut state, &symbols, speed)?;
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let instructions = frames * u64::from(speed);
+    println!(
+        "{} frames of {} instructions in {:.3}s",
+        frames, speed, elapsed
+    );
+    println!(
+        "{:.2} million instructions per second, {:.1}x real time",
+        instructions as f64 / elapsed / 1e6,
+        frames as f64 / 60.0 / elapsed
+    );
+
+    Ok(())
+}
+
+fn record(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut options = MachineOptions::default();
+    let mut frames = None;
+    let mut output = None;
+    let mut input_path = None;
+
+    while let Some(arg) = args.next() {
+        if options.parse(&arg, &mut args)? {
+            continue;
+        }
+
+        match arg.as_str() {
+            "--frames" => frames = Some(parse(&arg, &next_value(&mut args, &arg)?)?),
+            "--input" => input_path = Some(next_value(&mut args, &arg)?),
+            "-o" => output = Some(PathBuf::from(next_value(&mut args, &arg)?)),
+            _ if arg.starts_with("--") => return Err(unknown_option(&arg)),
+            _ => options.files.push(arg),
+        }
+    }
+
+    let output = match output {
+        Some(output) if !options.files.is_empty() => output,
+        _ => {
+            return Err(usage(
+                "record [OPTIONS] [--frames N] [--input FILE] -o FILE ROM",
+            ))
+        }
+    };
+
+    let masks = match &input_path {
+        Some(path) => {
+            input::read_masks(&read(path)?).map_err(|err| format!("{}: {}", path, err))?
+        }
+        None => Vec::new(),
+    };
+    let frames = frames.unwrap_or(if input_path.is_some() {
+        masks.len() as u64
+    } else {
+        600
+    });
+
+    let Machine {
+        mut state,
+        symbols,
+        settings,
+        ..
+    } = load(&options)?;
+    let speed = settings.speed.unwrap_or(1000);
+    let scale = settings.screenshot_scale.unwrap_or(SCREENSHOT_SCALE);
+
+    let mut recording = Recording::start(output, &settings.palette(), scale)?;
+    for frame in 0..frames {
+        let mask = masks.get(frame as usize).copied().unwrap_or(0);
+        input::press(mask, &mut state.buttons);
+
+        headless_frame(&mut state, &symbols, speed)?;
+        recording.frame(&state)?;
+    }
+
+    let path = recording.finish()?;
+    eprintln!("Saved {}.", path.display());
+
+    Ok(())
+}
+
+enum Command {
+    Continue,
+    Step,
+    Detach,
+    Quit,
+}
+
+fn console(state: &chip8::types::State, debugger: &mut Debugger) -> Command {
+    println!("{}", debug::format_registers(state));
+    let pc = Symbolic(&state.pc, &debugger.symbols);
+    match state.fetch() {
+        Ok(instr) => println!("{}: {}", pc, Symbolic(&instr, &debugger.symbols)),
+        Err(err) => println!("{}: {:?}", pc, err),
+    }
+
+    let stdin = io::stdin();
+
+    loop {
+        print!("(chip8) ");
+        io::stdout().flush().expect("Couldn't write to stdout!");
+
+        let mut line = String::new();
+        if stdin.read_line(&mut line).expect("Couldn't read stdin!") == 0 {
+            return Command::Quit;
+        }
+
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("c") | Some("continue") => return Command::Continue,
+            Some("s") | Some("step") | None => return Command::Step,
+            Some("q") | Some("quit") => return Command::Quit,
+            Some("regs") => println!("{}", debug::format_registers(state)),
+            Some("watch") => match Watchpoint::parse(words.next().unwrap_or(""), &debugger.symbols)
+            {
+                Ok(watchpoint) => {
+                    println!("watchpoint {}: {}", debugger.watchpoints.len(), watchpoint);
+                    debugger.watchpoints.push(watchpoint);
+                }
+                Err(err) => println!("{}", err),
+            },
+            Some("break") => {
+                match Breakpoint::parse(&words.collect::<Vec<_>>().join(" "), &debugger.symbols) {
+                    Ok(breakpoint) => {
+                        println!(
+                            "breakpoint {}: {}",
+                            debugger.breakpoints.len(),
+                            Symbolic(&breakpoint, &debugger.symbols)
+                        );
+                        debugger.breakpoints.push(breakpoint);
+                    }
+                    Err(err) => println!("{}", err),
+                }
+            }
+            Some("watchreg") => match words.next().unwrap_or("").parse() {
+                Ok(reg) => debugger.watched_registers.push(reg),
+                Err(err) => println!("{}", err),
+            },
+            Some("delete") => match (words.next(), words.next()) {
+                (Some("break"), Some(n)) => match n.parse() {
+                    Ok(n) if n < debugger.breakpoints.len() => {
+                        debugger.breakpoints.remove(n);
+                    }
+                    _ => println!("no such breakpoint"),
+                },
+                (Some("reg"), Some(reg)) => match reg.parse() {
+                    Ok(reg) => debugger.watched_registers.retain(|r| *r != reg),
+                    Err(err) => println!("{}", err),
+                },
+                (Some(n), None) => match n.parse() {
+                    Ok(n) if n < debugger.watchpoints.len() => {
+                        debugger.watchpoints.remove(n);
+                    }
+                    _ => println!("no such watchpoint"),
+                },
+                _ => println!("usage: delete N, delete break N, delete reg VX"),
+            },
+            Some("info") => {
+                for (i, watchpoint) in debugger.watchpoints.iter().enumerate() {
+                    println!("watchpoint {}: {}", i, watchpoint);
+                }
+                for (i, breakpoint) in debugger.breakpoints.iter().enumerate() {
+                    println!(
+                        "breakpoint {}: {}",
+                        i,
+                        Symbolic(breakpoint, &debugger.symbols)
+                    );
+                }
+                for reg in &debugger.watched_registers {
+                    println!("watching {}", reg);
+                }
+            }
+            Some(_) => println!(
+                "commands: continue, step, regs, watch RANGE[:r|w|rw], break ADDR [if EXPR], \
+                 break if EXPR, watchreg VX, delete [break|reg] N, info, quit"
+            ),
+        }
+    }
+}
+
+/// Waits for a hotkey in the debug view, keeping the window responsive.
+fn debug_view(
+    window: &mut Window,
+    overlay: &mut Overlay,
+    state: &chip8::types::State,
+    debugger: &Debugger,
+) -> Command {
+    overlay.draw(state, debugger, true);
+
+    while window.is_open() {
+        window
+            .update_with_buffer(&overlay.buffer)
+            .expect("Couldn't update window!");
+
+        if window.is_key_pressed(Key::F5, KeyRepeat::No) {
+            return Command::Continue;
+        }
+        if window.is_key_pressed(Key::F10, KeyRepeat::Yes) {
+            return Command::Step;
+        }
+
+        thread::sleep(Duration::from_millis(1000 / 60));
+    }
+
+    Command::Quit
+}
+
+#[derive(Copy, Clone, PartialEq)]
+enum Mode {
+    Run,
+    /// Starts stopped at the first instruction.
+    Debug,
+}
+
+fn run(mut args: impl Iterator<Item = String>, mode: Mode) -> Result<(), String> {
+    let mut options = MachineOptions::default();
+    let mut trace_path = None;
+    let mut trace_format = trace::Format::Text;
+    let mut trace_filter = trace::Filter::default();
+    let mut profile = false;
+    let mut profile_folded = None;
+    let mut coverage_path = None;
+    let mut debugger = Debugger::new();
+    let mut gdb_addr = None;
+    let mut dap_addr = None;
+    let mut overlay = None;
+    let mut record_path = None;
+    let mut watchpoints = Vec::new();
+    let mut breakpoints = Vec::new();
+    let mut trace_pc = None;
+
+    while let Some(arg) = args.next() {
+        if options.parse(&arg, &mut args)? {
+            continue;
+        }
+
+        let mut value = || next_value(&mut args, &arg);
+
+        match arg.as_str() {
+            "--trace" => trace_path = Some(value()?),
+            "--trace-format" => {
+                trace_format = match value()?.as_str() {
+                    "text" => trace::Format::Text,
+                    "binary" => trace::Format::Binary,
+                    format => {
+                        return Err(format!(
+                            "invalid --trace-format {:?}: expected text or binary",
+                            format
+                        ))
+                    }
+                }
+            }
+            "--trace-pc" => trace_pc = Some(value()?),
+            "--trace-class" => {
+                let classes = value()?
+                    .split(',')
+                    .map(|class| {
+                        u8::from_str_radix(class, 16)
+                            .map_err(|_| format!("invalid opcode class {:?}", class))
+                    })
+                    .collect::<Result<_, _>>()?;
+                trace_filter.classes = Some(classes);
+            }
+            "--trace-frames" => trace_filter.frames = Some(parse_range(&value()?, 10)?),
+            "--profile" => profile = true,
+            "--profile-folded" => profile_folded = Some(value()?),
+            "--coverage" => coverage_path = Some(value()?),
+            "--watch" => watchpoints.push(value()?),
+            "--break" => breakpoints.push(value()?),
+            "--break-if" => breakpoints.push(format!("if {}", value()?)),
+            "--gdb" => gdb_addr = Some(value()?),
+            "--dap" => dap_addr = Some(value()?),
+            "--debug-view" => overlay = Some(Overlay::new()),
+            "--record" => record_path = Some(PathBuf::from(value()?)),
+            "--watch-reg" => {
+                let reg = value()?;
+                debugger.watched_registers.push(parse("--watch-reg", &reg)?);
+            }
+            _ if arg.starts_with("--") => return Err(unknown_option(&arg)),
+            _ => options.files.push(arg),
+        }
+    }
+
+    let mut frontend: Option<Box<dyn Frontend>> = None;
+
+    if let Some(addr) = dap_addr {
+        let listener = TcpListener::bind(&addr)
+            .map_err(|err| format!("couldn't listen on {}: {}", addr, err))?;
+        eprintln!("Waiting for debug adapter client on {}...", addr);
+
+        let (stream, _) = listener
+            .accept()
+            .map_err(|err| format!("couldn't accept client: {}", err))?;
+        let (server, launch) =
+            DapServer::new(stream).map_err(|err| format!("couldn't talk to client: {}", err))?;
+
+        options
+            .files
+            .insert(0, launch.program.to_string_lossy().into_owned());
+        if let Some(path) = launch.symbols {
+            options.symbols_path = Some(path.to_string_lossy().into_owned());
+        }
+        frontend = Some(Box::new(server));
+    }
+
+    if options.files.is_empty() {
+        return Err(usage("[run] [OPTIONS] ROM [FILE...]"));
+    }
+
+    let Machine {
+        mut state,
+        rom,
+        symbols,
+        source,
+        settings,
+    } = load(&options)?;
+    debugger.symbols = symbols;
+
+    match source {
+        Source::Cartridge(_) => {}
+        Source::Database(entry) => {
+            if let Some(title) = &entry.title {
+                eprintln!("Recognized {} ({}).", title, entry.platform);
+            }
         }
+        Source::Detected(detection) => {
+            if state.quirks == detection.quirks {
+                eprintln!("Unknown ROM, guessing from its instructions.");
+                detection
+                    .write_report(&debugger.symbols, &mut io::stderr())
+                    .map_err(|err| format!("couldn't write report: {}", err))?;
+            }
+        }
+    }
+
+    for watchpoint in watchpoints {
+        debugger.watchpoints.push(
+            Watchpoint::parse(&watchpoint, &debugger.symbols)
+                .map_err(|err| format!("invalid --watch {:?}: {}", watchpoint, err))?,
+        );
+    }
+
+    for breakpoint in breakpoints {
+        debugger.breakpoints.push(
+            Breakpoint::parse(&breakpoint, &debugger.symbols)
+                .map_err(|err| format!("invalid breakpoint {:?}: {}", breakpoint, err))?,
+        );
     }
 
+    if let Some(range) = trace_pc {
+        let pc = debugger
+            .symbols
+            .parse_range(&range)
+            .map_err(|err| format!("invalid --trace-pc {:?}: {}", range, err))?;
+        trace_filter.pc = Some(pc.start().0..=pc.end().0);
+    }
+
+    let mut tracer = match trace_path {
+        Some(path) => {
+            let mut tracer = Tracer::new(create(&path)?, trace_format)
+                .map_err(|err| format!("couldn't write {}: {}", path, err))?;
+            tracer.filter = trace_filter;
+            tracer.symbols = debugger.symbols.clone();
+            Some(tracer)
+        }
+        None => None,
+    };
+
+    let mut profiler = if profile || profile_folded.is_some() {
+        Some(Profiler::new())
+    } else {
+        None
+    };
+
+    let mut coverage = coverage_path.as_ref().map(|_| Coverage::new(rom));
+
+    if let Some(addr) = gdb_addr {
+        let listener = TcpListener::bind(&addr)
+            .map_err(|err| format!("couldn't listen on {}: {}", addr, err))?;
+        eprintln!("Waiting for gdb on {}...", addr);
+
+        let (stream, _) = listener
+            .accept()
+            .map_err(|err| format!("couldn't accept gdb: {}", err))?;
+        frontend = Some(Box::new(
+            GdbStub::new(stream).map_err(|err| format!("couldn't talk to gdb: {}", err))?,
+        ));
+    }
+
+    let palette = settings.palette();
+    let mut phosphor = Phosphor::new(settings.filter.unwrap_or(Filter::None));
+    let key_map = settings.key_map();
+    let record_scale = settings.screenshot_scale.unwrap_or(SCREENSHOT_SCALE);
+
+    let mut recording = match record_path {
+        Some(path) => Some(Recording::start(path, &palette, record_scale)?),
+        None => None,
+    };
+
     let mut time = Instant::now();
+    let frame_length = Duration::from_millis(1000 / 60);
+    let mut executed = 0;
 
     let device = rodio::default_output_device();
 
@@ -40,7 +1368,7 @@ fn main() {
             None
         } else {
             let sink = Sink::new(device);
-            sink.set_volume(0.5);
+            sink.set_volume(settings.volume.unwrap_or(VOLUME));
             sink.pause();
 
             let sine = SineWave::new(440);
@@ -54,36 +1382,115 @@ fn main() {
         eprintln!("Couldn't initialize audio!");
     }
 
+    let (width, height, default_scale) = if overlay.is_some() {
+        (overlay::WIDTH, overlay::HEIGHT, 2)
+    } else {
+        (64, 32, SCALE)
+    };
+
     let mut window = Window::new(
         "chip8-rs",
-        64,
-        32,
+        width,
+        height,
         WindowOptions {
-            scale: minifb::Scale::X16,
+            scale: window_scale(settings.scale.unwrap_or(default_scale)),
             ..Default::default()
         },
     )
-    .expect("Couldn't initialize window!");
+    .map_err(|err| format!("couldn't open a window: {}", err))?;
 
-    loop {
-        let instr_start: u16 = state.pc.into();
-        let instr_end: u16 = instr_start + 1;
+    let mut paused = frontend.is_some() || mode == Mode::Debug;
+
+    for stop in &debugger.breakpoints_hit(&state) {
+        println!("{}", Symbolic(stop, &debugger.symbols));
+        paused = true;
+    }
+
+    let debugger_error = |err: io::Error| format!("couldn't talk to debugger: {}", err);
+
+    while window.is_open() {
+        if let (false, Some(frontend)) = (paused, &mut frontend) {
+            paused = frontend
+                .interrupted(&mut state, &mut debugger)
+                .map_err(debugger_error)?;
+        }
+
+        if paused {
+            let command = match &mut frontend {
+                Some(frontend) => match frontend
+                    .serve(&mut state, &mut debugger)
+                    .map_err(debugger_error)?
+                {
+                    Resume::Continue => Command::Continue,
+                    Resume::Step => Command::Step,
+                    Resume::Detach => Command::Detach,
+                    Resume::Kill => Command::Quit,
+                },
+                None => match &mut overlay {
+                    Some(overlay) => debug_view(&mut window, overlay, &state, &debugger),
+                    None => console(&state, &mut debugger),
+                },
+            };
+
+            match command {
+                Command::Continue => paused = false,
+                Command::Step => {}
+                Command::Detach => {
+                    frontend = None;
+                    paused = false;
+                }
+                Command::Quit => break,
+            }
+        }
+
+        let before = tracer.as_ref().map(|_| state.clone());
+        let pc = state.pc;
+
+        let instr = state
+            .fetch()
+            .map_err(|err| eval_error(&state, &debugger.symbols, err))?;
+
+        if let Some(coverage) = &mut coverage {
+            coverage.record(&state, &instr);
+        }
 
-        let current_instr =
-            chip8::parser::instr(&state.memory[(instr_start as usize)..=(instr_end as usize)])
-                .expect("invalid instruction at pc")
-                .1;
+        let stops = debugger.eval(&instr, &mut state).map_err(|err| {
+            state.pc = pc;
+            eval_error(&state, &debugger.symbols, err)
+        })?;
 
-        current_instr.eval(&mut state);
+        for stop in &stops {
+            println!("{}", Symbolic(stop, &debugger.symbols));
+            paused = true;
+        }
+
+        if let (Some(tracer), Some(before)) = (&mut tracer, &before) {
+            tracer
+                .record(before, &state)
+                .map_err(|err| format!("couldn't write trace: {}", err))?;
+        }
+
+        if let Some(profiler) = &mut profiler {
+            profiler.record(pc, &instr, &state);
+        }
+
+        // Octo's tickrate caps the instructions run each frame.
+        executed += 1;
+        if matches!(settings.speed, Some(rate) if executed >= rate) {
+            thread::sleep(frame_length.checked_sub(time.elapsed()).unwrap_or_default());
+        }
 
         let now = Instant::now();
-        if now - time > Duration::from_millis(1000 / 60) {
+        let frame = now - time > frame_length;
+        if frame {
             time = now;
-            if state.timer > 0 {
-                state.timer -= 1;
-            }
-            if state.sound_timer > 0 {
-                state.sound_timer -= 1;
+            executed = 0;
+            state.tick_timers();
+            phosphor.end_frame(&state.bit_gfx);
+
+            if let Some(Err(err)) = recording.as_mut().map(|r| r.frame(&state)) {
+                eprintln!("{}", err);
+                recording = None;
             }
         }
 
@@ -95,28 +1502,131 @@ fn main() {
             }
         }
 
-        for (i, e) in (&state.bit_gfx[..])
-            .as_bitslice::<BigEndian>()
-            .iter()
-            .enumerate()
-        {
-            if e {
-                state.pix_gfx[i] = 0xFFFFFFFF;
-            } else {
-                state.pix_gfx[i] = 0;
+        phosphor.render(&palette, &state.bit_gfx, &mut state.pix_gfx);
+
+        match &mut overlay {
+            Some(overlay) => {
+                if frame {
+                    overlay.draw(&state, &debugger, paused);
+                }
+
+                window.update_with_buffer(&overlay.buffer)
             }
+            None => window.update_with_buffer(&state.pix_gfx[..]),
         }
+        .map_err(|err| format!("couldn't update the window: {}", err))?;
 
-        window
-            .update_with_buffer(&state.pix_gfx[..])
-            .expect("Couldn't update window!");
+        if overlay.is_some() && window.is_key_pressed(Key::F6, KeyRepeat::No) {
+            paused = true;
+        }
 
-        for button in &chip8::types::BUTTON_KEYS {
-            if window.is_key_down(*button) {
-                state.buttons[chip8::types::Button::from_key(*button).unwrap()] = true;
-            } else {
-                state.buttons[chip8::types::Button::from_key(*button).unwrap()] = false;
+        if window.is_key_pressed(Key::F12, KeyRepeat::No) {
+            let path = capture_path(&options.files[0], "png");
+            let scale = settings.screenshot_scale.unwrap_or(SCREENSHOT_SCALE);
+
+            // Losing a screenshot isn't worth losing the game over.
+            match screenshot(&path, &palette, &state, scale) {
+                Ok(()) => eprintln!("Saved {}.", path.display()),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+
+        if window.is_key_pressed(Key::F11, KeyRepeat::No) {
+            let result = match recording.take() {
+                Some(recording) => recording
+                    .finish()
+                    .map(|path| format!("Saved {}.", path.display())),
+                None => {
+                    let path = capture_path(&options.files[0], "gif");
+                    Recording::start(path, &palette, record_scale).map(|started| {
+                        let message = format!("Recording to {}...", started.path.display());
+                        recording = Some(started);
+                        message
+                    })
+                }
+            };
+
+            match result {
+                Ok(message) => eprintln!("{}", message),
+                Err(err) => eprintln!("{}", err),
             }
         }
+
+        key_map.update(|key| window.is_key_down(key), &mut state.buttons);
+    }
+
+    if let Some(recording) = recording {
+        let path = recording.finish()?;
+        eprintln!("Saved {}.", path.display());
+    }
+
+    if let Some(tracer) = &mut tracer {
+        tracer
+            .flush()
+            .map_err(|err| format!("couldn't write trace: {}", err))?;
+    }
+
+    if let (Some(coverage), Some(path)) = (&coverage, coverage_path) {
+        let mut file = create(&path)?;
+
+        if path.ends_with(".json") {
+            coverage.write_json(&mut file)
+        } else {
+            coverage.write_hex(&state.memory, &mut file)
+        }
+        .and_then(|_| file.flush())
+        .map_err(|err| format!("couldn't write {}: {}", path, err))?;
+    }
+
+    if let Some(profiler) = &profiler {
+        if profile {
+            profiler
+                .write_report(&state, &debugger.symbols, &mut io::stdout())
+                .map_err(|err| format!("couldn't write profile: {}", err))?;
+        }
+
+        if let Some(path) = profile_folded {
+            let mut file = create(&path)?;
+            profiler
+                .write_folded(&debugger.symbols, &mut file)
+                .and_then(|_| file.flush())
+                .map_err(|err| format!("couldn't write {}: {}", path, err))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn main() {
+    let mut args = env::args().skip(1).peekable();
+
+    let command = match args.peek().map(String::as_str) {
+        Some("run") | Some("debug") | Some("bench") | Some("record") | Some("info")
+        | Some("config") | Some("disasm") | Some("asm") | Some("decompile") | Some("analyze")
+        | Some("tracediff") | Some("help") | Some("--help") | Some("-h") => args.next(),
+        _ => None,
+    };
+
+    let result = match command.as_deref() {
+        None | Some("run") => run(args, Mode::Run),
+        Some("debug") => run(args, Mode::Debug),
+        Some("bench") => bench(args),
+        Some("record") => record(args),
+        Some("info") => info(args),
+        Some("config") => config(args),
+        Some("disasm") => disasm(args),
+        Some("asm") => asm(args),
+        Some("decompile") => decompile(args),
+        Some("analyze") => analyze(args),
+        Some("tracediff") => tracediff(args),
+        Some(_) => {
+            print!("{}", USAGE);
+            Ok(())
+        }
+    };
+
+    if let Err(err) = result {
+        eprintln!("chip8: {}", err);
+        process::exit(1);
     }
 }
@@ -0,0 +1,834 @@
+//! An assembler for Octo, the CHIP-8 assembly language. Covers labels,
+//! `:alias`, `:const`, `:calc`, `:macro`, `:next`, `:org`, `:byte`, `:call`
+//! and the `loop`/`again`/`while` and `if`/`then`/`begin`/`else`/`end`
+//! structures.
+
+use crate::linemap::LineMap;
+use crate::symbols::Symbols;
+use crate::types::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+const START: u16 = 0x200;
+
+/// Stops runaway recursive macros.
+const MAX_EXPANSIONS: usize = 100_000;
+
+#[derive(Debug, Clone)]
+pub struct Program {
+    /// The ROM, to be loaded at 0x200.
+    pub bytes: Vec<u8>,
+    pub symbols: Symbols,
+    pub lines: LineMap,
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    line: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+/// A use of a label before it's defined, patched at the end.
+#[derive(Debug, Clone)]
+struct Fixup {
+    at: u16,
+    name: String,
+    line: u32,
+}
+
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+
+    for (n, line) in source.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+
+        for word in line.split_whitespace() {
+            // Allow `(1+2)` in calculations without spaces around brackets.
+            let mut start = 0;
+            for (i, c) in word.char_indices() {
+                if "(){}".contains(c) && word.len() > 1 {
+                    if start < i {
+                        tokens.push(Token {
+                            text: word[start..i].to_string(),
+                            line: n as u32 + 1,
+                        });
+                    }
+                    tokens.push(Token {
+                        text: c.to_string(),
+                        line: n as u32 + 1,
+                    });
+                    start = i + 1;
+                }
+            }
+
+            if start < word.len() {
+                tokens.push(Token {
+                    text: word[start..].to_string(),
+                    line: n as u32 + 1,
+                });
+            }
+        }
+    }
+
+    tokens
+}
+
+fn parse_number(s: &str) -> Option<f64> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+
+    let n = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+
+    Some(if negative { -n } else { n })
+}
+
+fn parse_register(s: &str) -> Option<Register> {
+    match s.as_bytes() {
+        [b'v', n] | [b'V', n] => (*n as char).to_digit(16).and_then(|n| Register::n(n as u8)),
+        _ => None,
+    }
+}
+
+struct Assembler<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    line: u32,
+    file: &'a Path,
+    memory: Vec<u8>,
+    here: u16,
+    end: u16,
+    /// Whether 0x200 is still held for a `jump main`.
+    reserved: bool,
+    labels: HashMap<String, u16>,
+    order: Vec<String>,
+    constants: HashMap<String, f64>,
+    aliases: HashMap<String, Register>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    next: Option<String>,
+    /// The start of each open `loop`, with the `while` jumps to patch.
+    loops: Vec<(u16, Vec<u16>)>,
+    /// The jump to patch for each open `begin` or `else`.
+    begins: Vec<u16>,
+    expansions: usize,
+    lines: LineMap,
+}
+
+impl<'a> Assembler<'a> {
+    fn new(source: &str, file: &'a Path) -> Self {
+        Assembler {
+            tokens: tokenize(source),
+            pos: 0,
+            line: 1,
+            file,
+            memory: vec![0; 0x1000],
+            here: START + 2,
+            end: START + 2,
+            reserved: true,
+            labels: HashMap::new(),
+            order: Vec::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            fixups: Vec::new(),
+            next: None,
+            loops: Vec::new(),
+            begins: Vec::new(),
+            expansions: 0,
+            lines: LineMap::new(),
+        }
+    }
+
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, String> {
+        Err(format!("line {}: {}", self.line, message.into()))
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|token| token.text.as_str())
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        match self.tokens.get(self.pos) {
+            Some(token) => {
+                self.line = token.line;
+                self.pos += 1;
+                Ok(token.text.clone())
+            }
+            None => self.error("unexpected end of file"),
+        }
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            self.error(format!("expected {:?}, found {:?}", expected, token))
+        }
+    }
+
+    fn name(&mut self) -> Result<String, String> {
+        let name = self.next()?;
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && parse_register(&name).is_none();
+
+        if valid {
+            Ok(name)
+        } else {
+            self.error(format!("invalid name {:?}", name))
+        }
+    }
+
+    fn register(&mut self) -> Result<Register, String> {
+        let token = self.next()?;
+        match self.as_register(&token) {
+            Some(reg) => Ok(reg),
+            None => self.error(format!("expected a register, found {:?}", token)),
+        }
+    }
+
+    fn as_register(&self, token: &str) -> Option<Register> {
+        parse_register(token).or_else(|| self.aliases.get(token).cloned())
+    }
+
+    /// A number or constant, or a `{ ... }` calculation.
+    fn number(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+
+        if token == "{" {
+            let value = self.calc()?;
+            self.expect("}")?;
+            return Ok(value);
+        }
+
+        match parse_number(&token).or_else(|| self.constants.get(&token).cloned()) {
+            Some(n) => Ok(n),
+            None => self.error(format!("expected a number, found {:?}", token)),
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let n = self.number()?.floor();
+
+        if (-128.0..=255.0).contains(&n) {
+            Ok(n as i64 as u8)
+        } else {
+            self.error(format!("{} doesn't fit in a byte", n))
+        }
+    }
+
+    fn nibble(&mut self) -> Result<u8, String> {
+        let n = self.number()?.floor();
+
+        if (0.0..=15.0).contains(&n) {
+            Ok(n as u8)
+        } else {
+            self.error(format!("{} doesn't fit in a nibble", n))
+        }
+    }
+
+    /// An address for the instruction about to be emitted at `here`. Labels
+    /// that aren't defined yet get patched once they are.
+    fn address(&mut self) -> Result<Address, String> {
+        let token = self.next()?;
+
+        if let Some(addr) = self.labels.get(&token) {
+            return Ok(Address(*addr));
+        }
+
+        let n = if token == "{" {
+            let value = self.calc()?;
+            self.expect("}")?;
+            value
+        } else if let Some(n) = parse_number(&token).or_else(|| self.constants.get(&token).cloned())
+        {
+            n
+        } else if self.name_is_free(&token) {
+            self.fixups.push(Fixup {
+                at: self.here,
+                name: token,
+                line: self.line,
+            });
+            return Ok(Address(0));
+        } else {
+            return self.error(format!("expected an address, found {:?}", token));
+        };
+
+        let n = n.floor();
+        if (0.0..=4095.0).contains(&n) {
+            Ok(Address(n as u16))
+        } else {
+            self.error(format!("{} isn't a valid address", n))
+        }
+    }
+
+    fn name_is_free(&self, token: &str) -> bool {
+        token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && self.as_register(token).is_none()
+            && !self.macros.contains_key(token)
+    }
+
+    /// Evaluates a `:calc` expression. Like Octo, operators have no
+    /// precedence and are applied right to left.
+    fn calc(&mut self) -> Result<f64, String> {
+        let lhs = self.calc_term()?;
+
+        match self.peek() {
+            Some("}") | Some(")") | None => return Ok(lhs),
+            _ => {}
+        }
+
+        let op = self.next()?;
+        let rhs = self.calc()?;
+
+        Ok(match op.as_str() {
+            "+" => lhs + rhs,
+            "-" => lhs - rhs,
+            "*" => lhs * rhs,
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "&" => (lhs as i64 & rhs as i64) as f64,
+            "|" => (lhs as i64 | rhs as i64) as f64,
+            "^" => (lhs as i64 ^ rhs as i64) as f64,
+            "<<" => ((lhs as i64) << (rhs as i64)) as f64,
+            ">>" => (lhs as i64 >> rhs as i64) as f64,
+            "pow" => lhs.powf(rhs),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "<" => (lhs < rhs) as u8 as f64,
+            ">" => (lhs > rhs) as u8 as f64,
+            "<=" => (lhs <= rhs) as u8 as f64,
+            ">=" => (lhs >= rhs) as u8 as f64,
+            "==" => (lhs == rhs) as u8 as f64,
+            "!=" => (lhs != rhs) as u8 as f64,
+            _ => return self.error(format!("unknown operator {:?}", op)),
+        })
+    }
+
+    fn calc_term(&mut self) -> Result<f64, String> {
+        let token = self.next()?;
+
+        Ok(match token.as_str() {
+            "(" => {
+                let value = self.calc()?;
+                self.expect(")")?;
+                value
+            }
+            "-" => -self.calc_term()?,
+            "~" => !(self.calc_term()? as i64) as f64,
+            "!" => (self.calc_term()? == 0.0) as u8 as f64,
+            "abs" => self.calc_term()?.abs(),
+            "floor" => self.calc_term()?.floor(),
+            "ceil" => self.calc_term()?.ceil(),
+            "sqrt" => self.calc_term()?.sqrt(),
+            "HERE" => f64::from(self.here),
+            _ => match parse_number(&token)
+                .or_else(|| self.constants.get(&token).cloned())
+                .or_else(|| self.labels.get(&token).map(|addr| f64::from(*addr)))
+            {
+                Some(n) => n,
+                None => return self.error(format!("unknown value {:?} in calculation", token)),
+            },
+        })
+    }
+
+    fn emit_byte(&mut self, byte: u8) -> Result<(), String> {
+        if self.here > 0xFFF {
+            return self.error("program doesn't fit in memory");
+        }
+
+        self.memory[usize::from(self.here)] = byte;
+        self.here += 1;
+        self.end = self.end.max(self.here);
+        Ok(())
+    }
+
+    fn emit(&mut self, instr: Instruction) -> Result<(), String> {
+        if let Some(name) = self.next.take() {
+            self.define(name, self.here + 1)?;
+        }
+
+        self.lines.insert(Address(self.here), self.file, self.line);
+
+        let [hi, lo] = instr.encode().to_be_bytes();
+        self.emit_byte(hi)?;
+        self.emit_byte(lo)
+    }
+
+    /// Points the jump at `at` to `target`.
+    fn patch(&mut self, at: u16, target: u16) {
+        let at = usize::from(at);
+        self.memory[at] = (self.memory[at] & 0xF0) | (target >> 8) as u8;
+        self.memory[at + 1] = target as u8;
+    }
+
+    fn define(&mut self, name: String, addr: u16) -> Result<(), String> {
+        if self.labels.contains_key(&name) || self.constants.contains_key(&name) {
+            return self.error(format!("{:?} is already defined", name));
+        }
+
+        self.labels.insert(name.clone(), addr);
+        self.order.push(name);
+        Ok(())
+    }
+
+    fn label(&mut self) -> Result<(), String> {
+        let name = self.name()?;
+
+        // `main` right at the start doesn't need the jump to it.
+        if name == "main" && self.reserved && self.here == START + 2 && self.end == START + 2 {
+            self.reserved = false;
+            self.here = START;
+            self.end = START;
+        }
+
+        self.define(name, self.here)
+    }
+
+    /// Parses a condition, giving the skip that jumps over the next
+    /// instruction when it's false, or when it's true if `inverted`.
+    fn condition(&mut self, inverted: bool) -> Result<Instruction, String> {
+        use Instruction::*;
+
+        let reg = self.register()?;
+        let op = self.next()?;
+
+        let instr = match op.as_str() {
+            "key" => SkipUnpressed(reg),
+            "-key" => SkipPressed(reg),
+            "==" | "!=" => {
+                let rhs = self.peek().and_then(|token| self.as_register(token));
+                let equal = op == "==";
+
+                match rhs {
+                    Some(r2) => {
+                        self.pos += 1;
+                        if equal {
+                            SkipNeqReg(reg, r2)
+                        } else {
+                            SkipEqReg(reg, r2)
+                        }
+                    }
+                    None => {
+                        let n = self.byte()?;
+                        if equal {
+                            SkipNeqImm(reg, n)
+                        } else {
+                            SkipEqImm(reg, n)
+                        }
+                    }
+                }
+            }
+            _ => return self.error(format!("unsupported comparison {:?}", op)),
+        };
+
+        Ok(if !inverted {
+            instr
+        } else {
+            match instr {
+                SkipUnpressed(reg) => SkipPressed(reg),
+                SkipPressed(reg) => SkipUnpressed(reg),
+                SkipNeqReg(r1, r2) => SkipEqReg(r1, r2),
+                SkipEqReg(r1, r2) => SkipNeqReg(r1, r2),
+                SkipNeqImm(reg, n) => SkipEqImm(reg, n),
+                SkipEqImm(reg, n) => SkipNeqImm(reg, n),
+                _ => unreachable!(),
+            }
+        })
+    }
+
+    fn assignment(&mut self, reg: Register) -> Result<(), String> {
+        use Instruction::*;
+
+        let op = self.next()?;
+        let rhs = self.peek().and_then(|token| self.as_register(token));
+
+        if let Some(r2) = rhs {
+            self.pos += 1;
+
+            let instr = match op.as_str() {
+                ":=" => SetReg(reg, r2),
+                "|=" => OrReg(reg, r2),
+                "&=" => AndReg(reg, r2),
+                "^=" => XorReg(reg, r2),
+                "+=" => AddReg(reg, r2),
+                "-=" => SubReg(reg, r2),
+                "=-" => RevSubReg(reg, r2),
+                ">>=" => RShiftReg(reg, r2),
+                "<<=" => LShiftReg(reg, r2),
+                _ => return self.error(format!("unknown operator {:?}", op)),
+            };
+
+            return self.emit(instr);
+        }
+
+        let instr = match (op.as_str(), self.peek()) {
+            (":=", Some("random")) => {
+                self.pos += 1;
+                Rand(reg, self.byte()?)
+            }
+            (":=", Some("delay")) => {
+                self.pos += 1;
+                GetTimer(reg)
+            }
+            (":=", Some("key")) => {
+                self.pos += 1;
+                WaitPress(reg)
+            }
+            (":=", _) => SetImm(reg, self.byte()?),
+            ("+=", _) => AddImm(reg, self.byte()?),
+            ("-=", _) => AddImm(reg, self.byte()?.wrapping_neg()),
+            _ => return self.error(format!("unknown operator {:?}", op)),
+        };
+
+        self.emit(instr)
+    }
+
+    fn directive(&mut self, token: &str) -> Result<(), String> {
+        match token {
+            ":" => self.label()?,
+            ":next" => {
+                let name = self.name()?;
+                self.next = Some(name);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let reg = self.register()?;
+                self.aliases.insert(name, reg);
+            }
+            ":const" | ":calc" => {
+                let name = self.name()?;
+                let value = if token == ":calc" {
+                    self.expect("{")?;
+                    let value = self.calc()?;
+                    self.expect("}")?;
+                    value
+                } else {
+                    self.number()?
+                };
+
+                if self.labels.contains_key(&name) {
+                    return self.error(format!("{:?} is already a label", name));
+                }
+                self.constants.insert(name, value);
+            }
+            ":macro" => {
+                let name = self.name()?;
+                let mut args = Vec::new();
+
+                while self.peek() != Some("{") {
+                    args.push(self.name()?);
+                }
+                self.expect("{")?;
+
+                let mut body = Vec::new();
+                let mut depth = 1;
+
+                loop {
+                    let token = self.tokens.get(self.pos).cloned();
+                    let token = match token {
+                        Some(token) => token,
+                        None => return self.error("unterminated macro"),
+                    };
+                    self.pos += 1;
+
+                    match token.text.as_str() {
+                        "{" => depth += 1,
+                        "}" => depth -= 1,
+                        _ => {}
+                    }
+
+                    if depth == 0 {
+                        break;
+                    }
+                    body.push(token);
+                }
+
+                self.macros.insert(name, Macro { args, body });
+            }
+            ":org" => {
+                let addr = self.number()?.floor();
+                if !(0.0..=4095.0).contains(&addr) {
+                    return self.error(format!("{} isn't a valid address", addr));
+                }
+                self.here = addr as u16;
+            }
+            ":byte" => {
+                let byte = self.byte()?;
+                self.emit_byte(byte)?;
+            }
+            ":call" => {
+                let addr = self.address()?;
+                self.emit(Instruction::Call(addr))?;
+            }
+            _ => return self.error(format!("unknown directive {:?}", token)),
+        }
+
+        Ok(())
+    }
+
+    fn expand(&mut self, name: &str) -> Result<(), String> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return self.error("too many macro expansions");
+        }
+
+        let mac = self.macros[name].clone();
+        let mut args = HashMap::new();
+
+        for arg in &mac.args {
+            args.insert(arg.clone(), self.next()?);
+        }
+
+        let line = self.line;
+        let body: Vec<_> = mac
+            .body
+            .iter()
+            .map(|token| Token {
+                text: args.get(&token.text).unwrap_or(&token.text).clone(),
+                line,
+            })
+            .collect();
+
+        self.tokens.splice(self.pos..self.pos, body);
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        use Instruction::*;
+
+        let token = self.next()?;
+
+        if token.starts_with(':') {
+            return self.directive(&token);
+        }
+
+        if let Some(reg) = self.as_register(&token) {
+            return self.assignment(reg);
+        }
+
+        match token.as_str() {
+            "clear" => self.emit(ClearDisplay)?,
+            "return" | ";" => self.emit(Return)?,
+            "jump" => {
+                let addr = self.address()?;
+                self.emit(Goto(addr))?;
+            }
+            "jump0" => {
+                let addr = self.address()?;
+                self.emit(IndexedJump(addr))?;
+            }
+            "native" => {
+                let addr = self.address()?;
+                self.emit(RcaCall(addr))?;
+            }
+            "i" => {
+                let op = self.next()?;
+                match (op.as_str(), self.peek()) {
+                    (":=", Some("hex")) => {
+                        self.pos += 1;
+                        let reg = self.register()?;
+                        self.emit(SpriteAddr(reg))?;
+                    }
+                    (":=", _) => {
+                        let addr = self.address()?;
+                        self.emit(SetAddr(addr))?;
+                    }
+                    ("+=", _) => {
+                        let reg = self.register()?;
+                        self.emit(AddAddr(reg))?;
+                    }
+                    _ => return self.error(format!("unknown operator {:?}", op)),
+                }
+            }
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                let reg = self.register()?;
+                self.emit(if token == "delay" {
+                    SetTimer(reg)
+                } else {
+                    SetSoundTimer(reg)
+                })?;
+            }
+            "sprite" => {
+                let x = self.register()?;
+                let y = self.register()?;
+                let h = self.nibble()?;
+                self.emit(Draw(x, y, h))?;
+            }
+            "bcd" => {
+                let reg = self.register()?;
+                self.emit(BCD(reg))?;
+            }
+            "save" => {
+                let reg = self.register()?;
+                self.emit(RegDump(reg))?;
+            }
+            "load" => {
+                let reg = self.register()?;
+                self.emit(RegLoad(reg))?;
+            }
+            "loop" => self.loops.push((self.here, Vec::new())),
+            "while" => {
+                if self.loops.is_empty() {
+                    return self.error("while outside of a loop");
+                }
+
+                let skip = self.condition(true)?;
+                self.emit(skip)?;
+
+                let jump = self.here;
+                self.emit(Goto(Address(0)))?;
+                self.loops.last_mut().unwrap().1.push(jump);
+            }
+            "again" => {
+                let (start, whiles) = match self.loops.pop() {
+                    Some(open) => open,
+                    None => return self.error("again without loop"),
+                };
+
+                self.emit(Goto(Address(start)))?;
+                for jump in whiles {
+                    self.patch(jump, self.here);
+                }
+            }
+            "if" => {
+                // `begin` needs the opposite skip, so look ahead for it.
+                let mut end = self.pos;
+                while end < self.tokens.len()
+                    && !["then", "begin"].contains(&&*self.tokens[end].text)
+                {
+                    end += 1;
+                }
+                let begin = self.tokens.get(end).map(|t| t.text.as_str()) == Some("begin");
+
+                let skip = self.condition(begin)?;
+                let word = self.next()?;
+                if word != "then" && word != "begin" {
+                    return self.error(format!("expected then or begin, found {:?}", word));
+                }
+
+                self.emit(skip)?;
+
+                if begin {
+                    self.begins.push(self.here);
+                    self.emit(Goto(Address(0)))?;
+                }
+            }
+            "else" => {
+                let jump = match self.begins.pop() {
+                    Some(jump) => jump,
+                    None => return self.error("else without begin"),
+                };
+
+                let end = self.here;
+                self.emit(Goto(Address(0)))?;
+                self.patch(jump, self.here);
+                self.begins.push(end);
+            }
+            "end" => match self.begins.pop() {
+                Some(jump) => self.patch(jump, self.here),
+                None => return self.error("end without begin"),
+            },
+            _ => {
+                if let Some(n) =
+                    parse_number(&token).or_else(|| self.constants.get(&token).cloned())
+                {
+                    let n = n.floor();
+                    if !(-128.0..=255.0).contains(&n) {
+                        return self.error(format!("{} doesn't fit in a byte", n));
+                    }
+                    self.emit_byte(n as i64 as u8)?;
+                } else if self.macros.contains_key(&token) {
+                    self.expand(&token)?;
+                } else if self.name_is_free(&token) {
+                    self.pos -= 1;
+                    let addr = self.address()?;
+                    self.emit(Call(addr))?;
+                } else {
+                    return self.error(format!("unexpected {:?}", token));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<Program, String> {
+        if !self.loops.is_empty() {
+            return self.error("loop without again");
+        }
+        if !self.begins.is_empty() {
+            return self.error("begin without end");
+        }
+        if let Some(name) = &self.next {
+            return self.error(format!(":next {} isn't followed by an instruction", name));
+        }
+
+        let main = match self.labels.get("main") {
+            Some(main) => *main,
+            None => return Err("no main label".to_string()),
+        };
+
+        if self.reserved {
+            let [hi, lo] = Instruction::Goto(Address(main)).encode().to_be_bytes();
+            self.memory[usize::from(START)] = hi;
+            self.memory[usize::from(START) + 1] = lo;
+        }
+
+        for fixup in std::mem::take(&mut self.fixups) {
+            match self.labels.get(&fixup.name) {
+                Some(addr) => self.patch(fixup.at, *addr),
+                None => {
+                    return Err(format!(
+                        "line {}: undefined label {:?}",
+                        fixup.line, fixup.name
+                    ))
+                }
+            }
+        }
+
+        let mut symbols = Symbols::new();
+        for name in &self.order {
+            symbols.insert(name.as_str(), Address(self.labels[name]));
+        }
+
+        let end = usize::from(self.end.max(START));
+        Ok(Program {
+            bytes: self.memory[usize::from(START)..end].to_vec(),
+            symbols,
+            lines: self.lines,
+        })
+    }
+}
+
+/// Assembles Octo source. `file` is the name the line map refers to.
+pub fn assemble(source: &str, file: impl AsRef<Path>) -> Result<Program, String> {
+    let file: PathBuf = file.as_ref().to_path_buf();
+    let mut asm = Assembler::new(source, &file);
+
+    while asm.pos < asm.tokens.len() {
+        asm.statement()?;
+    }
+
+    asm.finish()
+}
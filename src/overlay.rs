@@ -0,0 +1,326 @@
+//! A debugger layout drawn into a framebuffer alongside the game view:
+//! registers, stack and timers beside the display, with disassembly around
+//! PC and a hex view around I below it.
+
+use crate::debug::Debugger;
+use crate::symbols::Symbolic;
+use crate::types::*;
+
+pub const WIDTH: usize = 480;
+pub const HEIGHT: usize = 304;
+
+const GAME_SCALE: usize = 4;
+
+const CHAR_WIDTH: usize = 6;
+const LINE_HEIGHT: usize = 9;
+
+const BACKGROUND: u32 = 0x10_10_18;
+const BORDER: u32 = 0x40_40_50;
+const TEXT: u32 = 0xE0_E0_E0;
+const LABEL: u32 = 0x80_80_90;
+const HIGHLIGHT: u32 = 0xFF_D0_40;
+const BREAKPOINT: u32 = 0xFF_50_50;
+
+pub struct Overlay {
+    pub buffer: Vec<u32>,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Overlay {
+            buffer: vec![BACKGROUND; WIDTH * HEIGHT],
+        }
+    }
+}
+
+impl Overlay {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        for row in self.buffer.chunks_mut(WIDTH).skip(y).take(height) {
+            for pixel in row.iter_mut().skip(x).take(width) {
+                *pixel = color;
+            }
+        }
+    }
+
+    /// Draws `text` with its top left corner at `x`, `y`, clipping at the
+    /// edge of the buffer.
+    fn text(&mut self, x: usize, y: usize, text: &str, color: u32) {
+        for (i, c) in text.chars().enumerate() {
+            let glyph = match c {
+                ' '..='~' => &FONT[c as usize - ' ' as usize],
+                _ => &FONT['?' as usize - ' ' as usize],
+            };
+
+            for (dx, column) in glyph.iter().enumerate() {
+                for dy in 0..7 {
+                    let (px, py) = (x + i * CHAR_WIDTH + dx, y + dy);
+
+                    if column & (1 << dy) != 0 && px < WIDTH && py < HEIGHT {
+                        self.buffer[py * WIDTH + px] = color;
+                    }
+                }
+            }
+        }
+    }
+
+    fn game(&mut self, state: &State) {
+        for (y, row) in state.pix_gfx.chunks(64).enumerate() {
+            for (x, pixel) in row.iter().enumerate() {
+                self.fill(
+                    x * GAME_SCALE,
+                    y * GAME_SCALE,
+                    GAME_SCALE,
+                    GAME_SCALE,
+                    *pixel,
+                );
+            }
+        }
+    }
+
+    fn registers(&mut self, x: usize, state: &State) {
+        let line = |n: usize| n * LINE_HEIGHT + 2;
+
+        self.text(x, line(0), "PC", LABEL);
+        self.text(x + 3 * CHAR_WIDTH, line(0), &state.pc.to_string(), TEXT);
+        self.text(x + 10 * CHAR_WIDTH, line(0), "I", LABEL);
+        self.text(x + 12 * CHAR_WIDTH, line(0), &state.i_reg.to_string(), TEXT);
+
+        for (i, (reg, value)) in state.registers.iter().enumerate() {
+            let (col, row) = (i % 4, i / 4);
+            let x = x + col * 7 * CHAR_WIDTH;
+
+            self.text(x, line(row + 2), &reg.to_string(), LABEL);
+            self.text(
+                x + 3 * CHAR_WIDTH,
+                line(row + 2),
+                &format!("{:02X}", value),
+                TEXT,
+            );
+        }
+
+        self.text(x, line(7), "DT", LABEL);
+        self.text(
+            x + 3 * CHAR_WIDTH,
+            line(7),
+            &format!("{:02X}", state.timer),
+            TEXT,
+        );
+        self.text(x + 7 * CHAR_WIDTH, line(7), "ST", LABEL);
+        self.text(
+            x + 10 * CHAR_WIDTH,
+            line(7),
+            &format!("{:02X}", state.sound_timer),
+            TEXT,
+        );
+
+        self.text(x, line(9), "Stack", LABEL);
+        for (i, ret) in state.call_stack.iter().rev().enumerate() {
+            let (col, row) = (i % 5, i / 5);
+            self.text(
+                x + col * 7 * CHAR_WIDTH,
+                line(10 + row),
+                &ret.to_string(),
+                TEXT,
+            );
+        }
+    }
+
+    fn disassembly(&mut self, y: usize, lines: usize, state: &State, debugger: &Debugger) {
+        let before = lines / 2;
+
+        for row in 0..lines {
+            let addr = state
+                .pc
+                .wrapping_add(2 * row as u16)
+                .wrapping_sub(2 * before as u16);
+            let bytes = [
+                state.memory[addr.index()],
+                state.memory[addr.wrapping_add(1).index()],
+            ];
+            let instr = match crate::parser::decode(&bytes) {
+                Ok((_, instr)) => Symbolic(&instr, &debugger.symbols).to_string(),
+                Err(_) => "???".to_string(),
+            };
+
+            let breakpoint = debugger.breakpoints.iter().any(|b| b.address == Some(addr));
+            let color = if addr == state.pc { HIGHLIGHT } else { TEXT };
+
+            let y = y + row * LINE_HEIGHT;
+            if breakpoint {
+                self.text(2, y, "*", BREAKPOINT);
+            }
+            if addr == state.pc {
+                self.text(2 + CHAR_WIDTH, y, ">", HIGHLIGHT);
+            }
+            self.text(2 + 3 * CHAR_WIDTH, y, &addr.to_string(), LABEL);
+            self.text(
+                2 + 9 * CHAR_WIDTH,
+                y,
+                &format!("{:02X}{:02X}", bytes[0], bytes[1]),
+                LABEL,
+            );
+            self.text(2 + 14 * CHAR_WIDTH, y, &instr, color);
+        }
+    }
+
+    fn memory(&mut self, x: usize, y: usize, lines: usize, state: &State) {
+        // Start a few rows above I so what it points at is in view.
+        let first = (state.i_reg.0 as usize & !7).saturating_sub(8 * 4);
+        let first = first.min(state.memory.len() - 8 * lines);
+
+        for row in 0..lines {
+            let base = first + row * 8;
+            let y = y + row * LINE_HEIGHT;
+
+            self.text(x, y, &format!("{:03X}", base), LABEL);
+
+            for col in 0..8 {
+                let addr = base + col;
+                let color = if addr == state.i_reg.index() {
+                    HIGHLIGHT
+                } else {
+                    TEXT
+                };
+
+                self.text(
+                    x + (5 + col * 3) * CHAR_WIDTH,
+                    y,
+                    &format!("{:02X}", state.memory[addr]),
+                    color,
+                );
+            }
+        }
+    }
+
+    /// Redraws everything from `state`.
+    pub fn draw(&mut self, state: &State, debugger: &Debugger, paused: bool) {
+        let game_width = 64 * GAME_SCALE;
+        let game_height = 32 * GAME_SCALE;
+        let panels = game_height + 8;
+        let lines = (HEIGHT - panels - LINE_HEIGHT - 4) / LINE_HEIGHT;
+
+        self.fill(0, 0, WIDTH, HEIGHT, BACKGROUND);
+        self.game(state);
+        self.fill(game_width, 0, 1, HEIGHT - LINE_HEIGHT - 2, BORDER);
+        self.fill(0, game_height, WIDTH, 1, BORDER);
+        self.fill(0, HEIGHT - LINE_HEIGHT - 2, WIDTH, 1, BORDER);
+
+        self.registers(game_width + 8, state);
+        self.disassembly(panels, lines, state, debugger);
+        self.memory(game_width + 8, panels, lines, state);
+
+        let status = if paused { "PAUSED " } else { "RUNNING" };
+        self.text(2, HEIGHT - LINE_HEIGHT, status, HIGHLIGHT);
+        self.text(
+            2 + 9 * CHAR_WIDTH,
+            HEIGHT - LINE_HEIGHT,
+            "F5 continue  F6 pause  F10 step",
+            LABEL,
+        );
+    }
+}
+
+/// A 5x7 font covering printable ASCII, one byte per column with the top
+/// row in the low bit.
+#[rustfmt::skip]
+const FONT: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00], // !
+    [0x00, 0x07, 0x00, 0x07, 0x00], // "
+    [0x14, 0x7F, 0x14, 0x7F, 0x14], // #
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12], // $
+    [0x23, 0x13, 0x08, 0x64, 0x62], // %
+    [0x36, 0x49, 0x55, 0x22, 0x50], // &
+    [0x00, 0x05, 0x03, 0x00, 0x00], // '
+    [0x00, 0x1C, 0x22, 0x41, 0x00], // (
+    [0x00, 0x41, 0x22, 0x1C, 0x00], // )
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08], // *
+    [0x08, 0x08, 0x3E, 0x08, 0x08], // +
+    [0x00, 0x50, 0x30, 0x00, 0x00], // ,
+    [0x08, 0x08, 0x08, 0x08, 0x08], // -
+    [0x00, 0x60, 0x60, 0x00, 0x00], // .
+    [0x20, 0x10, 0x08, 0x04, 0x02], // /
+    [0x3E, 0x51, 0x49, 0x45, 0x3E], // 0
+    [0x00, 0x42, 0x7F, 0x40, 0x00], // 1
+    [0x42, 0x61, 0x51, 0x49, 0x46], // 2
+    [0x21, 0x41, 0x45, 0x4B, 0x31], // 3
+    [0x18, 0x14, 0x12, 0x7F, 0x10], // 4
+    [0x27, 0x45, 0x45, 0x45, 0x39], // 5
+    [0x3C, 0x4A, 0x49, 0x49, 0x30], // 6
+    [0x01, 0x71, 0x09, 0x05, 0x03], // 7
+    [0x36, 0x49, 0x49, 0x49, 0x36], // 8
+    [0x06, 0x49, 0x49, 0x29, 0x1E], // 9
+    [0x00, 0x36, 0x36, 0x00, 0x00], // :
+    [0x00, 0x56, 0x36, 0x00, 0x00], // ;
+    [0x08, 0x14, 0x22, 0x41, 0x00], // <
+    [0x14, 0x14, 0x14, 0x14, 0x14], // =
+    [0x00, 0x41, 0x22, 0x14, 0x08], // >
+    [0x02, 0x01, 0x51, 0x09, 0x06], // ?
+    [0x32, 0x49, 0x79, 0x41, 0x3E], // @
+    [0x7E, 0x11, 0x11, 0x11, 0x7E], // A
+    [0x7F, 0x49, 0x49, 0x49, 0x36], // B
+    [0x3E, 0x41, 0x41, 0x41, 0x22], // C
+    [0x7F, 0x41, 0x41, 0x22, 0x1C], // D
+    [0x7F, 0x49, 0x49, 0x49, 0x41], // E
+    [0x7F, 0x09, 0x09, 0x09, 0x01], // F
+    [0x3E, 0x41, 0x49, 0x49, 0x7A], // G
+    [0x7F, 0x08, 0x08, 0x08, 0x7F], // H
+    [0x00, 0x41, 0x7F, 0x41, 0x00], // I
+    [0x20, 0x40, 0x41, 0x3F, 0x01], // J
+    [0x7F, 0x08, 0x14, 0x22, 0x41], // K
+    [0x7F, 0x40, 0x40, 0x40, 0x40], // L
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F], // M
+    [0x7F, 0x04, 0x08, 0x10, 0x7F], // N
+    [0x3E, 0x41, 0x41, 0x41, 0x3E], // O
+    [0x7F, 0x09, 0x09, 0x09, 0x06], // P
+    [0x3E, 0x41, 0x51, 0x21, 0x5E], // Q
+    [0x7F, 0x09, 0x19, 0x29, 0x46], // R
+    [0x46, 0x49, 0x49, 0x49, 0x31], // S
+    [0x01, 0x01, 0x7F, 0x01, 0x01], // T
+    [0x3F, 0x40, 0x40, 0x40, 0x3F], // U
+    [0x1F, 0x20, 0x40, 0x20, 0x1F], // V
+    [0x3F, 0x40, 0x38, 0x40, 0x3F], // W
+    [0x63, 0x14, 0x08, 0x14, 0x63], // X
+    [0x07, 0x08, 0x70, 0x08, 0x07], // Y
+    [0x61, 0x51, 0x49, 0x45, 0x43], // Z
+    [0x00, 0x7F, 0x41, 0x41, 0x00], // [
+    [0x02, 0x04, 0x08, 0x10, 0x20], // \
+    [0x00, 0x41, 0x41, 0x7F, 0x00], // ]
+    [0x04, 0x02, 0x01, 0x02, 0x04], // ^
+    [0x40, 0x40, 0x40, 0x40, 0x40], // _
+    [0x00, 0x01, 0x02, 0x04, 0x00], // `
+    [0x20, 0x54, 0x54, 0x54, 0x78], // a
+    [0x7F, 0x48, 0x44, 0x44, 0x38], // b
+    [0x38, 0x44, 0x44, 0x44, 0x20], // c
+    [0x38, 0x44, 0x44, 0x48, 0x7F], // d
+    [0x38, 0x54, 0x54, 0x54, 0x18], // e
+    [0x08, 0x7E, 0x09, 0x01, 0x02], // f
+    [0x0C, 0x52, 0x52, 0x52, 0x3E], // g
+    [0x7F, 0x08, 0x04, 0x04, 0x78], // h
+    [0x00, 0x44, 0x7D, 0x40, 0x00], // i
+    [0x20, 0x40, 0x44, 0x3D, 0x00], // j
+    [0x7F, 0x10, 0x28, 0x44, 0x00], // k
+    [0x00, 0x41, 0x7F, 0x40, 0x00], // l
+    [0x7C, 0x04, 0x18, 0x04, 0x78], // m
+    [0x7C, 0x08, 0x04, 0x04, 0x78], // n
+    [0x38, 0x44, 0x44, 0x44, 0x38], // o
+    [0x7C, 0x14, 0x14, 0x14, 0x08], // p
+    [0x08, 0x14, 0x14, 0x18, 0x7C], // q
+    [0x7C, 0x08, 0x04, 0x04, 0x08], // r
+    [0x48, 0x54, 0x54, 0x54, 0x20], // s
+    [0x04, 0x3F, 0x44, 0x40, 0x20], // t
+    [0x3C, 0x40, 0x40, 0x20, 0x7C], // u
+    [0x1C, 0x20, 0x40, 0x20, 0x1C], // v
+    [0x3C, 0x40, 0x30, 0x40, 0x3C], // w
+    [0x44, 0x28, 0x10, 0x28, 0x44], // x
+    [0x0C, 0x50, 0x50, 0x50, 0x3C], // y
+    [0x44, 0x64, 0x54, 0x4C, 0x44], // z
+    [0x00, 0x08, 0x36, 0x41, 0x00], // {
+    [0x00, 0x00, 0x7F, 0x00, 0x00], // |
+    [0x00, 0x41, 0x36, 0x08, 0x00], // }
+    [0x08, 0x04, 0x08, 0x10, 0x08], // ~
+];
@@ -0,0 +1,89 @@
+//! The colors `bit_gfx` is drawn into `pix_gfx` with.
+
+use bitvec::prelude::*;
+use std::fmt;
+
+/// 0RGB colors indexed by a pixel's bit in each XO-CHIP plane: off, the
+/// first plane, the second plane, and both.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Palette(pub [u32; 4]);
+
+impl Palette {
+    pub fn background(&self) -> u32 {
+        self.0[0]
+    }
+
+    pub fn foreground(&self) -> u32 {
+        self.0[1]
+    }
+
+    /// Draws the planes, the first in the lowest bit of each color index.
+    pub fn render(&self, planes: &[&[u8]], pix_gfx: &mut [u32]) {
+        for (i, pixel) in pix_gfx.iter_mut().enumerate() {
+            let index = planes.iter().enumerate().fold(0, |index, (bit, plane)| {
+                let lit = plane.as_bitslice::<BigEndian>().get(i).unwrap_or(false);
+                index | (lit as usize) << bit
+            });
+
+            *pixel = self.0[index & 3];
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Theme::Classic.palette()
+    }
+}
+
+/// Built-in palettes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Theme {
+    /// White on black.
+    Classic,
+    /// A green phosphor monitor.
+    Phosphor,
+    /// A greenish reflective LCD.
+    Lcd,
+    /// Octo's yellow on brown.
+    Octo,
+}
+
+impl Theme {
+    pub fn palette(self) -> Palette {
+        Palette(match self {
+            Theme::Classic => [0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555],
+            Theme::Phosphor => [0x001400, 0x33FF66, 0x1A9933, 0x0D5C1F],
+            Theme::Lcd => [0x9BBC0F, 0x0F380F, 0x306230, 0x8BAC0F],
+            Theme::Octo => [0x996600, 0xFFCC00, 0xFF6600, 0x662200],
+        })
+    }
+}
+
+impl std::str::FromStr for Theme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "classic" => Ok(Theme::Classic),
+            "phosphor" => Ok(Theme::Phosphor),
+            "lcd" => Ok(Theme::Lcd),
+            "octo" => Ok(Theme::Octo),
+            _ => Err(format!(
+                "unknown theme {:?}: expected classic, phosphor, lcd or octo",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Theme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Theme::Classic => "classic",
+            Theme::Phosphor => "phosphor",
+            Theme::Lcd => "lcd",
+            Theme::Octo => "octo",
+        })
+    }
+}
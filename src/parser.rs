@@ -64,7 +64,7 @@ pub fn instr_bits(input: Bits) -> IResult<Bits, Instruction> {
         map(
             preceded(
                 bits::tag(0x5, 4usize),
-                tuple((register_bits, register_bits, bits::take(4usize))),
+                tuple((register_bits, register_bits, bits::tag(0x0, 4usize))),
             ),
             |(r1, r2, _): (_, _, u8)| SkipEqReg(r1, r2),
         ),
@@ -257,3 +257,18 @@ pub fn instr_bits(input: Bits) -> IResult<Bits, Instruction> {
 pub fn instr(input: &[u8]) -> IResult<&[u8], Instruction> {
     bits(instr_bits)(input)
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    UnknownOpcode(u16),
+    Incomplete,
+}
+
+pub fn decode(input: &[u8]) -> Result<(&[u8], Instruction), DecodeError> {
+    match input {
+        [hi, lo, ..] => {
+            instr(input).map_err(|_| DecodeError::UnknownOpcode(u16::from_be_bytes([*hi, *lo])))
+        }
+        _ => Err(DecodeError::Incomplete),
+    }
+}
@@ -0,0 +1,118 @@
+//! Display filters that hide the flicker of sprites being erased and drawn
+//! again, by remembering what earlier frames showed.
+//!
+//! They only change what's drawn into `pix_gfx`; `bit_gfx` is left alone.
+
+use crate::palette::Palette;
+use bitvec::prelude::*;
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Filter {
+    /// Shows each instant as it is.
+    None,
+    /// Lights pixels lit now or at the end of the last frame.
+    Persist,
+    /// Fades pixels out, keeping this much of their brightness each frame.
+    Decay(f32),
+}
+
+impl std::str::FromStr for Filter {
+    type Err = String;
+
+    /// Parses `none`, `persist`, `decay` or `decay:0.6`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+
+        match (parts.next(), parts.next()) {
+            (Some("none"), None) => Ok(Filter::None),
+            (Some("persist"), None) => Ok(Filter::Persist),
+            (Some("decay"), None) => Ok(Filter::Decay(0.6)),
+            (Some("decay"), Some(factor)) => match factor.parse() {
+                Ok(factor) if (0.0..1.0).contains(&factor) => Ok(Filter::Decay(factor)),
+                _ => Err(format!("invalid decay {:?}: expected 0 to 1", factor)),
+            },
+            _ => Err(format!(
+                "unknown filter {:?}: expected none, persist or decay[:FACTOR]",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Filter::None => write!(f, "none"),
+            Filter::Persist => write!(f, "persist"),
+            Filter::Decay(factor) => write!(f, "decay:{}", factor),
+        }
+    }
+}
+
+/// Mixes `to` into `from` by `amount`, from 0 to 1.
+fn blend(from: u32, to: u32, amount: f32) -> u32 {
+    (0..3).fold(0, |color, channel| {
+        let shift = channel * 8;
+        let from = (from >> shift & 0xFF) as f32;
+        let to = (to >> shift & 0xFF) as f32;
+        color | ((from + (to - from) * amount).round() as u32) << shift
+    })
+}
+
+/// What the display looked like in earlier frames.
+#[derive(Debug, Clone)]
+pub struct Phosphor {
+    pub filter: Filter,
+    last: [u8; 256],
+    /// How brightly each pixel still glows, from 0 to 1.
+    glow: Vec<f32>,
+}
+
+impl Phosphor {
+    pub fn new(filter: Filter) -> Self {
+        Phosphor {
+            filter,
+            last: [0; 256],
+            glow: vec![0.0; 2048],
+        }
+    }
+
+    /// Remembers the display as it is at the end of a 60 Hz frame.
+    pub fn end_frame(&mut self, bit_gfx: &[u8]) {
+        let bits = bit_gfx.as_bitslice::<BigEndian>();
+
+        if let Filter::Decay(factor) = self.filter {
+            for (glow, lit) in self.glow.iter_mut().zip(bits.iter()) {
+                *glow = if lit { 1.0 } else { *glow * factor };
+            }
+        }
+
+        self.last.copy_from_slice(&bit_gfx[..256]);
+    }
+
+    /// Draws `bit_gfx` with whatever earlier frames left behind.
+    pub fn render(&self, palette: &Palette, bit_gfx: &[u8], pix_gfx: &mut [u32]) {
+        match self.filter {
+            Filter::None => palette.render(&[bit_gfx], pix_gfx),
+            Filter::Persist => {
+                let mut lit = self.last;
+                for (lit, now) in lit.iter_mut().zip(bit_gfx) {
+                    *lit |= now;
+                }
+
+                palette.render(&[&lit], pix_gfx);
+            }
+            Filter::Decay(_) => {
+                palette.render(&[bit_gfx], pix_gfx);
+
+                let bits = bit_gfx.as_bitslice::<BigEndian>();
+                for ((pixel, lit), &glow) in pix_gfx.iter_mut().zip(bits.iter()).zip(&self.glow) {
+                    if !lit && glow > 0.0 {
+                        *pixel = blend(palette.background(), palette.foreground(), glow);
+                    }
+                }
+            }
+        }
+    }
+}
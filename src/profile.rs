@@ -0,0 +1,186 @@
+use crate::symbols::{Symbolic, Symbols};
+use crate::types::*;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct Subroutine {
+    pub calls: u64,
+    /// Instructions executed in the subroutine itself.
+    pub exclusive: u64,
+    /// Instructions executed in the subroutine or anything it called.
+    pub inclusive: u64,
+}
+
+/// Counts instructions per PC and per subroutine. Subroutines are identified
+/// by their `Call` target; code outside of any call is attributed to `None`.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    pub total: u64,
+    pub pcs: HashMap<Address, u64>,
+    pub subroutines: HashMap<Option<Address>, Subroutine>,
+    /// Each call stack seen, by `Call` target, and its index in `stack_counts`.
+    stack_ids: HashMap<Vec<Address>, usize>,
+    stack_counts: Vec<u64>,
+    /// The index of `targets` in `stack_counts`, or `None` if it changed.
+    stack: Option<usize>,
+    targets: Vec<Address>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Records `instr`, fetched from `pc`, once `state` has evaluated it.
+    pub fn record(&mut self, pc: Address, instr: &Instruction, state: &State) {
+        self.total += 1;
+        *self.pcs.entry(pc).or_insert(0) += 1;
+
+        let stack = match self.stack {
+            Some(stack) => stack,
+            None => self.stack_id(),
+        };
+        self.stack_counts[stack] += 1;
+
+        self.subroutines
+            .entry(self.targets.last().cloned())
+            .or_default()
+            .exclusive += 1;
+
+        let mut seen = Vec::with_capacity(self.targets.len() + 1);
+        for target in std::iter::once(None).chain(self.targets.iter().cloned().map(Some)) {
+            if !seen.contains(&target) {
+                seen.push(target);
+                self.subroutines.entry(target).or_default().inclusive += 1;
+            }
+        }
+
+        if let Instruction::Call(target) = instr {
+            self.targets.push(*target);
+            self.subroutines.entry(Some(*target)).or_default().calls += 1;
+            self.stack = None;
+        }
+
+        // Keep our view of the stack in step with the real one, whatever the
+        // program did to it.
+        if state.call_stack.len() < self.targets.len() {
+            self.targets.truncate(state.call_stack.len());
+            self.stack = None;
+        }
+    }
+
+    /// Looks up `targets` in `stack_ids`, only copying it the first time
+    /// it's seen.
+    fn stack_id(&mut self) -> usize {
+        let stack = match self.stack_ids.get(&self.targets) {
+            Some(&stack) => stack,
+            None => {
+                let stack = self.stack_counts.len();
+                self.stack_ids.insert(self.targets.clone(), stack);
+                self.stack_counts.push(0);
+                stack
+            }
+        };
+
+        self.stack = Some(stack);
+        stack
+    }
+
+    /// Instructions executed per call stack, outermost call first.
+    pub fn stacks(&self) -> impl Iterator<Item = (&[Address], u64)> {
+        self.stack_ids
+            .iter()
+            .map(move |(stack, &id)| (&stack[..], self.stack_counts[id]))
+    }
+
+    pub fn write_report<W: Write>(
+        &self,
+        state: &State,
+        symbols: &Symbols,
+        out: &mut W,
+    ) -> io::Result<()> {
+        let percent = |n: u64| 100.0 * n as f64 / self.total.max(1) as f64;
+
+        let mut pcs: Vec<_> = self.pcs.iter().collect();
+        pcs.sort_by_key(|(pc, count)| (Reverse(**count), **pc));
+
+        writeln!(out, "{} instructions executed", self.total)?;
+        writeln!(out)?;
+        writeln!(out, "Hot spots:")?;
+        writeln!(
+            out,
+            "    {:<20} {:>12} {:>7}  instruction",
+            "pc", "count", "%"
+        )?;
+        for (pc, count) in pcs.into_iter().take(20) {
+            let bytes = [
+                state.memory[pc.index()],
+                state.memory[pc.wrapping_add(1).index()],
+            ];
+            let instr = match crate::parser::decode(&bytes) {
+                Ok((_, instr)) => Symbolic(&instr, symbols).to_string(),
+                Err(_) => "???".to_string(),
+            };
+
+            writeln!(
+                out,
+                "    {:<20} {:>12} {:>6.2}%  {}",
+                Symbolic(pc, symbols).to_string(),
+                count,
+                percent(*count),
+                instr
+            )?;
+        }
+
+        let mut subroutines: Vec<_> = self.subroutines.iter().collect();
+        subroutines.sort_by_key(|(target, sub)| (Reverse(sub.inclusive), **target));
+
+        writeln!(out)?;
+        writeln!(out, "Subroutines:")?;
+        writeln!(
+            out,
+            "    {:<20} {:>8} {:>12} {:>7} {:>12} {:>7}",
+            "entry", "calls", "self", "%", "total", "%"
+        )?;
+        for (target, sub) in subroutines {
+            let name = match target {
+                Some(target) => Symbolic(target, symbols).to_string(),
+                None => "main".to_string(),
+            };
+
+            writeln!(
+                out,
+                "    {:<20} {:>8} {:>12} {:>6.2}% {:>12} {:>6.2}%",
+                name,
+                sub.calls,
+                sub.exclusive,
+                percent(sub.exclusive),
+                sub.inclusive,
+                percent(sub.inclusive)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes stacks in the folded format read by `flamegraph.pl` and inferno.
+    pub fn write_folded<W: Write>(&self, symbols: &Symbols, out: &mut W) -> io::Result<()> {
+        let mut stacks: Vec<_> = self.stacks().collect();
+        stacks.sort();
+
+        for (stack, count) in stacks {
+            write!(out, "main")?;
+            for target in stack {
+                match symbols.locate(*target) {
+                    Some((name, 0)) => write!(out, ";{}", name)?,
+                    _ => write!(out, ";sub_{:03X}", target.0)?,
+                }
+            }
+            writeln!(out, " {}", count)?;
+        }
+
+        Ok(())
+    }
+}
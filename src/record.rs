@@ -0,0 +1,226 @@
+//! Recordings of the display, one frame per 60 Hz tick, as animated GIFs or
+//! raw Y4M video.
+
+use crate::palette::Palette;
+use crate::screenshot::{self, HEIGHT, MAX_SCALE, WIDTH};
+use gif::SetParameter;
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::mem;
+use std::path::Path;
+use std::rc::Rc;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    /// Palette-indexed, holding frames while the display doesn't change.
+    Gif,
+    /// Uncompressed 4:4:4 video at 60 frames per second.
+    Y4m,
+}
+
+impl Format {
+    /// Picks the format by `path`'s extension.
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|ext| ext.to_str());
+
+        match extension.map(str::to_ascii_lowercase).as_deref() {
+            Some("gif") => Ok(Format::Gif),
+            Some("y4m") => Ok(Format::Y4m),
+            _ => Err(format!(
+                "can't record to {}: expected a .gif or .y4m file",
+                path.display()
+            )),
+        }
+    }
+}
+
+/// GIF delays are in hundredths of a second, so a frame lasts 1 or 2 and
+/// the display's time is kept by counting ticks.
+fn centiseconds(ticks: u64) -> u64 {
+    (ticks * 100 + 30) / 60
+}
+
+/// Collects what the GIF encoder writes so it can be passed on to the real
+/// writer, since the encoder writes its trailer on drop and drops any error.
+#[derive(Clone, Default)]
+struct Pipe(Rc<RefCell<Vec<u8>>>);
+
+impl Pipe {
+    fn drain_into(&self, w: &mut impl Write) -> io::Result<()> {
+        let bytes = mem::take(&mut *self.0.borrow_mut());
+        w.write_all(&bytes)
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+enum Encoder<W: Write> {
+    Gif {
+        w: W,
+        encoder: gif::Encoder<Pipe>,
+        pipe: Pipe,
+        /// The frame being held, and the tick it was first shown at.
+        pending: Option<(Vec<u8>, u64)>,
+    },
+    Y4m {
+        w: W,
+        /// Each palette color as Y, Cb and Cr.
+        colors: [[u8; 3]; 4],
+    },
+}
+
+pub struct Recorder<W: Write> {
+    encoder: Encoder<W>,
+    scale: usize,
+    ticks: u64,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Starts a recording `scale` times the display's size.
+    pub fn new(mut w: W, format: Format, palette: &Palette, scale: u32) -> Result<Self, String> {
+        if !(1..=MAX_SCALE).contains(&scale) {
+            return Err(format!("scale must be from 1 to {}", MAX_SCALE));
+        }
+
+        let (width, height) = (WIDTH * scale, HEIGHT * scale);
+
+        let encoder = match format {
+            Format::Gif => {
+                let colors: Vec<u8> = palette
+                    .0
+                    .iter()
+                    .flat_map(|color| color.to_be_bytes()[1..].to_vec())
+                    .collect();
+                let pipe = Pipe::default();
+                let mut encoder =
+                    gif::Encoder::new(pipe.clone(), width as u16, height as u16, &colors)
+                        .map_err(|err| err.to_string())?;
+                encoder
+                    .set(gif::Repeat::Infinite)
+                    .map_err(|err| err.to_string())?;
+                pipe.drain_into(&mut w).map_err(|err| err.to_string())?;
+
+                Encoder::Gif {
+                    w,
+                    encoder,
+                    pipe,
+                    pending: None,
+                }
+            }
+            Format::Y4m => {
+                writeln!(w, "YUV4MPEG2 W{} H{} F60:1 Ip A1:1 C444", width, height)
+                    .map_err(|err| err.to_string())?;
+
+                let mut colors = [[0; 3]; 4];
+                for (yuv, &color) in colors.iter_mut().zip(&palette.0) {
+                    let [_, r, g, b] = color.to_be_bytes();
+                    let (r, g, b) = (i32::from(r), i32::from(g), i32::from(b));
+
+                    // BT.601 in studio range.
+                    *yuv = [
+                        (((66 * r + 129 * g + 25 * b + 128) >> 8) + 16) as u8,
+                        (((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128) as u8,
+                        (((112 * r - 94 * g - 18 * b + 128) >> 8) + 128) as u8,
+                    ];
+                }
+
+                Encoder::Y4m { w, colors }
+            }
+        };
+
+        Ok(Recorder {
+            encoder,
+            scale: scale as usize,
+            ticks: 0,
+        })
+    }
+
+    /// Records the display at the end of a tick.
+    pub fn frame(&mut self, planes: &[&[u8]]) -> Result<(), String> {
+        let indices = screenshot::indices(planes, self.scale);
+        let tick = self.ticks;
+        self.ticks += 1;
+
+        match &mut self.encoder {
+            Encoder::Gif { pending, .. } => {
+                if matches!(pending, Some((held, _)) if *held == indices) {
+                    return Ok(());
+                }
+
+                let last = pending.replace((indices, tick));
+                self.write_gif_frame(last, tick)
+            }
+            Encoder::Y4m { w, colors } => {
+                let mut frame = Vec::with_capacity(indices.len() * 3 + 6);
+                frame.extend_from_slice(b"FRAME\n");
+                let pixels: Vec<[u8; 3]> = indices
+                    .iter()
+                    .map(|&index| colors[usize::from(index)])
+                    .collect();
+                for plane in 0..3 {
+                    frame.extend(pixels.iter().map(|yuv| yuv[plane]));
+                }
+
+                w.write_all(&frame).map_err(|err| err.to_string())
+            }
+        }
+    }
+
+    /// Writes a held GIF frame, which lasted until `until`.
+    fn write_gif_frame(&mut self, frame: Option<(Vec<u8>, u64)>, until: u64) -> Result<(), String> {
+        let (w, encoder, pipe, (pixels, since)) = match (&mut self.encoder, frame) {
+            (
+                Encoder::Gif {
+                    w, encoder, pipe, ..
+                },
+                Some(frame),
+            ) => (w, encoder, pipe, frame),
+            _ => return Ok(()),
+        };
+
+        let width = (WIDTH as usize * self.scale) as u16;
+        let height = (HEIGHT as usize * self.scale) as u16;
+        let mut frame = gif::Frame::from_indexed_pixels(width, height, &pixels, None);
+
+        let delay = centiseconds(until) - centiseconds(since);
+        frame.delay = delay.min(u64::from(u16::MAX)) as u16;
+
+        encoder
+            .write_frame(&frame)
+            .and_then(|_| pipe.drain_into(w))
+            .map_err(|err| err.to_string())
+    }
+
+    /// Writes whatever's held, ends the recording and flushes the writer.
+    pub fn finish(mut self) -> Result<(), String> {
+        if let Encoder::Gif { pending, .. } = &mut self.encoder {
+            let last = pending.take();
+            self.write_gif_frame(last, self.ticks)?;
+        }
+
+        match self.encoder {
+            Encoder::Gif {
+                mut w,
+                encoder,
+                pipe,
+                ..
+            } => {
+                // Dropping the encoder writes the trailer.
+                drop(encoder);
+                pipe.drain_into(&mut w).and_then(|_| w.flush())
+            }
+            Encoder::Y4m { mut w, .. } => w.flush(),
+        }
+        .map_err(|err| err.to_string())
+    }
+}
@@ -0,0 +1,174 @@
+//! Settings for known ROMs, looked up by the SHA-1 of their contents.
+//!
+//! The database is a JSON object from lowercase hex hashes to entries like
+//!
+//! ```json
+//! {
+//!   "title": "Pong",
+//!   "platform": "chip8",
+//!   "quirks": { "shift": true },
+//!   "tickrate": 15,
+//!   "keys": { "W": 1, "S": 4 },
+//!   "foreground": "#FFCC00",
+//!   "background": "#996600"
+//! }
+//! ```
+//!
+//! where every field is optional, `quirks` adjusts the platform's defaults
+//! and `keys` binds host keys on top of the usual layout.
+
+use crate::cartridge::{parse_color, parse_tickrate};
+use crate::config;
+use crate::keymap::parse_key;
+use crate::types::{Button, Platform, Quirks};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+const BUNDLED: &str = include_str!("../data/roms.json");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub title: Option<String>,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    /// Instructions per 60 Hz frame.
+    pub tickrate: Option<u32>,
+    pub keys: Vec<(minifb::Key, Button)>,
+    pub foreground: Option<u32>,
+    pub background: Option<u32>,
+}
+
+fn color(json: &Map<String, Value>, name: &str) -> Result<Option<u32>, String> {
+    json.get(name)
+        .map(|value| {
+            value
+                .as_str()
+                .and_then(parse_color)
+                .ok_or_else(|| format!("invalid {} {}", name, value))
+        })
+        .transpose()
+}
+
+impl Entry {
+    fn from_json(json: &Map<String, Value>) -> Result<Self, String> {
+        let title = match json.get("title") {
+            Some(Value::String(title)) => Some(title.clone()),
+            Some(title) => return Err(format!("invalid title {}", title)),
+            None => None,
+        };
+
+        let platform = match json.get("platform") {
+            Some(Value::String(platform)) => platform.parse()?,
+            Some(platform) => return Err(format!("invalid platform {}", platform)),
+            None => Platform::Chip8,
+        };
+
+        let mut quirks = platform.quirks();
+        match json.get("quirks") {
+            Some(Value::Object(flags)) => {
+                for (name, value) in flags {
+                    let mut table = quirks.flags_mut();
+                    let (_, quirk) = table
+                        .iter_mut()
+                        .find(|(quirk, _)| quirk == name)
+                        .ok_or_else(|| format!("unknown quirk {:?}", name))?;
+                    **quirk = value
+                        .as_bool()
+                        .ok_or_else(|| format!("invalid {} {}", name, value))?;
+                }
+            }
+            Some(flags) => return Err(format!("invalid quirks {}", flags)),
+            None => {}
+        }
+
+        let tickrate = json.get("tickrate").map(parse_tickrate).transpose()?;
+
+        let mut keys = Vec::new();
+        match json.get("keys") {
+            Some(Value::Object(bindings)) => {
+                for (name, button) in bindings {
+                    let key = parse_key(name).ok_or_else(|| format!("unknown key {:?}", name))?;
+                    let button = button
+                        .as_u64()
+                        .filter(|&n| n < 16)
+                        .and_then(|n| Button::n(n as u8))
+                        .ok_or_else(|| format!("invalid button {}", button))?;
+                    keys.push((key, button));
+                }
+            }
+            Some(bindings) => return Err(format!("invalid keys {}", bindings)),
+            None => {}
+        }
+
+        Ok(Entry {
+            title,
+            platform,
+            quirks,
+            tickrate,
+            keys,
+            foreground: color(json, "foreground")?,
+            background: color(json, "background")?,
+        })
+    }
+}
+
+pub fn hash(rom: &[u8]) -> String {
+    sha1::Sha1::from(rom).digest().to_string()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RomDb {
+    entries: HashMap<String, Map<String, Value>>,
+}
+
+impl RomDb {
+    /// The database that ships with the emulator.
+    pub fn bundled() -> Self {
+        BUNDLED.parse().expect("bundled ROM database is invalid")
+    }
+
+    /// Where users can add ROMs or override the bundled entries:
+    /// `$XDG_CONFIG_HOME/chip8-rs/roms.json`.
+    pub fn user_path() -> Option<PathBuf> {
+        Some(config::dir()?.join("roms.json"))
+    }
+
+    /// Adds `other`'s entries, with its fields taking precedence.
+    pub fn merge(&mut self, other: RomDb) {
+        for (hash, fields) in other.entries {
+            self.entries.entry(hash).or_default().extend(fields);
+        }
+    }
+
+    pub fn get(&self, rom: &[u8]) -> Option<Entry> {
+        self.entries
+            .get(&hash(rom))
+            .map(|fields| Entry::from_json(fields).expect("entries are checked when parsed"))
+    }
+}
+
+impl std::str::FromStr for RomDb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let json: Value = serde_json::from_str(s).map_err(|err| err.to_string())?;
+        let json = match json {
+            Value::Object(json) => json,
+            _ => return Err("ROM database isn't an object".into()),
+        };
+
+        let mut entries = HashMap::new();
+        for (hash, fields) in json {
+            let fields = match fields {
+                Value::Object(fields) => fields,
+                _ => return Err(format!("{}: entry isn't an object", hash)),
+            };
+
+            Entry::from_json(&fields).map_err(|err| format!("{}: {}", hash, err))?;
+            entries.insert(hash.to_ascii_lowercase(), fields);
+        }
+
+        Ok(RomDb { entries })
+    }
+}
@@ -0,0 +1,62 @@
+//! PNG screenshots of the display.
+
+use crate::palette::Palette;
+use std::io::Write;
+
+pub const WIDTH: u32 = 64;
+pub const HEIGHT: u32 = 32;
+
+/// The largest scale, a 4096 by 2048 image.
+pub const MAX_SCALE: u32 = 64;
+
+/// Each pixel's palette index, with each pixel a `scale` by `scale` square.
+pub(crate) fn indices(planes: &[&[u8]], scale: usize) -> Vec<u8> {
+    // Rendering through the palette of indices gives each pixel's index.
+    let mut indices = [0; (WIDTH * HEIGHT) as usize];
+    Palette([0, 1, 2, 3]).render(planes, &mut indices);
+
+    let mut scaled = Vec::with_capacity(indices.len() * scale * scale);
+    for row in indices.chunks(WIDTH as usize) {
+        let row: Vec<u8> = row
+            .iter()
+            .flat_map(|&index| vec![index as u8; scale])
+            .collect();
+
+        for _ in 0..scale {
+            scaled.extend_from_slice(&row);
+        }
+    }
+
+    scaled
+}
+
+/// Writes the planes as an indexed PNG in `palette`'s colors, with each
+/// pixel a `scale` by `scale` square.
+pub fn write_png<W: Write>(
+    w: W,
+    palette: &Palette,
+    planes: &[&[u8]],
+    scale: u32,
+) -> Result<(), String> {
+    if !(1..=MAX_SCALE).contains(&scale) {
+        return Err(format!("scale must be from 1 to {}", MAX_SCALE));
+    }
+
+    let data = indices(planes, scale as usize);
+
+    let mut encoder = png::Encoder::new(w, WIDTH * scale, HEIGHT * scale);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(
+        palette
+            .0
+            .iter()
+            .flat_map(|color| color.to_be_bytes()[1..].to_vec())
+            .collect(),
+    );
+
+    let mut writer = encoder.write_header().map_err(|err| err.to_string())?;
+    writer
+        .write_image_data(&data)
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,209 @@
+//! Names for addresses, loaded from `name = 0x2A4` lines or a JSON object
+//! like the one the assembler writes.
+
+use crate::types::*;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Symbols {
+    by_name: HashMap<String, Address>,
+    by_addr: BTreeMap<Address, String>,
+}
+
+/// Pairs a value with the symbols to display it with, so addresses print as
+/// `draw_paddle+0x4`.
+pub struct Symbolic<'a, T>(pub &'a T, pub &'a Symbols);
+
+fn parse_number(s: &str) -> Option<u16> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+impl Symbols {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_name.is_empty()
+    }
+
+    /// Adds a symbol. When several share an address, the first one added is
+    /// the one addresses are displayed with.
+    pub fn insert(&mut self, name: impl Into<String>, addr: Address) {
+        let name = name.into();
+
+        self.by_addr.entry(addr).or_insert_with(|| name.clone());
+        self.by_name.insert(name, addr);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Address> {
+        self.by_name.get(name).cloned()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Address)> {
+        self.by_addr
+            .iter()
+            .map(|(addr, name)| (name.as_str(), *addr))
+    }
+
+    /// The closest symbol at or before `addr`, and how far past it `addr` is.
+    pub fn locate(&self, addr: Address) -> Option<(&str, u16)> {
+        self.by_addr
+            .range(..=addr)
+            .next_back()
+            .map(|(start, name)| (name.as_str(), addr.0 - start.0))
+    }
+
+    /// Parses an address typed by the user: a symbol, a hex number with or
+    /// without `0x`, or either of those plus a hex offset, e.g. `draw+4`.
+    /// It must fall within memory.
+    pub fn parse_address(&self, s: &str) -> Result<Address, String> {
+        let s = s.trim();
+
+        let (base, offset) = match s.find('+') {
+            Some(split) => (s[..split].trim(), Some(s[split + 1..].trim())),
+            None => (s, None),
+        };
+
+        let hex = |s: &str| u16::from_str_radix(s.trim_start_matches("0x"), 16).ok();
+
+        let base = self
+            .get(base)
+            .or_else(|| hex(base).map(Address))
+            .ok_or_else(|| format!("unknown address {:?}", base))?;
+
+        let offset = match offset {
+            Some(offset) => hex(offset).ok_or_else(|| format!("invalid offset {:?}", offset))?,
+            None => 0,
+        };
+
+        match base.0.checked_add(offset) {
+            Some(addr) if addr <= 0xFFF => Ok(Address(addr)),
+            _ => Err(format!("{:?} is past the end of memory", s)),
+        }
+    }
+
+    /// Parses `ADDR` or `ADDR-ADDR`, with each end as `parse_address` takes
+    /// it. Names containing `-` are tried whole before being split.
+    pub fn parse_range(&self, s: &str) -> Result<RangeInclusive<Address>, String> {
+        let whole = match self.parse_address(s) {
+            Ok(addr) => return Ok(addr..=addr),
+            Err(err) => err,
+        };
+
+        let mut error = None;
+        for (split, _) in s.match_indices('-') {
+            let start = self.parse_address(&s[..split]);
+            match start.and_then(|start| Ok(start..=self.parse_address(&s[split + 1..])?)) {
+                Ok(range) => return Ok(range),
+                Err(err) => {
+                    error.get_or_insert(err);
+                }
+            }
+        }
+
+        Err(error.unwrap_or(whole))
+    }
+
+    /// A JSON object of names to addresses, as `from_str` reads back.
+    pub fn to_json(&self) -> String {
+        let object = self
+            .by_name
+            .iter()
+            .map(|(name, addr)| (name.clone(), Value::String(format!("0x{:03X}", addr.0))))
+            .collect();
+
+        serde_json::to_string_pretty(&Value::Object(object)).unwrap()
+    }
+
+    fn from_json(s: &str) -> Result<Self, String> {
+        let json: Value = serde_json::from_str(s).map_err(|err| err.to_string())?;
+        let object = json
+            .as_object()
+            .ok_or_else(|| "symbol file should be a JSON object".to_string())?;
+
+        let mut symbols = Symbols::new();
+
+        for (name, value) in object {
+            let addr = match value {
+                Value::Number(n) => n.as_u64().filter(|n| *n <= 0xFFFF).map(|n| n as u16),
+                Value::String(s) => parse_number(s),
+                _ => None,
+            };
+
+            let addr = addr.ok_or_else(|| format!("invalid address for {:?}", name))?;
+            symbols.insert(name.as_str(), Address(addr));
+        }
+
+        Ok(symbols)
+    }
+}
+
+impl FromStr for Symbols {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim_start().starts_with('{') {
+            return Symbols::from_json(s);
+        }
+
+        let mut symbols = Symbols::new();
+
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || format!("invalid symbol on line {}: {:?}", n + 1, line);
+
+            let split = line.find('=').ok_or_else(invalid)?;
+            let name = line[..split].trim();
+            let addr = parse_number(line[split + 1..].trim()).ok_or_else(invalid)?;
+
+            if name.is_empty() {
+                return Err(invalid());
+            }
+
+            symbols.insert(name, Address(addr));
+        }
+
+        Ok(symbols)
+    }
+}
+
+impl fmt::Display for Symbolic<'_, Address> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Symbolic(addr, symbols) = *self;
+
+        match symbols.locate(*addr) {
+            Some((name, 0)) => write!(f, "{}", name),
+            Some((name, offset)) => write!(f, "{}+{:#X}", name, offset),
+            None => write!(f, "{}", addr),
+        }
+    }
+}
+
+impl fmt::Display for Symbolic<'_, Instruction> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+
+        let Symbolic(instr, symbols) = *self;
+
+        match instr {
+            RcaCall(addr) => write!(f, "SYS {}", Symbolic(addr, symbols)),
+            Goto(addr) => write!(f, "JP {}", Symbolic(addr, symbols)),
+            Call(addr) => write!(f, "CALL {}", Symbolic(addr, symbols)),
+            SetAddr(addr) => write!(f, "LD I, {}", Symbolic(addr, symbols)),
+            IndexedJump(addr) => write!(f, "JP V0, {}", Symbolic(addr, symbols)),
+            _ => write!(f, "{}", instr),
+        }
+    }
+}
@@ -0,0 +1,383 @@
+use crate::symbols::{Symbolic, Symbols};
+use crate::types::*;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::ops::RangeInclusive;
+
+pub const MAGIC: &[u8; 4] = b"C8TR";
+pub const VERSION: u8 = 2;
+
+/// FNV-1a hash of the display, so traces can tell when it changed.
+pub fn gfx_hash(bit_gfx: &[u8]) -> u32 {
+    bit_gfx.iter().fold(0x811C_9DC5, |hash, byte| {
+        (hash ^ u32::from(*byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Change {
+    Register(Register, u8, u8),
+    I(Address, Address),
+    Memory(Address, u8, u8),
+    Display(u32, u32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub frame: u64,
+    pub pc: Address,
+    pub opcode: u16,
+    pub changes: Vec<Change>,
+}
+
+impl Entry {
+    /// Describes the instruction that took `before` to `after`.
+    pub fn new(before: &State, after: &State) -> Self {
+        let opcode = u16::from_be_bytes([
+            before.memory[before.pc.index()],
+            before.memory[before.pc.wrapping_add(1).index()],
+        ]);
+
+        let mut changes = Vec::new();
+
+        for (reg, old) in &before.registers {
+            let new = after.registers[reg];
+            if *old != new {
+                changes.push(Change::Register(reg, *old, new));
+            }
+        }
+
+        if before.i_reg != after.i_reg {
+            changes.push(Change::I(before.i_reg, after.i_reg));
+        }
+
+        if before.memory[..] != after.memory[..] {
+            for (addr, (old, new)) in before.memory.iter().zip(after.memory.iter()).enumerate() {
+                if old != new {
+                    changes.push(Change::Memory(Address(addr as u16), *old, *new));
+                }
+            }
+        }
+
+        if before.bit_gfx[..] != after.bit_gfx[..] {
+            changes.push(Change::Display(
+                gfx_hash(&before.bit_gfx),
+                gfx_hash(&after.bit_gfx),
+            ));
+        }
+
+        Entry {
+            frame: before.frame,
+            pc: before.pc,
+            opcode,
+            changes,
+        }
+    }
+
+    pub fn write_text<W: Write>(&self, symbols: &Symbols, out: &mut W) -> io::Result<()> {
+        writeln!(out, "{}", Symbolic(self, symbols))
+    }
+
+    pub fn write_binary<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(&self.frame.to_le_bytes())?;
+        out.write_all(&self.pc.0.to_le_bytes())?;
+        out.write_all(&self.opcode.to_le_bytes())?;
+        out.write_all(&[self.changes.len().min(255) as u8])?;
+
+        for change in self.changes.iter().take(255) {
+            match change {
+                Change::Register(reg, old, new) => out.write_all(&[*reg as u8, *old, *new])?,
+                Change::I(old, new) => {
+                    out.write_all(&[0x10])?;
+                    out.write_all(&old.0.to_le_bytes())?;
+                    out.write_all(&new.0.to_le_bytes())?;
+                }
+                Change::Memory(addr, old, new) => {
+                    out.write_all(&[0x11])?;
+                    out.write_all(&addr.0.to_le_bytes())?;
+                    out.write_all(&[*old, *new])?;
+                }
+                Change::Display(old, new) => {
+                    out.write_all(&[0x12])?;
+                    out.write_all(&old.to_le_bytes())?;
+                    out.write_all(&new.to_le_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Symbolic<'_, Entry> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let Symbolic(entry, symbols) = *self;
+
+        let mnemonic = match crate::parser::decode(&entry.opcode.to_be_bytes()) {
+            Ok((_, instr)) => Symbolic(&instr, symbols).to_string(),
+            Err(_) => "???".to_string(),
+        };
+
+        let mut line = format!(
+            "{:>8} {:03X} {:04X} {:<18}",
+            entry.frame, entry.pc.0, entry.opcode, mnemonic
+        );
+
+        for change in &entry.changes {
+            line += &match change {
+                Change::Register(reg, old, new) => format!(" {}:{:02X}->{:02X}", reg, old, new),
+                Change::I(old, new) => format!(" I:{:03X}->{:03X}", old.0, new.0),
+                Change::Memory(addr, old, new) => {
+                    format!(" [{:03X}]:{:02X}->{:02X}", addr.0, old, new)
+                }
+                Change::Display(old, new) => format!(" gfx:{:08X}->{:08X}", old, new),
+            };
+        }
+
+        write!(f, "{}", line.trim_end())
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Symbolic(self, &Symbols::new()).fmt(f)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Binary,
+}
+
+/// Restricts which instructions get logged. `None` matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub pc: Option<RangeInclusive<u16>>,
+    /// High nibbles of the opcodes to keep, e.g. `0xD` for draws.
+    pub classes: Option<Vec<u8>>,
+    pub frames: Option<RangeInclusive<u64>>,
+}
+
+impl Filter {
+    pub fn matches(&self, entry: &Entry) -> bool {
+        if let Some(pc) = &self.pc {
+            if !pc.contains(&entry.pc.0) {
+                return false;
+            }
+        }
+
+        if let Some(classes) = &self.classes {
+            if !classes.contains(&((entry.opcode >> 12) as u8)) {
+                return false;
+            }
+        }
+
+        if let Some(frames) = &self.frames {
+            if !frames.contains(&entry.frame) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+pub struct Tracer<W: Write> {
+    out: W,
+    format: Format,
+    pub filter: Filter,
+    pub symbols: Symbols,
+}
+
+impl<W: Write> Tracer<W> {
+    pub fn new(mut out: W, format: Format) -> io::Result<Self> {
+        if format == Format::Binary {
+            out.write_all(MAGIC)?;
+            out.write_all(&[VERSION])?;
+        }
+
+        Ok(Tracer {
+            out,
+            format,
+            filter: Default::default(),
+            symbols: Symbols::new(),
+        })
+    }
+
+    pub fn record(&mut self, before: &State, after: &State) -> io::Result<()> {
+        let entry = Entry::new(before, after);
+
+        if !self.filter.matches(&entry) {
+            return Ok(());
+        }
+
+        match self.format {
+            Format::Text => entry.write_text(&self.symbols, &mut self.out),
+            Format::Binary => entry.write_binary(&mut self.out),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn parse_hex(s: &str) -> io::Result<u32> {
+    u32::from_str_radix(s, 16).map_err(|_| invalid("invalid hex number in trace"))
+}
+
+fn parse_change(field: &str) -> io::Result<Change> {
+    let colon = field.rfind(':').ok_or_else(|| invalid("invalid change"))?;
+    let (name, values) = (&field[..colon], &field[colon + 1..]);
+    let arrow = values.find("->").ok_or_else(|| invalid("invalid change"))?;
+    let old = parse_hex(&values[..arrow])?;
+    let new = parse_hex(&values[arrow + 2..])?;
+
+    if name == "I" {
+        Ok(Change::I(Address(old as u16), Address(new as u16)))
+    } else if name == "gfx" {
+        Ok(Change::Display(old, new))
+    } else if let Some(addr) = name.strip_prefix('[').and_then(|n| n.strip_suffix(']')) {
+        let addr = parse_hex(addr)?;
+        Ok(Change::Memory(Address(addr as u16), old as u8, new as u8))
+    } else if let Some(reg) = name.strip_prefix('V') {
+        let reg = Register::n(parse_hex(reg)? as u8).ok_or_else(|| invalid("invalid register"))?;
+        Ok(Change::Register(reg, old as u8, new as u8))
+    } else {
+        Err(invalid("invalid change"))
+    }
+}
+
+fn parse_text(line: &str) -> io::Result<Entry> {
+    let mut fields = line.split_whitespace();
+    let mut next = || fields.next().ok_or_else(|| invalid("truncated trace line"));
+
+    let frame = next()?
+        .parse()
+        .map_err(|_| invalid("invalid frame number"))?;
+    let pc = Address(parse_hex(next()?)? as u16);
+    let opcode = parse_hex(next()?)? as u16;
+    let changes = fields
+        .filter(|field| field.contains("->"))
+        .map(parse_change)
+        .collect::<io::Result<_>>()?;
+
+    Ok(Entry {
+        frame,
+        pc,
+        opcode,
+        changes,
+    })
+}
+
+/// Reads back traces in either format, telling them apart by `MAGIC`.
+pub struct Reader<R: BufRead> {
+    input: R,
+    format: Format,
+    line: String,
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let format = if input.fill_buf()?.starts_with(MAGIC) {
+            let mut header = [0u8; 5];
+            input.read_exact(&mut header)?;
+
+            if header[4] != VERSION {
+                return Err(invalid("unsupported trace version"));
+            }
+
+            Format::Binary
+        } else {
+            Format::Text
+        };
+
+        Ok(Reader {
+            input,
+            format,
+            line: String::new(),
+        })
+    }
+
+    fn read_exact<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.input.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn read_binary(&mut self) -> io::Result<Option<Entry>> {
+        if self.input.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+
+        let frame = u64::from_le_bytes(self.read_exact()?);
+        let pc = u16::from_le_bytes(self.read_exact()?);
+        let opcode = u16::from_le_bytes(self.read_exact()?);
+        let [count] = self.read_exact()?;
+
+        let mut changes = Vec::with_capacity(count.into());
+        for _ in 0..count {
+            let [tag] = self.read_exact()?;
+
+            changes.push(match tag {
+                0x00..=0x0F => {
+                    let [old, new] = self.read_exact()?;
+                    Change::Register(Register::n(tag).unwrap(), old, new)
+                }
+                0x10 => Change::I(
+                    Address(u16::from_le_bytes(self.read_exact()?)),
+                    Address(u16::from_le_bytes(self.read_exact()?)),
+                ),
+                0x11 => {
+                    let addr = Address(u16::from_le_bytes(self.read_exact()?));
+                    let [old, new] = self.read_exact()?;
+                    Change::Memory(addr, old, new)
+                }
+                0x12 => Change::Display(
+                    u32::from_le_bytes(self.read_exact()?),
+                    u32::from_le_bytes(self.read_exact()?),
+                ),
+                _ => return Err(invalid("invalid change tag")),
+            });
+        }
+
+        Ok(Some(Entry {
+            frame,
+            pc: Address(pc),
+            opcode,
+            changes,
+        }))
+    }
+
+    fn read_text(&mut self) -> io::Result<Option<Entry>> {
+        loop {
+            self.line.clear();
+
+            if self.input.read_line(&mut self.line)? == 0 {
+                return Ok(None);
+            }
+
+            if !self.line.trim().is_empty() {
+                return parse_text(&self.line).map(Some);
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for Reader<R> {
+    type Item = io::Result<Entry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = match self.format {
+            Format::Text => self.read_text(),
+            Format::Binary => self.read_binary(),
+        };
+
+        entry.transpose()
+    }
+}
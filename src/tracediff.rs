@@ -0,0 +1,172 @@
+use crate::trace::{gfx_hash, Change, Entry};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io;
+
+/// Machine state rebuilt by replaying a trace's changes from power-on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Replay {
+    registers: [u8; 16],
+    i: u16,
+    gfx: u32,
+}
+
+impl Default for Replay {
+    fn default() -> Self {
+        Replay {
+            registers: [0; 16],
+            i: 0,
+            gfx: gfx_hash(&[0; 256]),
+        }
+    }
+}
+
+impl Replay {
+    fn apply(&mut self, entry: &Entry) {
+        for change in &entry.changes {
+            match *change {
+                Change::Register(reg, _, new) => self.registers[reg as usize] = new,
+                Change::I(_, new) => self.i = new.0,
+                Change::Display(_, new) => self.gfx = new,
+                Change::Memory(..) => {}
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    /// Number of instructions both traces agree on.
+    pub step: u64,
+    pub differences: Vec<String>,
+    pub before: Vec<Entry>,
+    /// The diverging entries; `None` if that trace ended first.
+    pub a: Option<Entry>,
+    pub b: Option<Entry>,
+    pub after_a: Vec<Entry>,
+    pub after_b: Vec<Entry>,
+}
+
+fn take<I>(trace: &mut I, n: usize) -> io::Result<Vec<Entry>>
+where
+    I: Iterator<Item = io::Result<Entry>>,
+{
+    trace.take(n).collect()
+}
+
+/// Walks two traces in lockstep and returns the first instruction after which
+/// PC, registers, I or the display differ, with `context` entries either side.
+pub fn diff<A, B>(mut a: A, mut b: B, context: usize) -> io::Result<Option<Divergence>>
+where
+    A: Iterator<Item = io::Result<Entry>>,
+    B: Iterator<Item = io::Result<Entry>>,
+{
+    let mut replay_a = Replay::default();
+    let mut replay_b = Replay::default();
+    let mut before = VecDeque::with_capacity(context + 1);
+
+    let mut step = 0;
+
+    loop {
+        let (entry_a, entry_b) = match (a.next().transpose()?, b.next().transpose()?) {
+            (None, None) => return Ok(None),
+            entries => entries,
+        };
+
+        let mut differences = Vec::new();
+
+        match (&entry_a, &entry_b) {
+            (Some(ea), Some(eb)) => {
+                replay_a.apply(ea);
+                replay_b.apply(eb);
+
+                if ea.pc != eb.pc {
+                    differences.push(format!("PC: {} != {}", ea.pc, eb.pc));
+                }
+                if ea.opcode != eb.opcode {
+                    differences.push(format!("opcode: {:04X} != {:04X}", ea.opcode, eb.opcode));
+                }
+                for (reg, (ra, rb)) in replay_a
+                    .registers
+                    .iter()
+                    .zip(replay_b.registers.iter())
+                    .enumerate()
+                {
+                    if ra != rb {
+                        differences.push(format!("V{:X}: {:#04X} != {:#04X}", reg, ra, rb));
+                    }
+                }
+                if replay_a.i != replay_b.i {
+                    differences.push(format!("I: {:#05X} != {:#05X}", replay_a.i, replay_b.i));
+                }
+                if replay_a.gfx != replay_b.gfx {
+                    differences.push(format!(
+                        "display: {:08X} != {:08X}",
+                        replay_a.gfx, replay_b.gfx
+                    ));
+                }
+            }
+            (Some(_), None) => differences.push("b ended".to_string()),
+            (None, Some(_)) => differences.push("a ended".to_string()),
+            (None, None) => unreachable!(),
+        }
+
+        if !differences.is_empty() {
+            return Ok(Some(Divergence {
+                step,
+                differences,
+                before: before.into_iter().collect(),
+                a: entry_a,
+                b: entry_b,
+                after_a: take(&mut a, context)?,
+                after_b: take(&mut b, context)?,
+            }));
+        }
+
+        if before.len() == context {
+            before.pop_front();
+        }
+        if context > 0 {
+            before.push_back(entry_a.unwrap());
+        }
+
+        step += 1;
+    }
+}
+
+fn write_side(
+    f: &mut fmt::Formatter,
+    name: &str,
+    entry: &Option<Entry>,
+    after: &[Entry],
+) -> fmt::Result {
+    match entry {
+        Some(entry) => writeln!(f, "{} > {}", name, entry)?,
+        None => writeln!(f, "{} > (end of trace)", name)?,
+    }
+
+    for entry in after {
+        writeln!(f, "    {}", entry)?;
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Traces diverge at step {}:", self.step)?;
+        for difference in &self.differences {
+            writeln!(f, "    {}", difference)?;
+        }
+
+        writeln!(f)?;
+        for entry in &self.before {
+            writeln!(f, "    {}", entry)?;
+        }
+
+        write_side(f, "a", &self.a, &self.after_a)?;
+        write_side(f, "b", &self.b, &self.after_b)?;
+
+        Ok(())
+    }
+}
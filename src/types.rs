@@ -1,17 +1,44 @@
 use derive_more::{Add, AddAssign, From, Into, Sub, SubAssign};
 use enum_map::EnumMap;
 use enumn::N;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
 
 pub(crate) type Bits<'a> = (&'a [u8], usize);
 
-#[derive(Debug, Default, From, Into, Copy, Clone, Add, AddAssign, Sub, SubAssign, PartialEq, Eq)]
+#[derive(
+    Debug,
+    Default,
+    From,
+    Into,
+    Copy,
+    Clone,
+    Add,
+    AddAssign,
+    Sub,
+    SubAssign,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
 pub struct Address(pub u16);
 
-pub const BUTTON_KEYS: [minifb::Key; 16] = {
-    use minifb::Key::*;
+impl Address {
+    pub fn wrapping_add(self, n: u16) -> Self {
+        Address(self.0.wrapping_add(n) & 0xFFF)
+    }
 
-    [Key1, Key2, Key3, Key4, Q, W, E, R, A, S, D, F, Z, X, C, V]
-};
+    pub fn wrapping_sub(self, n: u16) -> Self {
+        Address(self.0.wrapping_sub(n) & 0xFFF)
+    }
+
+    /// Index into `State::memory`, wrapping at the end of the address space.
+    pub fn index(self) -> usize {
+        usize::from(self.0 & 0xFFF)
+    }
+}
 
 #[repr(u8)]
 #[derive(enum_map::Enum, Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash, N)]
@@ -34,35 +61,8 @@ pub enum Button {
     BF = 0xF,
 }
 
-impl Button {
-    pub fn from_key(key: minifb::Key) -> Option<Self> {
-        use minifb::Key::*;
-        use Button::*;
-
-        match key {
-            Key1 => Some(B1),
-            Key2 => Some(B2),
-            Key3 => Some(B3),
-            Key4 => Some(BC),
-            Q => Some(B4),
-            W => Some(B5),
-            E => Some(B6),
-            R => Some(BD),
-            A => Some(B7),
-            S => Some(B8),
-            D => Some(B9),
-            F => Some(BE),
-            Z => Some(BA),
-            X => Some(B0),
-            C => Some(BB),
-            V => Some(BF),
-            _ => None,
-        }
-    }
-}
-
 #[repr(u8)]
-#[derive(enum_map::Enum, Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash)]
+#[derive(enum_map::Enum, Debug, Copy, Clone, PartialOrd, Ord, PartialEq, Eq, Hash, N)]
 pub enum Register {
     V0 = 0x0,
     V1 = 0x1,
@@ -82,8 +82,212 @@ pub enum Register {
     VF = 0xF,
 }
 
+impl std::str::FromStr for Register {
+    type Err = String;
+
+    /// Parses `V0` through `VF`, in either case.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.strip_prefix('V')
+            .or_else(|| s.strip_prefix('v'))
+            .filter(|n| n.len() == 1)
+            .and_then(|n| u8::from_str_radix(n, 16).ok())
+            .and_then(Register::n)
+            .ok_or_else(|| format!("unknown register {:?}", s))
+    }
+}
+
 pub use crate::eval::Instruction;
 
+/// The hexadecimal digits FX29 points at, loaded at 0x000.
+pub const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+/// Behaviours that differ between interpreters, named after Octo's options.
+/// The defaults are this emulator's original behaviour.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE shift VX in place, ignoring VY.
+    pub shift: bool,
+    /// FX55 and FX65 leave I unchanged.
+    pub load_store: bool,
+    /// 8XYN writes VF before the result, so the result wins when X is VF.
+    pub vf_order: bool,
+    /// Sprites are clipped at the edges of the screen instead of wrapping.
+    pub clip: bool,
+    /// BNNN jumps to NNN + VX instead of NNN + V0.
+    pub jump: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF.
+    pub logic: bool,
+    /// DXYN draws at most one sprite per frame.
+    pub vblank: bool,
+}
+
+impl Quirks {
+    /// Each quirk by the name config files and reports use for it.
+    pub fn flags_mut(&mut self) -> [(&'static str, &mut bool); 7] {
+        [
+            ("shift", &mut self.shift),
+            ("load_store", &mut self.load_store),
+            ("vf_order", &mut self.vf_order),
+            ("clip", &mut self.clip),
+            ("jump", &mut self.jump),
+            ("logic", &mut self.logic),
+            ("vblank", &mut self.vblank),
+        ]
+    }
+}
+
+/// Parses a platform's profile and/or quirks to change, like `schip` or
+/// `chip8,shift,no-clip`.
+impl std::str::FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut items = s.split(',').map(str::trim).peekable();
+
+        let mut quirks = match items.peek().map(|item| item.parse::<Platform>()) {
+            Some(Ok(platform)) => {
+                items.next();
+                platform.quirks()
+            }
+            _ => Quirks::default(),
+        };
+
+        for item in items {
+            let (name, on) = match item.strip_prefix("no-") {
+                Some(name) => (name, false),
+                None => (item, true),
+            };
+
+            let mut flags = quirks.flags_mut();
+            let (_, flag) = flags
+                .iter_mut()
+                .find(|(flag, _)| *flag == name)
+                .ok_or_else(|| format!("unknown quirk {:?}", name))?;
+            **flag = on;
+        }
+
+        Ok(quirks)
+    }
+}
+
+/// Lists the quirks that are on.
+impl std::fmt::Display for Quirks {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let mut quirks = *self;
+        let on: Vec<_> = quirks
+            .flags_mut()
+            .iter()
+            .filter(|(_, on)| **on)
+            .map(|(name, _)| *name)
+            .collect();
+
+        if on.is_empty() {
+            f.write_str("none")
+        } else {
+            f.write_str(&on.join(", "))
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks {
+            shift: true,
+            load_store: true,
+            vf_order: false,
+            clip: false,
+            jump: false,
+            logic: false,
+            vblank: false,
+        }
+    }
+}
+
+/// The interpreters ROMs are written for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Platform {
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    /// The quirks of the platform's reference interpreter: the COSMAC VIP,
+    /// SCHIP 1.1 on the HP-48, and Octo.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks {
+                shift: false,
+                load_store: false,
+                vf_order: false,
+                clip: true,
+                jump: false,
+                logic: true,
+                vblank: true,
+            },
+            Platform::SuperChip => Quirks {
+                shift: true,
+                load_store: true,
+                vf_order: false,
+                clip: true,
+                jump: true,
+                logic: false,
+                vblank: false,
+            },
+            Platform::XoChip => Quirks {
+                shift: false,
+                load_store: false,
+                vf_order: false,
+                clip: false,
+                jump: false,
+                logic: false,
+                vblank: false,
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for Platform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "chip8" => Ok(Platform::Chip8),
+            "schip" => Ok(Platform::SuperChip),
+            "xochip" => Ok(Platform::XoChip),
+            _ => Err(format!("unknown platform {:?}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Platform::Chip8 => "chip8",
+            Platform::SuperChip => "schip",
+            Platform::XoChip => "xochip",
+        })
+    }
+}
+
+#[derive(Clone)]
 pub struct State {
     pub memory: [u8; 4096],
     pub registers: EnumMap<Register, u8>,
@@ -95,6 +299,11 @@ pub struct State {
     pub bit_gfx: [u8; 256],
     pub pix_gfx: [u32; 2048],
     pub buttons: EnumMap<Button, bool>,
+    pub frame: u64,
+    pub rng: StdRng,
+    pub quirks: Quirks,
+    /// Set by a draw and cleared at the next frame, for `Quirks::vblank`.
+    pub drawn: bool,
 }
 
 impl Default for State {
@@ -110,6 +319,10 @@ impl Default for State {
             bit_gfx: [0u8; 256],
             pix_gfx: [0u32; 2048],
             buttons: Default::default(),
+            frame: 0,
+            rng: StdRng::from_entropy(),
+            quirks: Default::default(),
+            drawn: false,
         }
     }
 }
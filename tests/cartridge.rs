@@ -0,0 +1,96 @@
+use chip8::cartridge::{Cartridge, Options};
+use chip8::types::{Address, Register, State};
+
+const SOURCE: &str = "
+: main
+  v1 := 0x81
+  v0 >>= v1
+  i := 0x300
+  save v1
+  jump0 0x400
+";
+
+fn cartridge(options: Options) -> Cartridge {
+    let cartridge = Cartridge {
+        source: SOURCE.into(),
+        options,
+    };
+
+    let mut gif = Vec::new();
+    cartridge.write(&mut gif).unwrap();
+    assert_eq!(&gif[..6], b"GIF89a");
+
+    Cartridge::read(&gif[..]).unwrap()
+}
+
+fn run(cartridge: &Cartridge) -> State {
+    let program = cartridge.assemble("cart.gif").unwrap();
+
+    let mut state = State {
+        quirks: cartridge.options.quirks,
+        ..State::default()
+    };
+    state.memory[0x200..0x200 + program.bytes.len()].copy_from_slice(&program.bytes);
+
+    while state.pc.0 < 0x200 + program.bytes.len() as u16 {
+        state.step().unwrap();
+    }
+
+    state
+}
+
+#[test]
+fn round_trips_source_and_options() {
+    let mut options = Options {
+        tickrate: Some(500),
+        fill_color: Some(0xFFCC00),
+        background_color: Some(0x996600),
+        ..Options::default()
+    };
+    options.quirks.shift = true;
+    options.quirks.vblank = true;
+
+    let cartridge = cartridge(options.clone());
+    assert_eq!(cartridge.source, SOURCE);
+    assert_eq!(cartridge.options, options);
+
+    assert!(Cartridge::read(&b"GIF89a"[..]).is_err());
+}
+
+#[test]
+fn rejects_zero_tickrate() {
+    let cartridge = Cartridge {
+        source: SOURCE.into(),
+        options: Options {
+            tickrate: Some(0),
+            ..Options::default()
+        },
+    };
+
+    let mut gif = Vec::new();
+    cartridge.write(&mut gif).unwrap();
+    assert_eq!(
+        Cartridge::read(&gif[..]).unwrap_err(),
+        "tickrate must be at least 1"
+    );
+}
+
+#[test]
+fn options_configure_quirks() {
+    let vip = run(&cartridge(Options::default()));
+    assert_eq!(vip.registers[Register::V0], 0x40);
+    assert_eq!(vip.registers[Register::VF], 1);
+    assert_eq!(vip.i_reg, Address(0x302));
+    assert_eq!(vip.pc, Address(0x440));
+
+    let mut options = Options::default();
+    options.quirks.shift = true;
+    options.quirks.load_store = true;
+    options.quirks.jump = true;
+
+    let schip = run(&cartridge(options));
+    assert_eq!(schip.registers[Register::V0], 0);
+    assert_eq!(schip.registers[Register::VF], 0);
+    assert_eq!(schip.i_reg, Address(0x300));
+    assert_eq!(schip.pc, Address(0x400));
+}
@@ -0,0 +1,62 @@
+use chip8::cfg::{Cfg, EdgeKind, Issue};
+use chip8::types::Address;
+
+mod common;
+
+fn analyze(words: &[u16]) -> Cfg {
+    let (memory, rom) = common::program(words);
+    Cfg::new(&memory, rom)
+}
+
+#[test]
+fn indexed_jump_follows_v0() {
+    let cfg = analyze(&[
+        0xC002, // RND V0, 0x02
+        0xB206, // JP V0, 0x206
+        0x1204, // JP 0x204
+        0x00E0, // CLS
+        0x1208, // JP 0x208
+    ]);
+
+    let targets: Vec<_> = cfg.blocks[&Address(0x200)].successors.clone();
+    assert_eq!(
+        targets,
+        vec![
+            (Address(0x206), EdgeKind::Indexed),
+            (Address(0x208), EdgeKind::Indexed)
+        ]
+    );
+    assert!(cfg.blocks.contains_key(&Address(0x208)));
+    assert_eq!(cfg.issues, vec![Issue::Unreachable(0x204..0x206)]);
+}
+
+#[test]
+fn flags_stack_and_alignment_problems() {
+    let cfg = analyze(&[
+        0x2210, // CALL 0x210
+        0x6002, // LD V0, 0x02
+        0xB20A, // JP V0, 0x20A
+        0x00EE, // RET
+        0x1209, // JP 0x209
+        0x0000, // SYS 0x000
+        0x00E0, // CLS
+        0x00EE, // RET
+        0x2210, // CALL 0x210
+        0x120F, // JP 0x20F
+    ]);
+
+    assert_eq!(cfg.subroutines.len(), 2);
+    for issue in &[
+        Issue::InvalidInstruction(Address(0x20F)),
+        Issue::MisalignedJump {
+            from: Address(0x212),
+            to: Address(0x20F),
+        },
+        Issue::ReturnWithoutCall(Address(0x20E)),
+        Issue::NoReturn(Address(0x210)),
+        Issue::Recursion(Address(0x210)),
+        Issue::Unreachable(0x206..0x20C),
+    ] {
+        assert!(cfg.issues.contains(issue), "missing {}", issue);
+    }
+}
@@ -0,0 +1,11 @@
+use std::ops::Range;
+
+/// Memory holding `words` from 0x200, and the range they fill.
+pub fn program(words: &[u16]) -> ([u8; 4096], Range<usize>) {
+    let mut memory = [0; 4096];
+    for (i, word) in words.iter().enumerate() {
+        memory[0x200 + 2 * i..0x202 + 2 * i].copy_from_slice(&word.to_be_bytes());
+    }
+
+    (memory, 0x200..0x200 + 2 * words.len())
+}
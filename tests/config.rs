@@ -0,0 +1,68 @@
+use chip8::config::{Config, Settings};
+use chip8::romdb;
+use chip8::types::{Button, Platform};
+use minifb::Key;
+
+const ROM: &[u8] = &[0x12, 0x00];
+
+fn config() -> Config {
+    format!(
+        r##"
+scale = 8
+quirks = "schip"
+foreground = "#FFCC00"
+
+[keys]
+W = 1
+Up = 5
+
+[roms."game.ch8"]
+speed = 10
+volume = 0.25
+
+[roms.{}]
+speed = 20
+keys = {{ Up = 0xC }}
+"##,
+        romdb::hash(ROM).to_ascii_uppercase()
+    )
+    .parse()
+    .unwrap()
+}
+
+#[test]
+fn rom_sections_override_defaults() {
+    let config = config();
+    assert_eq!(config.defaults.scale, Some(8));
+    assert_eq!(config.defaults.quirks, Some(Platform::SuperChip.quirks()));
+    assert_eq!(config.defaults.foreground, Some(0xFFCC00));
+
+    let mut settings = config.defaults.clone();
+    settings.merge(&config.rom("game.ch8", ROM));
+    assert_eq!(settings.speed, Some(20));
+    assert_eq!(settings.volume, Some(0.25));
+    assert_eq!(settings.scale, Some(8));
+    assert_eq!(settings.keys, [(Key::W, Button::B1), (Key::Up, Button::BC)]);
+
+    let other = config.rom("other.ch8", &[0x00, 0xE0]);
+    assert_eq!(other, Settings::default());
+}
+
+#[test]
+fn prints_settings_it_can_read_back() {
+    let settings = config().defaults;
+    let printed: Config = settings.to_string().parse().unwrap();
+    assert_eq!(printed.defaults, settings);
+
+    let err = |toml: &str| toml.parse::<Config>().unwrap_err();
+    assert_eq!(
+        err("scale = 3"),
+        "invalid scale 3: expected 1, 2, 4, 8, 16 or 32"
+    );
+    assert_eq!(err("speed = 0"), "speed must be at least 1");
+    assert_eq!(
+        err("screenshot_scale = 100000"),
+        "screenshot_scale must be from 1 to 64"
+    );
+    assert_eq!(err("[roms.x]\nfps = 60"), "roms.x: unknown setting \"fps\"");
+}
@@ -0,0 +1,83 @@
+use chip8::coverage::Coverage;
+use chip8::types::{Address, State};
+
+const ROM: [u8; 12] = [
+    0xA2, 0x0A, // LD I, 0x20A
+    0xD0, 0x01, // DRW V0, V0, 1
+    0xA2, 0x0A, // LD I, 0x20A
+    0xF1, 0x55, // LD [I], V1
+    0x12, 0x08, // JP 0x208
+    0xF0, 0x90, // sprite
+];
+
+fn cover() -> (Coverage, State) {
+    let mut state = State::default();
+    state.memory[0x200..0x200 + ROM.len()].copy_from_slice(&ROM);
+    let mut coverage = Coverage::new(0x200..0x200 + ROM.len());
+
+    for _ in 0..6 {
+        let instr = state.fetch().unwrap();
+        coverage.record(&state, &instr);
+        instr.eval(&mut state).unwrap();
+    }
+
+    (coverage, state)
+}
+
+#[test]
+fn marks_fetched_read_and_written_bytes() {
+    let (coverage, _) = cover();
+    let marked = |flag: fn(&Coverage, Address) -> bool| {
+        (0..0x1000)
+            .filter(|&addr| flag(&coverage, Address(addr)))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        marked(Coverage::fetched),
+        (0x200..0x20A).collect::<Vec<_>>()
+    );
+    assert_eq!(marked(Coverage::read), [0x20A]);
+    assert_eq!(marked(Coverage::written), [0x20A, 0x20B]);
+}
+
+#[test]
+fn writes_json() {
+    let (coverage, _) = cover();
+
+    let mut json = Vec::new();
+    coverage.write_json(&mut json).unwrap();
+    assert_eq!(
+        String::from_utf8(json).unwrap(),
+        "\
+{
+  \"rom\": [512, 523],
+  \"fetched\": [[512, 521]],
+  \"read\": [[522, 522]],
+  \"written\": [[522, 523]]
+}
+"
+    );
+}
+
+#[test]
+fn writes_hex() {
+    let (coverage, state) = cover();
+
+    let mut hex = Vec::new();
+    coverage.write_hex(&state.memory, &mut hex).unwrap();
+    assert_eq!(
+        String::from_utf8(hex).unwrap(),
+        "\
+ROM 0x200-0x20B: 12 of 12 bytes touched (100.0%)
+    fetched     10 (83.3%)
+    read         1 (8.3%)
+    written      2 (16.7%)
+
+*
+200  A2 0A D0 01 A2 0A F1 55 12 08 00 00 00 00 00 00
+     C. C. C. C. C. C. C. C. C. C. .B .W .. .. .. ..
+*
+"
+    );
+}
@@ -0,0 +1,258 @@
+use chip8::dap::DapServer;
+use chip8::debug::{Debugger, Frontend, Resume};
+use chip8::types::State;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+const ROM: [u8; 12] = [
+    0x60, 0x05, // LD V0, 0x05
+    0x22, 0x08, // CALL 0x208
+    0x70, 0x01, // ADD V0, 0x01
+    0x12, 0x06, // JP 0x206
+    0x61, 0x0A, // LD V1, 0x0A
+    0x00, 0xEE, // RET
+];
+
+const LINE_MAP: &str = "\
+0x200 game.8o:1
+0x202 game.8o:2
+0x204 game.8o:3
+0x206 game.8o:4
+0x208 game.8o:6
+0x20A game.8o:7
+";
+
+/// Runs a machine the way `main` does, minus the window.
+fn serve(listener: TcpListener) {
+    let (stream, _) = listener.accept().unwrap();
+    let (mut server, launch) = DapServer::new(stream).unwrap();
+    assert_eq!(launch.program.to_str(), Some("game.ch8"));
+
+    let mut state = State::default();
+    state.memory[0x200..0x200 + ROM.len()].copy_from_slice(&ROM);
+    let mut debugger = Debugger::new();
+
+    let mut paused = true;
+
+    loop {
+        if !paused {
+            paused = server.interrupted(&mut state, &mut debugger).unwrap();
+        }
+
+        if paused {
+            match server.serve(&mut state, &mut debugger).unwrap() {
+                Resume::Continue => paused = false,
+                Resume::Step => {}
+                Resume::Detach | Resume::Kill => return,
+            }
+        }
+
+        let instr = state.fetch().unwrap();
+        if !debugger.eval(&instr, &mut state).unwrap().is_empty() {
+            paused = true;
+        }
+    }
+}
+
+struct Client {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    seq: u64,
+    events: VecDeque<Value>,
+}
+
+impl Client {
+    fn connect() -> (Self, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || serve(listener));
+
+        let stream = TcpStream::connect(addr).unwrap();
+        let client = Client {
+            reader: BufReader::new(stream.try_clone().unwrap()),
+            writer: stream,
+            seq: 1,
+            events: VecDeque::new(),
+        };
+
+        (client, server)
+    }
+
+    fn read_message(&mut self) -> Value {
+        let mut length = 0;
+
+        loop {
+            let mut header = String::new();
+            self.reader.read_line(&mut header).unwrap();
+            let header = header.trim_end();
+
+            if header.is_empty() {
+                break;
+            }
+            if let Some(n) = header.strip_prefix("Content-Length:") {
+                length = n.trim().parse().unwrap();
+            }
+        }
+
+        let mut body = vec![0; length];
+        self.reader.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    /// Sends a request, returning its whole response.
+    fn respond(&mut self, command: &str, arguments: Value) -> Value {
+        let seq = self.seq;
+        self.seq += 1;
+
+        let message = json!({
+            "seq": seq,
+            "type": "request",
+            "command": command,
+            "arguments": arguments,
+        })
+        .to_string();
+        write!(
+            self.writer,
+            "Content-Length: {}\r\n\r\n{}",
+            message.len(),
+            message
+        )
+        .unwrap();
+
+        loop {
+            let message = self.read_message();
+
+            if message["type"] == "event" {
+                self.events.push_back(message);
+            } else if message["request_seq"] == seq {
+                return message;
+            }
+        }
+    }
+
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        let response = self.respond(command, arguments);
+        assert_eq!(response["success"], true, "{}", response);
+        response["body"].clone()
+    }
+
+    fn event(&mut self, event: &str) -> Value {
+        loop {
+            let message = match self.events.pop_front() {
+                Some(message) => message,
+                None => self.read_message(),
+            };
+
+            if message["event"] == event {
+                return message["body"].clone();
+            }
+        }
+    }
+
+    fn top_frame(&mut self) -> Value {
+        self.request("stackTrace", json!({ "threadId": 1 }))["stackFrames"][0].clone()
+    }
+}
+
+#[test]
+fn debugs_by_source_line() {
+    let dir = std::env::temp_dir().join(format!("chip8-dap-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let line_map = dir.join("game.map");
+    fs::write(&line_map, LINE_MAP).unwrap();
+
+    let (mut client, server) = Client::connect();
+
+    client.request("initialize", json!({ "adapterID": "chip8" }));
+    client.request(
+        "launch",
+        json!({ "program": "game.ch8", "lineMap": line_map }),
+    );
+    client.event("initialized");
+
+    let source = dir.join("game.8o");
+    let breakpoints = client.request(
+        "setBreakpoints",
+        json!({ "source": { "path": source }, "breakpoints": [{ "line": 2 }, { "line": 5 }] }),
+    );
+    assert_eq!(
+        breakpoints["breakpoints"],
+        json!([{ "verified": true, "line": 2 }, { "verified": true, "line": 6 }])
+    );
+
+    client.request("configurationDone", Value::Null);
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+    assert_eq!(client.top_frame()["line"], 2);
+    assert_eq!(client.top_frame()["source"]["path"], json!(source));
+
+    let registers = client.request("variables", json!({ "variablesReference": 1 }));
+    assert_eq!(
+        registers["variables"][0],
+        json!({ "name": "V0", "value": "0x05", "variablesReference": 0 })
+    );
+
+    client.request("stepIn", json!({ "threadId": 1 }));
+    client.event("stopped");
+    let frames = client.request("stackTrace", json!({ "threadId": 1 }))["stackFrames"].clone();
+    assert_eq!(frames[0]["line"], 6);
+    assert_eq!(frames[1]["line"], 2);
+
+    client.request("stepOut", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "step");
+    assert_eq!(client.top_frame()["line"], 3);
+
+    client.request("next", json!({ "threadId": 1 }));
+    client.event("stopped");
+    assert_eq!(client.top_frame()["line"], 4);
+
+    let memory = client.request(
+        "readMemory",
+        json!({ "memoryReference": "0x200", "count": 4 }),
+    );
+    assert_eq!(memory["data"], "YAUiCA==");
+
+    client.request(
+        "writeMemory",
+        json!({ "memoryReference": "0x300", "data": "q80=" }),
+    );
+    let memory = client.request(
+        "readMemory",
+        json!({ "memoryReference": "0x2FF", "offset": 1, "count": 2 }),
+    );
+    assert_eq!(memory["data"], "q80=");
+
+    let memory = client.request(
+        "readMemory",
+        json!({ "memoryReference": "0xFFE", "count": u64::MAX }),
+    );
+    assert_eq!(memory["data"], "AAA=");
+    assert_eq!(memory["unreadableBytes"], u64::MAX - 2);
+
+    let failed = |response: Value| response["success"] == false;
+    assert!(failed(client.respond(
+        "readMemory",
+        json!({ "memoryReference": "0x200", "offset": i64::MAX, "count": 1 }),
+    )));
+    assert!(failed(client.respond(
+        "readMemory",
+        json!({ "memoryReference": "0x200", "offset": -0x201, "count": 1 }),
+    )));
+    assert!(failed(client.respond(
+        "writeMemory",
+        json!({ "memoryReference": "0xFFF", "offset": i64::MAX, "data": "q80=" }),
+    )));
+
+    client.request("continue", json!({ "threadId": 1 }));
+    client.request("pause", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "pause");
+    assert_eq!(client.top_frame()["line"], 4);
+
+    client.request("disconnect", Value::Null);
+    server.join().unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+}
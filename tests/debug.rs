@@ -0,0 +1,145 @@
+use chip8::debug::{Access, Breakpoint, Debugger, Stop, WatchKind, Watchpoint};
+use chip8::symbols::Symbols;
+use chip8::types::{Address, Button, Register, State};
+
+/// A machine about to run `instr` at 0x200, with I at 0x300 and V0 to V2
+/// holding 1 to 3.
+fn machine(instr: [u8; 2]) -> State {
+    let mut state = State::default();
+    state.memory[0x200..0x202].copy_from_slice(&instr);
+    state.memory[0x300..0x306].copy_from_slice(&[0xA0, 0xB0, 0xC0, 0xD0, 0xE0, 0xF0]);
+    state.i_reg = Address(0x300);
+    state.registers[Register::V0] = 1;
+    state.registers[Register::V1] = 2;
+    state.registers[Register::V2] = 3;
+    state
+}
+
+/// Runs `instr` watching `watchpoints`, checking the machine ends up where
+/// it would have without them.
+fn watch(instr: [u8; 2], watchpoints: &[&str]) -> Vec<Stop> {
+    let mut debugger = Debugger::new();
+    for watchpoint in watchpoints {
+        debugger.watchpoints.push(watchpoint.parse().unwrap());
+    }
+
+    let mut state = machine(instr);
+    let stops = debugger.eval(&state.fetch().unwrap(), &mut state).unwrap();
+
+    let mut unwatched = machine(instr);
+    unwatched.fetch().unwrap().eval(&mut unwatched).unwrap();
+    assert_eq!(state.memory[..], unwatched.memory[..]);
+    assert_eq!(state.registers, unwatched.registers);
+    assert_eq!((state.i_reg, state.pc), (unwatched.i_reg, unwatched.pc));
+    assert_eq!(state.bit_gfx[..], unwatched.bit_gfx[..]);
+
+    stops
+}
+
+fn watched(addr: u16, access: Access, old: u8, new: u8) -> Stop {
+    Stop::Watch {
+        watchpoint: 0,
+        pc: Address(0x200),
+        addr: Address(addr),
+        access,
+        old,
+        new,
+    }
+}
+
+#[test]
+fn parses_ranges_and_kinds() {
+    let mut symbols = Symbols::new();
+    symbols.insert("score", Address(0x2F0));
+
+    let parse = |s| Watchpoint::parse(s, &symbols);
+    assert_eq!(
+        parse("2F0-2F2:w"),
+        Ok(Watchpoint {
+            range: 0x2F0..=0x2F2,
+            kind: WatchKind::Write
+        })
+    );
+    assert_eq!(
+        parse("score+1:r"),
+        Ok(Watchpoint {
+            range: 0x2F1..=0x2F1,
+            kind: WatchKind::Read
+        })
+    );
+    assert_eq!(parse("score:rw"), parse("2F0"));
+    assert_eq!(parse("score").unwrap().kind, WatchKind::Access);
+    assert_eq!(parse("2F0-2F2:w").unwrap().to_string(), "2F0-2F2:w");
+
+    assert!(parse("2F0:x").is_err());
+    assert!(parse("lives").is_err());
+}
+
+#[test]
+fn reg_dump_writes_its_range() {
+    assert_eq!(
+        watch([0xF2, 0x55], &["301-305:w"]),
+        [
+            watched(0x301, Access::Write, 0xB0, 2),
+            watched(0x302, Access::Write, 0xC0, 3)
+        ]
+    );
+    assert_eq!(watch([0xF2, 0x55], &["301-305:r"]), []);
+}
+
+#[test]
+fn reg_load_reads_its_range() {
+    assert_eq!(
+        watch([0xF1, 0x65], &["2FF-300:r"]),
+        [watched(0x300, Access::Read, 0xA0, 0xA0)]
+    );
+    assert_eq!(watch([0xF1, 0x65], &["300-302:w"]), []);
+}
+
+#[test]
+fn draw_reads_its_sprite() {
+    assert_eq!(
+        watch([0xD0, 0x05], &["304-305"]),
+        [watched(0x304, Access::Read, 0xE0, 0xE0)]
+    );
+}
+
+#[test]
+fn fetch_stops_once_per_instruction() {
+    assert_eq!(
+        watch([0x60, 0x07], &["200-201:r"]),
+        [watched(0x200, Access::Fetch, 0x60, 0x60)]
+    );
+    assert_eq!(watch([0x60, 0x07], &["200-201:w"]), []);
+}
+
+#[test]
+fn continues_from_a_breakpoint_on_a_key_wait() {
+    let mut debugger = Debugger::new();
+    debugger
+        .breakpoints
+        .push(Breakpoint::parse("202", &Symbols::new()).unwrap());
+
+    let mut state = State::default();
+    state.memory[0x200..0x206].copy_from_slice(&[0x60, 0x07, 0xF1, 0x0A, 0x12, 0x04]);
+    let mut step = |state: &mut State| debugger.eval(&state.fetch().unwrap(), state).unwrap();
+
+    assert_eq!(
+        step(&mut state),
+        [Stop::Breakpoint {
+            breakpoint: 0,
+            pc: Address(0x202)
+        }]
+    );
+
+    // Still waiting: PC stays on the breakpoint without stopping again.
+    for _ in 0..3 {
+        assert_eq!(step(&mut state), []);
+        assert_eq!(state.pc, Address(0x202));
+    }
+
+    state.buttons[Button::B5] = true;
+    assert_eq!(step(&mut state), []);
+    assert_eq!(state.pc, Address(0x204));
+    assert_eq!(state.registers[Register::V1], 5);
+}
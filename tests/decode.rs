@@ -0,0 +1,41 @@
+use chip8::parser::{self, DecodeError};
+use proptest::prelude::*;
+
+#[test]
+fn every_word_decodes_or_is_unknown() {
+    for word in 0..=0xFFFF_u16 {
+        match parser::decode(&word.to_be_bytes()) {
+            Ok((rest, _)) => assert!(rest.is_empty(), "{:#06X}", word),
+            Err(err) => assert_eq!(err, DecodeError::UnknownOpcode(word)),
+        }
+    }
+}
+
+#[test]
+fn decode_agrees_with_encode() {
+    for word in 0..=0xFFFF_u16 {
+        if let Ok((_, instr)) = parser::decode(&word.to_be_bytes()) {
+            assert_eq!(instr.encode(), word, "{:?}", instr);
+
+            let (_, roundtrip) = parser::decode(&instr.encode().to_be_bytes()).unwrap();
+            assert_eq!(roundtrip, instr);
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn decode_consumes_one_word(bytes in proptest::collection::vec(any::<u8>(), 2..16)) {
+        if let Ok((rest, _)) = parser::decode(&bytes) {
+            prop_assert_eq!(rest.len(), bytes.len() - 2);
+        }
+        if let Ok((rest, _)) = parser::instr(&bytes) {
+            prop_assert_eq!(rest.len(), bytes.len() - 2);
+        }
+    }
+
+    #[test]
+    fn short_input_is_incomplete(bytes in proptest::collection::vec(any::<u8>(), 0..2)) {
+        prop_assert_eq!(parser::decode(&bytes).unwrap_err(), DecodeError::Incomplete);
+    }
+}
@@ -0,0 +1,77 @@
+use chip8::decompile::decompile;
+use chip8::symbols::Symbols;
+
+mod common;
+
+fn octo(words: &[u16], symbols: &Symbols) -> String {
+    let (memory, rom) = common::program(words);
+
+    let mut out = Vec::new();
+    decompile(&memory, rom, symbols, &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn recovers_structures() {
+    let source = octo(
+        &[
+            0x6000, // LD V0, 0x00
+            0x7001, // ADD V0, 0x01
+            0x3005, // SE V0, 0x05
+            0x120C, // JP 0x20C
+            0x00E0, // CLS
+            0x120E, // JP 0x20E
+            0x6100, // LD V1, 0x00
+            0x1202, // JP 0x202
+            0x00EE, // RET
+        ],
+        &Symbols::new(),
+    );
+
+    assert_eq!(
+        source,
+        ": main\n\
+         \tv0 := 0x00\n\
+         \tloop\n\
+         \t\tv0 += 0x01\n\
+         \t\tif v0 == 0x05 begin\n\
+         \t\t\tclear\n\
+         \t\telse\n\
+         \t\t\tv1 := 0x00\n\
+         \t\tend\n\
+         \tagain\n\
+         \t0x00 0xEE\n"
+    );
+}
+
+#[test]
+fn names_labels_and_sprites() {
+    let symbols = "ball = 0x20A".parse().unwrap();
+    let source = octo(
+        &[
+            0xA20A, // LD I, 0x20A
+            0x2208, // CALL 0x208
+            0xE1A1, // SKNP V1
+            0x0123, // SYS 0x123
+            0x00EE, // RET
+            0x8000, // sprite rows
+        ],
+        &symbols,
+    );
+
+    assert_eq!(
+        source,
+        ": main\n\
+         \ti := ball\n\
+         \tsub_208\n\
+         \t0xE1 0xA1\n\
+         \t0x01 0x23\n\
+         \n\
+         : sub_208\n\
+         \treturn\n\
+         \n\
+         : ball\n\
+         \t0b10000000\n\
+         \t0b00000000\n"
+    );
+}
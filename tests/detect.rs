@@ -0,0 +1,71 @@
+use chip8::detect::{self, Confidence, Detection, Feature, Hint};
+use chip8::types::{Address, Platform};
+
+mod common;
+
+fn detect(words: &[u16]) -> Detection {
+    let (memory, rom) = common::program(words);
+    detect::detect(&memory, rom)
+}
+
+#[test]
+fn detects_platforms_from_reachable_code() {
+    let schip = detect(&[
+        0x00FF, // HIGH
+        0xD010, // DRW V0, V1, 0
+        0x1204, // JP 0x204
+    ]);
+    assert_eq!(schip.platform, Platform::SuperChip);
+    assert_eq!(schip.confidence, Confidence::High);
+    assert_eq!(
+        schip.hits.iter().map(|hit| hit.feature).collect::<Vec<_>>(),
+        vec![Feature::HiRes, Feature::LargeSprite]
+    );
+
+    let xochip = detect(&[
+        0x00FF, // HIGH
+        0xF000, // LD I, long
+        0x5123, // the address, which isn't an instruction
+        0x1206, // JP 0x206
+    ]);
+    assert_eq!(xochip.platform, Platform::XoChip);
+    assert_eq!(xochip.confidence, Confidence::High);
+    assert_eq!(xochip.hits.len(), 2);
+
+    // A single stray word after the program is probably data.
+    let chip8 = detect(&[
+        0x00E0, // CLS
+        0x1202, // JP 0x202
+        0x00FF, // data
+    ]);
+    assert_eq!(chip8.platform, Platform::Chip8);
+    assert_eq!(chip8.confidence, Confidence::Medium);
+    assert!(!chip8.hits[0].reachable);
+}
+
+#[test]
+fn guesses_quirks_from_usage() {
+    let vip = detect(&[
+        0x8126, // SHR V1, V2
+        0xA300, // LD I, 0x300
+        0xF155, // LD [I], V1
+        0xF355, // LD [I], V3
+        0x1208, // JP 0x208
+    ]);
+    assert_eq!(
+        vip.hints,
+        vec![
+            Hint::ShiftsVy(Address(0x200)),
+            Hint::IncrementsI(Address(0x206))
+        ]
+    );
+    assert!(!vip.quirks.shift && !vip.quirks.load_store);
+
+    let test2 = std::fs::read("test2.ch8").unwrap();
+    let mut memory = [0; 4096];
+    memory[0x200..0x200 + test2.len()].copy_from_slice(&test2);
+
+    let detection = detect::detect(&memory, 0x200..0x200 + test2.len());
+    assert_eq!(detection.platform, Platform::Chip8);
+    assert!(detection.quirks.shift && detection.quirks.load_store);
+}
@@ -0,0 +1,28 @@
+use chip8::disasm::write_listing;
+use chip8::symbols::Symbols;
+use chip8::types::Address;
+
+#[test]
+fn lists_code_and_data() {
+    let mut memory = [0; 4096];
+    let rom = [0x22, 0x06, 0x12, 0x02, 0xAB, 0xCD, 0x00, 0xEE];
+    memory[0x200..0x208].copy_from_slice(&rom);
+
+    let mut symbols = Symbols::new();
+    symbols.insert("sub", Address(0x206));
+
+    let mut out = Vec::new();
+    write_listing(&memory, 0x200..0x208, &symbols, &mut out).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "\
+0x200  2206  CALL sub
+0x202  1202  JP 0x202
+0x204  AB    DB 0xAB
+0x205  CD    DB 0xCD
+sub:
+0x206  00EE  RET
+"
+    );
+}
@@ -0,0 +1,116 @@
+use chip8::debug::Breakpoint;
+use chip8::expr::Condition;
+use chip8::symbols::Symbols;
+use chip8::types::{Address, Register, State};
+
+fn state() -> State {
+    let mut state = State {
+        pc: Address(0x2A4),
+        i_reg: Address(0x300),
+        timer: 3,
+        call_stack: vec![Address(0x202)],
+        frame: 60,
+        ..State::default()
+    };
+    state.memory[0x301] = 7;
+    state.registers[Register::V1] = 1;
+    state.registers[Register::VF] = 0xFF;
+    state
+}
+
+fn symbols() -> Symbols {
+    let mut symbols = Symbols::new();
+    symbols.insert("score", Address(0x301));
+    symbols.insert("pc", Address(0x123));
+    symbols
+}
+
+fn eval(s: &str) -> i64 {
+    Condition::parse(s, &symbols())
+        .unwrap_or_else(|err| panic!("{:?}: {}", s, err))
+        .value(&state())
+}
+
+fn error(s: &str) -> String {
+    Condition::parse(s, &symbols()).unwrap_err()
+}
+
+#[test]
+fn reads_variables_and_memory() {
+    assert_eq!(eval("pc"), 0x2A4);
+    assert_eq!(eval("PC"), 0x2A4);
+    assert_eq!(eval("i"), 0x300);
+    assert_eq!(eval("v1 + V1 + vf"), 0x101);
+    assert_eq!(eval("dt + st"), 3);
+    assert_eq!(eval("sp"), 1);
+    assert_eq!(eval("frame"), 60);
+    assert_eq!(eval("[i + 1]"), 7);
+    assert_eq!(eval("[0x301] == 7"), 1);
+}
+
+#[test]
+fn falls_back_to_symbols() {
+    assert_eq!(eval("score"), 0x301);
+    assert_eq!(eval("[score] - 7"), 0);
+    assert_eq!(error("lives > 0"), "unknown variable \"lives\"");
+}
+
+#[test]
+fn binds_by_precedence() {
+    assert_eq!(eval("v1 + 1 == 2 && !(i & 1)"), 1);
+    assert_eq!(eval("[i + 1] != 0"), 1);
+    assert_eq!(eval("0 && 0 || 1"), 1);
+    assert_eq!(eval("1 || 0 && 0"), 1);
+    assert_eq!(eval("1 ^ 1 | 1"), 1);
+    assert_eq!(eval("1 | 2 ^ 3 & 1"), 3);
+    assert_eq!(eval("5 & 3 == 3"), 1);
+    assert_eq!(eval("1 < 2 == 1"), 1);
+    assert_eq!(eval("3 == 1 + 2"), 1);
+    assert_eq!(eval("10 - 3 - 2"), 5);
+    assert_eq!(eval("(10 - 3) - 2 == 10 - (3 + 2)"), 1);
+}
+
+#[test]
+fn applies_unary_operators() {
+    assert_eq!(eval("-v1 + 3"), 2);
+    assert_eq!(eval("!0"), 1);
+    assert_eq!(eval("!5"), 0);
+    assert_eq!(eval("!!vf"), 1);
+    assert_eq!(eval("--1"), 1);
+}
+
+#[test]
+fn rejects_malformed_expressions() {
+    assert_eq!(error("v1 +"), "unexpected end of expression");
+    assert_eq!(error(""), "unexpected end of expression");
+    assert_eq!(error("(v1 == 1"), "expected \")\"");
+    assert_eq!(error("[i + 1"), "expected \"]\"");
+    assert_eq!(error("v1 == )"), "unexpected \")\"");
+    assert_eq!(error("0x1G"), "invalid number \"0x1G\"");
+    assert_eq!(error("v1 $ 2"), "unexpected character in \"$ 2\"");
+    assert!(Condition::parse("1 2", &symbols()).is_err());
+}
+
+#[test]
+fn parses_breakpoints() {
+    let symbols = symbols();
+    let parse = |s| Breakpoint::parse(s, &symbols).unwrap();
+
+    let at = parse("2A4");
+    assert_eq!(at.address, Some(Address(0x2A4)));
+    assert_eq!(at.condition, None);
+    assert!(at.hit(&state()));
+
+    let conditional = parse("score+1 if v1 == 1");
+    assert_eq!(conditional.address, Some(Address(0x302)));
+    assert_eq!(conditional.condition.unwrap().to_string(), "v1 == 1");
+
+    let anywhere = parse("if [score] > 5");
+    assert_eq!(anywhere.address, None);
+    assert!(anywhere.hit(&state()));
+    assert!(!parse("if [score] > 7").hit(&state()));
+    assert!(!parse("2A6 if v1 == 1").hit(&state()));
+
+    assert!(Breakpoint::parse("lives", &symbols).is_err());
+    assert!(Breakpoint::parse("2A4 if v1 ==", &symbols).is_err());
+}
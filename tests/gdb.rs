@@ -0,0 +1,175 @@
+use chip8::debug::{Debugger, Frontend, Resume};
+use chip8::gdb::GdbStub;
+use chip8::types::State;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+const ROM: [u8; 8] = [
+    0x60, 0x05, // LD V0, 0x05
+    0x61, 0x0A, // LD V1, 0x0A
+    0x70, 0x01, // ADD V0, 0x01
+    0x12, 0x04, // JP 0x204
+];
+
+/// Runs a machine the way `main` does, minus the window.
+fn serve(listener: TcpListener) {
+    let (stream, _) = listener.accept().unwrap();
+    let mut stub = GdbStub::new(stream).unwrap();
+
+    let mut state = State::default();
+    state.memory[0x200..0x200 + ROM.len()].copy_from_slice(&ROM);
+    let mut debugger = Debugger::new();
+
+    loop {
+        match stub.serve(&mut state, &mut debugger).unwrap() {
+            Resume::Step => {
+                let instr = state.fetch().unwrap();
+                debugger.eval(&instr, &mut state).unwrap();
+            }
+            Resume::Continue => loop {
+                let instr = state.fetch().unwrap();
+                if !debugger.eval(&instr, &mut state).unwrap().is_empty()
+                    || stub.interrupted(&mut state, &mut debugger).unwrap()
+                {
+                    break;
+                }
+            },
+            Resume::Detach | Resume::Kill => return,
+        }
+    }
+}
+
+struct Client(TcpStream);
+
+impl Client {
+    fn connect() -> (Self, thread::JoinHandle<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || serve(listener));
+
+        (Client(TcpStream::connect(addr).unwrap()), server)
+    }
+
+    fn read_packet(&mut self) -> String {
+        let mut byte = [0];
+        loop {
+            self.0.read_exact(&mut byte).unwrap();
+            if byte[0] == b'$' {
+                break;
+            }
+        }
+
+        let mut body = Vec::new();
+        loop {
+            self.0.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {
+                break;
+            }
+            body.push(byte[0]);
+        }
+
+        let mut checksum = [0; 2];
+        self.0.read_exact(&mut checksum).unwrap();
+        let checksum = u8::from_str_radix(std::str::from_utf8(&checksum).unwrap(), 16).unwrap();
+        assert_eq!(
+            checksum,
+            body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+        );
+
+        self.0.write_all(b"+").unwrap();
+        String::from_utf8(body).unwrap()
+    }
+
+    fn send(&mut self, body: &str) {
+        let checksum = body.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(self.0, "${}#{:02x}", body, checksum).unwrap();
+
+        let mut ack = [0];
+        self.0.read_exact(&mut ack).unwrap();
+        assert_eq!(ack[0], b'+');
+    }
+
+    fn request(&mut self, body: &str) -> String {
+        self.send(body);
+        self.read_packet()
+    }
+}
+
+#[test]
+fn reads_registers_and_memory() {
+    let (mut client, server) = Client::connect();
+
+    assert_eq!(client.request("?"), "S05");
+    assert_eq!(
+        client.request("g"),
+        format!("{}{}{}{}", "00".repeat(16), "0000", "0200", "0000")
+    );
+    assert_eq!(client.request("p11"), "0200");
+    assert_eq!(client.request("p14"), "E01");
+    assert_eq!(client.request("m200,4"), "6005610a");
+
+    assert!(client
+        .request("qXfer:features:read:target.xml:0,1000")
+        .starts_with("l<?xml"));
+
+    client.send("k");
+    server.join().unwrap();
+}
+
+#[test]
+fn clamps_oversized_reads() {
+    let (mut client, server) = Client::connect();
+
+    assert_eq!(client.request("mffe,ffffffffffffffff"), "0000");
+    assert_eq!(client.request("m1000,1"), "E01");
+    assert!(client
+        .request("qXfer:features:read:target.xml:1,ffffffffffffffff")
+        .starts_with("l?xml"));
+
+    client.send("k");
+    server.join().unwrap();
+}
+
+#[test]
+fn writes_registers_and_memory() {
+    let (mut client, server) = Client::connect();
+
+    assert_eq!(client.request("P3=7f"), "OK");
+    assert_eq!(client.request("P10=0300"), "OK");
+    assert_eq!(client.request("p3"), "7f");
+    assert_eq!(client.request("p10"), "0300");
+
+    assert_eq!(client.request("M300,2:abcd"), "OK");
+    assert_eq!(client.request("m300,2"), "abcd");
+    assert_eq!(client.request("Mfff,2:abcd"), "E01");
+    assert_eq!(client.request("M300,ffffffffffffffff:abcd"), "E01");
+
+    client.send("k");
+    server.join().unwrap();
+}
+
+#[test]
+fn steps_and_stops_at_breakpoints() {
+    let (mut client, server) = Client::connect();
+
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "0202");
+    assert_eq!(client.request("p0"), "05");
+
+    assert_eq!(client.request("Z0,206,2"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p11"), "0206");
+    assert_eq!(client.request("p0"), "06");
+
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p0"), "07");
+
+    assert_eq!(client.request("z0,206,2"), "OK");
+    client.send("c");
+    client.0.write_all(&[0x03]).unwrap();
+    assert_eq!(client.read_packet(), "S02");
+
+    assert_eq!(client.request("D"), "OK");
+    server.join().unwrap();
+}
@@ -0,0 +1,33 @@
+use chip8::input::{press, read_masks};
+use chip8::types::Button;
+use enum_map::EnumMap;
+
+#[test]
+fn reads_a_mask_per_frame() {
+    assert_eq!(
+        read_masks(&[0x00, 0x00, 0x80, 0x21, 0xFF, 0xFF]),
+        Ok(vec![0, 0x8021, 0xFFFF])
+    );
+    assert_eq!(read_masks(&[]), Ok(vec![]));
+    assert_eq!(
+        read_masks(&[0x00, 0x01, 0x02]),
+        Err("input ends partway through a frame".into())
+    );
+}
+
+#[test]
+fn presses_the_masked_buttons() {
+    let mut buttons = EnumMap::new();
+    buttons[Button::B4] = true;
+
+    press(0x8021, &mut buttons);
+    let pressed: Vec<_> = buttons
+        .iter()
+        .filter(|(_, &down)| down)
+        .map(|(button, _)| button)
+        .collect();
+    assert_eq!(pressed, [Button::B0, Button::B5, Button::BF]);
+
+    press(0, &mut buttons);
+    assert!(buttons.values().all(|&down| !down));
+}
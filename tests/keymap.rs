@@ -0,0 +1,56 @@
+use chip8::keymap::{parse_bindings, KeyMap, Layout};
+use chip8::types::Button;
+use enum_map::EnumMap;
+use minifb::Key;
+
+#[test]
+fn layouts_cover_the_keypad() {
+    let qwerty = KeyMap::default();
+    assert_eq!(qwerty.button(Key::Key4), Some(Button::BC));
+    assert_eq!(qwerty.button(Key::X), Some(Button::B0));
+    assert_eq!(qwerty.button(Key::Y), None);
+
+    let azerty = KeyMap::layout(Layout::Azerty);
+    assert_eq!(azerty.button(Key::A), Some(Button::B4));
+    assert_eq!(azerty.button(Key::Q), Some(Button::B7));
+
+    let dvorak = KeyMap::layout(Layout::Dvorak);
+    assert_eq!(dvorak.button(Key::Comma), Some(Button::B5));
+    assert_eq!(dvorak.keys(Button::BF), [Key::K]);
+
+    assert_eq!("Dvorak".parse(), Ok(Layout::Dvorak));
+    assert!("colemak".parse::<Layout>().is_err());
+}
+
+#[test]
+fn bindings_add_keys_to_buttons() {
+    let mut map = KeyMap::layout(Layout::Azerty);
+    for (key, button) in parse_bindings("W=5, a=7,S=8,D=9").unwrap() {
+        map.bind(key, button);
+    }
+
+    // W and A moved from their layout buttons, and Z still presses 5.
+    assert_eq!(map.keys(Button::B5), [Key::Z, Key::W]);
+    assert_eq!(map.keys(Button::BA), []);
+    assert_eq!(map.keys(Button::B4), []);
+
+    let mut buttons = EnumMap::new();
+    buttons[Button::B4] = true;
+    map.update(|key| key == Key::W || key == Key::D, &mut buttons);
+    let pressed: Vec<_> = buttons.iter().filter(|(_, &down)| down).collect();
+    assert_eq!(pressed, [(Button::B5, &true), (Button::B9, &true)]);
+
+    assert_eq!(
+        parse_bindings("Up=12,Down=0xD,Left=0XF").unwrap(),
+        [
+            (Key::Up, Button::BC),
+            (Key::Down, Button::BD),
+            (Key::Left, Button::BF)
+        ]
+    );
+    assert!(parse_bindings("W=16").is_err());
+    assert!(parse_bindings("W=0x10").is_err());
+    assert!(parse_bindings("W=C").is_err());
+    assert!(parse_bindings("Hyper=1").is_err());
+    assert!(parse_bindings("W").is_err());
+}
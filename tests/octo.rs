@@ -0,0 +1,108 @@
+use chip8::decompile::decompile;
+use chip8::octo::assemble;
+use chip8::symbols::Symbols;
+use chip8::types::Address;
+use proptest::prelude::*;
+
+fn roundtrip(rom: &[u8]) -> Vec<u8> {
+    let mut memory = [0; 4096];
+    memory[0x200..0x200 + rom.len()].copy_from_slice(rom);
+
+    let mut source = Vec::new();
+    decompile(
+        &memory,
+        0x200..0x200 + rom.len(),
+        &Symbols::new(),
+        &mut source,
+    )
+    .unwrap();
+
+    let source = String::from_utf8(source).unwrap();
+    assemble(&source, "rom.8o").unwrap().bytes
+}
+
+#[test]
+fn decompiled_roms_reassemble() {
+    for path in &["pong.rom", "breakout.rom", "test_opcode.ch8", "test2.ch8"] {
+        let rom = std::fs::read(path).unwrap();
+        assert!(roundtrip(&rom) == rom, "{} changed", path);
+    }
+}
+
+#[test]
+fn assembles_structures_and_directives() {
+    let source = "
+        :alias x v3
+        :const SPEED 2
+        :calc DOUBLE { SPEED * 2 + 1 }
+        :macro bump reg amount { reg += amount }
+
+        : data 0x3C 0b01000010
+
+        : main
+            x := SPEED
+            bump x DOUBLE
+            loop
+                while x != 10
+                x += 1
+                if x == 5 then x := 7
+                if x key begin
+                    clear
+                else
+                    :next target
+                    v0 := 0
+                    i := data
+                end
+            again
+            draw
+            v1 -= 1
+        : draw
+            sprite x x 4
+            ;
+    ";
+
+    let program = assemble(source, "test.8o").unwrap();
+
+    let words: Vec<u16> = program
+        .bytes
+        .chunks(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    assert_eq!(
+        words,
+        vec![
+            0x1204, 0x3C42, 0x6302, 0x7306, 0x430A, 0x1220, 0x7301, 0x4305, 0x6307, 0xE39E, 0x121A,
+            0x00E0, 0x121E, 0x6000, 0xA202, 0x1208, 0x2224, 0x71FF, 0xD334, 0x00EE,
+        ]
+    );
+
+    assert_eq!(program.symbols.get("target"), Some(Address(0x21B)));
+    assert_eq!(program.symbols.get("draw"), Some(Address(0x224)));
+    assert_eq!(
+        program.lines.address("test.8o".as_ref(), 13),
+        Some((Address(0x208), 13))
+    );
+}
+
+#[test]
+fn reports_errors_with_lines() {
+    assert_eq!(
+        assemble(": main\n v0 := 300", "bad.8o").unwrap_err(),
+        "line 2: 300 doesn't fit in a byte"
+    );
+    assert_eq!(
+        assemble(": main\n jump nowhere", "bad.8o").unwrap_err(),
+        "line 2: undefined label \"nowhere\""
+    );
+    assert_eq!(
+        assemble(": main\n loop", "bad.8o").unwrap_err(),
+        "line 2: loop without again"
+    );
+}
+
+proptest! {
+    #[test]
+    fn random_roms_reassemble(rom in proptest::collection::vec(any::<u8>(), 1..64)) {
+        prop_assert_eq!(roundtrip(&rom), rom);
+    }
+}
@@ -0,0 +1,30 @@
+use chip8::config::Settings;
+use chip8::palette::{Palette, Theme};
+
+#[test]
+fn renders_planes_through_the_palette() {
+    let palette = Palette([0, 1, 2, 3]);
+    let mut pix_gfx = [0xFF; 8];
+
+    palette.render(&[&[0b1100_0000]], &mut pix_gfx);
+    assert_eq!(pix_gfx, [1, 1, 0, 0, 0, 0, 0, 0]);
+
+    palette.render(&[&[0b1010_0000], &[0b0110_0000]], &mut pix_gfx);
+    assert_eq!(pix_gfx, [1, 2, 3, 0, 0, 0, 0, 0]);
+}
+
+#[test]
+fn colors_replace_the_theme() {
+    assert_eq!(Settings::default().palette(), Palette::default());
+    assert_eq!("LCD".parse(), Ok(Theme::Lcd));
+
+    let settings = Settings {
+        theme: Some(Theme::Octo),
+        foreground: Some(0x123456),
+        ..Settings::default()
+    };
+    assert_eq!(
+        settings.palette(),
+        Palette([0x996600, 0x123456, 0xFF6600, 0x662200])
+    );
+}
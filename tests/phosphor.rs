@@ -0,0 +1,40 @@
+use chip8::palette::Palette;
+use chip8::phosphor::{Filter, Phosphor};
+
+const PALETTE: Palette = Palette([0x000000, 0xFFFFFF, 0xAAAAAA, 0x555555]);
+
+/// Draws a frame with the first pixel lit, then erases it.
+fn flicker(filter: Filter) -> (u32, u32) {
+    let mut phosphor = Phosphor::new(filter);
+    let mut bit_gfx = [0; 256];
+    let mut pix_gfx = [0; 2048];
+
+    bit_gfx[0] = 0x80;
+    phosphor.end_frame(&bit_gfx);
+    bit_gfx[0] = 0;
+
+    phosphor.render(&PALETTE, &bit_gfx, &mut pix_gfx);
+    let erased = pix_gfx[0];
+
+    phosphor.end_frame(&bit_gfx);
+    phosphor.render(&PALETTE, &bit_gfx, &mut pix_gfx);
+
+    (erased, pix_gfx[0])
+}
+
+#[test]
+fn filters_keep_erased_pixels_lit() {
+    assert_eq!(flicker(Filter::None), (0x000000, 0x000000));
+    assert_eq!(flicker(Filter::Persist), (0xFFFFFF, 0x000000));
+    assert_eq!(flicker(Filter::Decay(0.5)), (0xFFFFFF, 0x808080));
+}
+
+#[test]
+fn parses_filters() {
+    assert_eq!("persist".parse(), Ok(Filter::Persist));
+    assert_eq!("decay".parse(), Ok(Filter::Decay(0.6)));
+    assert_eq!("decay:0.25".parse(), Ok(Filter::Decay(0.25)));
+    assert_eq!(Filter::Decay(0.25).to_string(), "decay:0.25");
+    assert!("decay:1.5".parse::<Filter>().is_err());
+    assert!("blur".parse::<Filter>().is_err());
+}
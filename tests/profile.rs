@@ -0,0 +1,80 @@
+use chip8::profile::{Profiler, Subroutine};
+use chip8::symbols::Symbols;
+use chip8::types::{Address, State};
+
+const ROM: [u8; 16] = [
+    0x22, 0x08, // CALL 0x208
+    0x22, 0x08, // CALL 0x208
+    0x12, 0x04, // JP 0x204
+    0x00, 0x00, //
+    0x22, 0x0C, // CALL 0x20C
+    0x00, 0xEE, // RET
+    0x60, 0x01, // LD V0, 0x01
+    0x00, 0xEE, // RET
+];
+
+fn profile(steps: usize) -> (Profiler, State) {
+    let mut state = State::default();
+    state.memory[0x200..0x200 + ROM.len()].copy_from_slice(&ROM);
+    let mut profiler = Profiler::new();
+
+    for _ in 0..steps {
+        let pc = state.pc;
+        let instr = state.fetch().unwrap();
+        instr.eval(&mut state).unwrap();
+        profiler.record(pc, &instr, &state);
+    }
+
+    (profiler, state)
+}
+
+#[test]
+fn counts_hot_spots_and_subroutines() {
+    let (profiler, _) = profile(12);
+
+    assert_eq!(profiler.total, 12);
+    assert_eq!(profiler.pcs[&Address(0x204)], 2);
+    assert_eq!(profiler.pcs[&Address(0x20C)], 2);
+    assert_eq!(profiler.pcs.values().sum::<u64>(), 12);
+
+    let subroutine = |target: Option<u16>| profiler.subroutines[&target.map(Address)];
+    assert_eq!(
+        subroutine(None),
+        Subroutine {
+            calls: 0,
+            exclusive: 4,
+            inclusive: 12
+        }
+    );
+    assert_eq!(
+        subroutine(Some(0x208)),
+        Subroutine {
+            calls: 2,
+            exclusive: 4,
+            inclusive: 8
+        }
+    );
+    assert_eq!(
+        subroutine(Some(0x20C)),
+        Subroutine {
+            calls: 2,
+            exclusive: 4,
+            inclusive: 4
+        }
+    );
+}
+
+#[test]
+fn writes_folded_stacks() {
+    let (profiler, _) = profile(12);
+
+    let mut symbols = Symbols::new();
+    symbols.insert("draw", Address(0x20C));
+
+    let mut folded = Vec::new();
+    profiler.write_folded(&symbols, &mut folded).unwrap();
+    assert_eq!(
+        String::from_utf8(folded).unwrap(),
+        "main 4\nmain;sub_208 4\nmain;sub_208;draw 4\n"
+    );
+}
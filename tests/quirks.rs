@@ -0,0 +1,15 @@
+use chip8::types::Quirks;
+
+#[test]
+fn parses_quirk_lists() {
+    let schip: Quirks = "schip".parse().unwrap();
+    assert!(schip.shift && schip.jump && !schip.logic);
+
+    let quirks: Quirks = "chip8,shift,no-clip".parse().unwrap();
+    assert!(quirks.shift && !quirks.clip && quirks.logic);
+
+    assert_eq!(
+        "logic,bogus".parse::<Quirks>(),
+        Err("unknown quirk \"bogus\"".into())
+    );
+}
@@ -0,0 +1,87 @@
+use chip8::palette::Palette;
+use chip8::record::{Format, Recorder};
+use std::io::{self, Write};
+
+#[test]
+fn gif_holds_unchanged_frames() {
+    let palette = Palette([0x112233, 0xFFCC00, 0, 0]);
+    let (blank, mut lit) = ([0; 256], [0; 256]);
+    lit[0] = 0x80;
+
+    let mut gif = Vec::new();
+    let mut recorder = Recorder::new(&mut gif, Format::Gif, &palette, 2).unwrap();
+    for bit_gfx in &[blank, blank, blank, lit, lit, blank] {
+        recorder.frame(&[bit_gfx]).unwrap();
+    }
+    recorder.finish().unwrap();
+
+    let mut reader = gif::Decoder::new(&gif[..]).read_info().unwrap();
+    assert_eq!((reader.width(), reader.height()), (128, 64));
+
+    let mut frames = Vec::new();
+    while let Some(frame) = reader.read_next_frame().unwrap() {
+        frames.push((frame.buffer[0], frame.delay));
+    }
+
+    // Six ticks last ten hundredths of a second.
+    assert_eq!(frames, [(0, 5), (1, 3), (0, 2)]);
+}
+
+#[test]
+fn y4m_writes_every_frame() {
+    let palette = Palette([0x000000, 0xFFFFFF, 0, 0]);
+    let mut bit_gfx = [0; 256];
+    bit_gfx[0] = 0x80;
+
+    let mut y4m = Vec::new();
+    let mut recorder = Recorder::new(&mut y4m, Format::Y4m, &palette, 1).unwrap();
+    recorder.frame(&[&bit_gfx]).unwrap();
+    recorder.frame(&[&bit_gfx]).unwrap();
+    recorder.finish().unwrap();
+
+    let header = b"YUV4MPEG2 W64 H32 F60:1 Ip A1:1 C444\n";
+    assert!(y4m.starts_with(header));
+
+    let frame_size = 6 + 64 * 32 * 3;
+    assert_eq!(y4m.len(), header.len() + frame_size * 2);
+
+    let frame = &y4m[header.len()..][..frame_size];
+    assert!(frame.starts_with(b"FRAME\n"));
+    let luma = &frame[6..][..64 * 32];
+    assert_eq!((luma[0], luma[1]), (235, 16));
+}
+
+#[test]
+fn picks_format_by_extension() {
+    assert_eq!(Format::from_path("pong.GIF"), Ok(Format::Gif));
+    assert_eq!(Format::from_path("clips/pong.y4m"), Ok(Format::Y4m));
+    assert!(Format::from_path("pong.mp4").is_err());
+    assert!(Format::from_path("pong").is_err());
+}
+
+/// Takes everything but fails to flush, like a full disk behind a buffer.
+struct Unflushable(Vec<u8>);
+
+impl Write for Unflushable {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    // io::Error::other is too new for the rest of the crate's idioms.
+    #[allow(clippy::io_other_error)]
+    fn flush(&mut self) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Other, "disk full"))
+    }
+}
+
+#[test]
+fn finishing_reports_write_errors() {
+    for &format in &[Format::Gif, Format::Y4m] {
+        let mut recorder =
+            Recorder::new(Unflushable(Vec::new()), format, &Palette::default(), 1).unwrap();
+        recorder.frame(&[&[0; 256]]).unwrap();
+        assert_eq!(recorder.finish(), Err("disk full".into()), "{:?}", format);
+    }
+
+    assert!(Recorder::new(Vec::new(), Format::Gif, &Palette::default(), 65).is_err());
+}
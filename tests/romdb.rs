@@ -0,0 +1,68 @@
+use chip8::romdb::{self, RomDb};
+use chip8::types::{Button, Platform};
+
+#[test]
+fn recognizes_bundled_roms() {
+    let pong = std::fs::read("pong.rom").unwrap();
+    assert_eq!(
+        romdb::hash(&pong),
+        "1830eb401ba8789a477dfcf294873a5479ebcfe8"
+    );
+
+    let entry = RomDb::bundled().get(&pong).unwrap();
+    assert_eq!(entry.title.as_deref(), Some("Pong"));
+    assert_eq!(entry.platform, Platform::Chip8);
+    assert_eq!(entry.quirks, Platform::Chip8.quirks());
+    assert!(entry.keys.contains(&(minifb::Key::W, Button::B1)));
+
+    assert!(RomDb::bundled().get(b"not a rom").is_none());
+}
+
+#[test]
+fn user_entries_override_fields() {
+    let pong = std::fs::read("pong.rom").unwrap();
+
+    let mut db = RomDb::bundled();
+    db.merge(
+        r##"{
+            "1830EB401BA8789A477DFCF294873A5479EBCFE8": {
+                "platform": "schip",
+                "quirks": { "clip": false },
+                "tickrate": 30,
+                "foreground": "#00FF00"
+            }
+        }"##
+        .parse()
+        .unwrap(),
+    );
+
+    let entry = db.get(&pong).unwrap();
+    assert_eq!(entry.title.as_deref(), Some("Pong"));
+    assert_eq!(entry.platform, Platform::SuperChip);
+    assert!(entry.quirks.shift && !entry.quirks.clip);
+    assert_eq!(entry.tickrate, Some(30));
+    assert_eq!(entry.foreground, Some(0x00FF00));
+
+    for (json, err) in &[
+        ("[]", "ROM database isn't an object"),
+        (
+            r#"{"ab": {"platform": "vip"}}"#,
+            "ab: unknown platform \"vip\"",
+        ),
+        (
+            r#"{"ab": {"quirks": {"wrap": true}}}"#,
+            "ab: unknown quirk \"wrap\"",
+        ),
+        (r#"{"ab": {"keys": {"Q": 16}}}"#, "ab: invalid button 16"),
+        (
+            r#"{"ab": {"tickrate": 0}}"#,
+            "ab: tickrate must be at least 1",
+        ),
+        (
+            r#"{"ab": {"tickrate": 4294967296}}"#,
+            "ab: invalid tickrate 4294967296",
+        ),
+    ] {
+        assert_eq!(json.parse::<RomDb>().unwrap_err(), *err);
+    }
+}
@@ -0,0 +1,31 @@
+use chip8::palette::Palette;
+use chip8::screenshot::write_png;
+
+#[test]
+fn writes_scaled_indexed_png() {
+    let palette = Palette([0x112233, 0xFFCC00, 0, 0]);
+    let mut bit_gfx = [0; 256];
+    bit_gfx[0] = 0x40;
+
+    let mut png = Vec::new();
+    write_png(&mut png, &palette, &[&bit_gfx], 3).unwrap();
+
+    let mut decoder = png::Decoder::new(&png[..]);
+    decoder.set_transformations(png::Transformations::IDENTITY);
+    let (info, mut reader) = decoder.read_info().unwrap();
+    assert_eq!((info.width, info.height), (192, 96));
+    assert_eq!(
+        reader.info().palette.as_deref(),
+        Some(&[0x11, 0x22, 0x33, 0xFF, 0xCC, 0x00, 0, 0, 0, 0, 0, 0][..])
+    );
+
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels).unwrap();
+    for row in pixels.chunks(192).take(3) {
+        assert_eq!(row[..7], [0, 0, 0, 1, 1, 1, 0]);
+    }
+    assert_eq!(pixels[192 * 3 + 3], 0);
+
+    assert!(write_png(&mut Vec::new(), &palette, &[&bit_gfx], 0).is_err());
+    assert!(write_png(&mut Vec::new(), &palette, &[&bit_gfx], 65).is_err());
+}
@@ -0,0 +1,141 @@
+use chip8::symbols::{Symbolic, Symbols};
+use chip8::types::Address;
+
+const TEXT: &str = "\
+# Pong
+main = 0x200
+
+draw_paddle = 0x2A4
+score = 752
+";
+
+#[test]
+fn parses_text() {
+    let symbols: Symbols = TEXT.parse().unwrap();
+
+    assert_eq!(symbols.get("main"), Some(Address(0x200)));
+    assert_eq!(symbols.get("draw_paddle"), Some(Address(0x2A4)));
+    assert_eq!(symbols.get("score"), Some(Address(0x2F0)));
+    assert_eq!(symbols.get("lives"), None);
+    assert_eq!(
+        symbols.iter().collect::<Vec<_>>(),
+        [
+            ("main", Address(0x200)),
+            ("draw_paddle", Address(0x2A4)),
+            ("score", Address(0x2F0))
+        ]
+    );
+}
+
+#[test]
+fn parses_json() {
+    let symbols: Symbols = r#"{ "main": "0x200", "draw_paddle": 676, "score": "752" }"#
+        .parse()
+        .unwrap();
+    assert_eq!(symbols, TEXT.parse().unwrap());
+
+    let roundtrip: Symbols = symbols.to_json().parse().unwrap();
+    assert_eq!(roundtrip, symbols);
+}
+
+#[test]
+fn rejects_bad_files() {
+    let error = |s: &str| s.parse::<Symbols>().unwrap_err();
+
+    assert_eq!(
+        error("main 0x200"),
+        "invalid symbol on line 1: \"main 0x200\""
+    );
+    assert_eq!(
+        error("\nmain = 0x2G0"),
+        "invalid symbol on line 2: \"main = 0x2G0\""
+    );
+    assert_eq!(error("= 0x200"), "invalid symbol on line 1: \"= 0x200\"");
+    assert_eq!(
+        error("main = 0x10000"),
+        "invalid symbol on line 1: \"main = 0x10000\""
+    );
+    assert_eq!(error(r#"{ "main": true }"#), "invalid address for \"main\"");
+    assert_eq!(
+        error(r#"{ "main": 65536 }"#),
+        "invalid address for \"main\""
+    );
+    assert!("{ \"main\": ".parse::<Symbols>().is_err());
+    assert_eq!(
+        "[]".parse::<Symbols>(),
+        Err("invalid symbol on line 1: \"[]\"".to_string())
+    );
+}
+
+#[test]
+fn locates_nearest_symbol() {
+    let symbols: Symbols = TEXT.parse().unwrap();
+
+    assert_eq!(symbols.locate(Address(0x1FF)), None);
+    assert_eq!(symbols.locate(Address(0x200)), Some(("main", 0)));
+    assert_eq!(symbols.locate(Address(0x2A3)), Some(("main", 0xA3)));
+    assert_eq!(symbols.locate(Address(0x2A8)), Some(("draw_paddle", 4)));
+    assert_eq!(symbols.locate(Address(0xFFF)), Some(("score", 0xD0F)));
+
+    let show = |addr| Symbolic(&Address(addr), &symbols).to_string();
+    assert_eq!(show(0x2A4), "draw_paddle");
+    assert_eq!(show(0x2A8), "draw_paddle+0x4");
+}
+
+#[test]
+fn shows_the_first_name_for_an_address() {
+    let mut symbols = Symbols::new();
+    symbols.insert("loop", Address(0x200));
+    symbols.insert("main", Address(0x200));
+
+    assert_eq!(symbols.locate(Address(0x200)), Some(("loop", 0)));
+    assert_eq!(symbols.get("main"), Some(Address(0x200)));
+}
+
+#[test]
+fn parses_addresses() {
+    let symbols: Symbols = TEXT.parse().unwrap();
+    let parse = |s| symbols.parse_address(s);
+
+    assert_eq!(parse("draw_paddle"), Ok(Address(0x2A4)));
+    assert_eq!(parse(" draw_paddle + 4 "), Ok(Address(0x2A8)));
+    assert_eq!(parse("score+0x10"), Ok(Address(0x300)));
+    assert_eq!(parse("2A4"), Ok(Address(0x2A4)));
+    assert_eq!(parse("0x2A4+A"), Ok(Address(0x2AE)));
+
+    assert_eq!(parse("lives"), Err("unknown address \"lives\"".to_string()));
+    assert_eq!(parse("main+x"), Err("invalid offset \"x\"".to_string()));
+    assert_eq!(
+        parse("1000"),
+        Err("\"1000\" is past the end of memory".to_string())
+    );
+    assert!(parse("score+D10").is_err());
+    assert_eq!(parse("score+D0F"), Ok(Address(0xFFF)));
+}
+
+#[test]
+fn parses_ranges() {
+    let mut symbols: Symbols = TEXT.parse().unwrap();
+    symbols.insert("game-over", Address(0x3A0));
+    let parse = |s| symbols.parse_range(s);
+
+    assert_eq!(parse("2A4"), Ok(Address(0x2A4)..=Address(0x2A4)));
+    assert_eq!(
+        parse("main-draw_paddle+4"),
+        Ok(Address(0x200)..=Address(0x2A8))
+    );
+    assert_eq!(parse("game-over"), Ok(Address(0x3A0)..=Address(0x3A0)));
+    assert_eq!(
+        parse("score-game-over"),
+        Ok(Address(0x2F0)..=Address(0x3A0))
+    );
+    assert_eq!(parse("200-FFF"), Ok(Address(0x200)..=Address(0xFFF)));
+
+    assert_eq!(
+        parse("200-1000"),
+        Err("\"1000\" is past the end of memory".to_string())
+    );
+    assert!(parse("10000-1FFFF").is_err());
+    assert_eq!(parse("lives"), Err("unknown address \"lives\"".to_string()));
+    assert!(parse("main-lives").is_err());
+}
@@ -0,0 +1,130 @@
+use chip8::trace::{self, Change, Entry, Filter, Format, Tracer};
+use chip8::types::{Address, Register, State};
+use std::io::Write;
+
+const ROM: [u8; 10] = [
+    0x60, 0x05, // LD V0, 0x05
+    0xA3, 0x00, // LD I, 0x300
+    0xF0, 0x55, // LD [I], V0
+    0x70, 0x01, // ADD V0, 0x01
+    0xD0, 0x01, // DRW V0, V0, 1
+];
+
+/// Runs `ROM` through `tracer`, ticking the timers after the third
+/// instruction.
+fn trace<W: Write>(tracer: &mut Tracer<W>) {
+    let mut state = State::default();
+    state.memory[0x200..0x200 + ROM.len()].copy_from_slice(&ROM);
+
+    for step in 0..ROM.len() / 2 {
+        let before = state.clone();
+        state.fetch().unwrap().eval(&mut state).unwrap();
+        tracer.record(&before, &state).unwrap();
+
+        if step == 2 {
+            state.tick_timers();
+        }
+    }
+}
+
+#[test]
+fn entries_list_what_changed() {
+    let mut before = State::default();
+    before.memory[0x200..0x202].copy_from_slice(&[0xF1, 0x55]);
+    before.registers[Register::V1] = 7;
+    before.i_reg = Address(0x300);
+
+    let mut after = before.clone();
+    after.fetch().unwrap().eval(&mut after).unwrap();
+
+    let entry = Entry::new(&before, &after);
+    assert_eq!(
+        (entry.frame, entry.pc, entry.opcode),
+        (0, Address(0x200), 0xF155)
+    );
+    assert_eq!(entry.changes[0], Change::Memory(Address(0x301), 0, 7));
+    assert!(entry
+        .changes
+        .iter()
+        .all(|change| !matches!(change, Change::Register(..))));
+}
+
+#[test]
+fn writes_text() {
+    let mut out = Vec::new();
+    trace(&mut Tracer::new(&mut out, Format::Text).unwrap());
+
+    let gfx = trace::gfx_hash(&[0; 256]);
+    let mut drawn = [0; 256];
+    // The sprite 0x05 at (6, 6) lights (11, 6) and (13, 6).
+    drawn[6 * 8 + 1] = 0x14;
+    let expected = format!(
+        "       0 200 6005 LD V0, 0x05        V0:00->05
+       0 202 A300 LD I, 0x300        I:000->300
+       0 204 F055 LD [I], V0         [300]:00->05
+       1 206 7001 ADD V0, 0x01       V0:05->06
+       1 208 D001 DRW V0, V0, 1      gfx:{:08X}->{:08X}
+",
+        gfx,
+        trace::gfx_hash(&drawn)
+    );
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+}
+
+#[test]
+fn writes_binary() {
+    let mut out = Vec::new();
+    let mut tracer = Tracer::new(&mut out, Format::Binary).unwrap();
+    tracer.filter.classes = Some(vec![0x6, 0xA]);
+    trace(&mut tracer);
+
+    let mut expected = trace::MAGIC.to_vec();
+    expected.push(trace::VERSION);
+    // Frame, PC and opcode, then each change.
+    expected.extend(&[
+        0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x02, 0x05, 0x60, 1, 0x00, 0x00, 0x05,
+    ]);
+    expected.extend(&[
+        0, 0, 0, 0, 0, 0, 0, 0, 0x02, 0x02, 0x00, 0xA3, 1, 0x10, 0, 0, 0x00, 0x03,
+    ]);
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn filters_by_pc_class_and_frame() {
+    let lines = |filter: Filter| {
+        let mut out = Vec::new();
+        let mut tracer = Tracer::new(&mut out, Format::Text).unwrap();
+        tracer.filter = filter;
+        trace(&mut tracer);
+
+        String::from_utf8(out)
+            .unwrap()
+            .lines()
+            .map(|line| line.split_whitespace().nth(2).unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        lines(Filter {
+            pc: Some(0x202..=0x206),
+            ..Filter::default()
+        }),
+        ["A300", "F055", "7001"]
+    );
+    assert_eq!(
+        lines(Filter {
+            classes: Some(vec![0x7, 0xD]),
+            ..Filter::default()
+        }),
+        ["7001", "D001"]
+    );
+    assert_eq!(
+        lines(Filter {
+            pc: Some(0x200..=0x206),
+            frames: Some(1..=1),
+            ..Filter::default()
+        }),
+        ["7001"]
+    );
+}
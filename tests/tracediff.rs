@@ -0,0 +1,113 @@
+use chip8::trace::{Entry, Format, Reader, Tracer};
+use chip8::tracediff;
+use chip8::types::State;
+use std::io;
+
+const ROM: [u8; 12] = [
+    0x60, 0x05, // LD V0, 0x05
+    0xA3, 0x00, // LD I, 0x300
+    0xF0, 0x55, // LD [I], V0
+    0x70, 0x01, // ADD V0, 0x01
+    0xD0, 0x01, // DRW V0, V0, 1
+    0x70, 0x01, // ADD V0, 0x01
+];
+
+/// Runs the first `steps` instructions of `rom`, returning the trace in
+/// `format` and the entries it should hold.
+fn record(rom: &[u8], steps: usize, format: Format) -> (Vec<u8>, Vec<Entry>) {
+    let mut state = State::default();
+    state.memory[0x200..0x200 + rom.len()].copy_from_slice(rom);
+
+    let mut out = Vec::new();
+    let mut tracer = Tracer::new(&mut out, format).unwrap();
+    let mut entries = Vec::new();
+
+    for step in 0..steps {
+        let before = state.clone();
+        state.fetch().unwrap().eval(&mut state).unwrap();
+        tracer.record(&before, &state).unwrap();
+        entries.push(Entry::new(&before, &state));
+
+        if step == 2 {
+            state.tick_timers();
+        }
+    }
+
+    (out, entries)
+}
+
+fn read(trace: &[u8]) -> impl Iterator<Item = io::Result<Entry>> + '_ {
+    Reader::new(trace).unwrap()
+}
+
+#[test]
+fn reads_back_both_formats() {
+    for &format in &[Format::Text, Format::Binary] {
+        let (trace, entries) = record(&ROM, 6, format);
+        let read: Vec<Entry> = read(&trace).collect::<io::Result<_>>().unwrap();
+        assert_eq!(read, entries, "{:?}", format);
+    }
+}
+
+#[test]
+fn keeps_frames_past_u32() {
+    let mut state = State::default();
+    state.memory[0x200..0x200 + ROM.len()].copy_from_slice(&ROM);
+    state.frame = 1 << 32 | 7;
+
+    let before = state.clone();
+    state.fetch().unwrap().eval(&mut state).unwrap();
+
+    for &format in &[Format::Text, Format::Binary] {
+        let mut trace = Vec::new();
+        let mut tracer = Tracer::new(&mut trace, format).unwrap();
+        tracer.record(&before, &state).unwrap();
+        drop(tracer);
+
+        let read: Vec<Entry> = read(&trace).collect::<io::Result<_>>().unwrap();
+        assert_eq!(read, [Entry::new(&before, &state)], "{:?}", format);
+        assert_eq!(read[0].frame, 1 << 32 | 7);
+    }
+}
+
+#[test]
+fn identical_traces_agree() {
+    let (a, _) = record(&ROM, 6, Format::Text);
+    let (b, _) = record(&ROM, 6, Format::Binary);
+    assert!(tracediff::diff(read(&a), read(&b), 2).unwrap().is_none());
+}
+
+#[test]
+fn finds_divergence_with_context() {
+    let mut changed = ROM;
+    changed[7] = 0x02;
+
+    let (a, entries) = record(&ROM, 6, Format::Text);
+    let (b, changed_entries) = record(&changed, 6, Format::Binary);
+    let divergence = tracediff::diff(read(&a), read(&b), 2).unwrap().unwrap();
+
+    assert_eq!(divergence.step, 3);
+    assert_eq!(
+        divergence.differences,
+        ["opcode: 7001 != 7002", "V0: 0x06 != 0x07"]
+    );
+    assert_eq!(divergence.before, entries[1..3]);
+    assert_eq!(divergence.a.as_ref(), Some(&entries[3]));
+    assert_eq!(divergence.b.as_ref(), Some(&changed_entries[3]));
+    assert_eq!(divergence.after_a, entries[4..6]);
+    assert_eq!(divergence.after_b, changed_entries[4..6]);
+}
+
+#[test]
+fn reports_the_shorter_trace_ending() {
+    let (a, entries) = record(&ROM, 6, Format::Text);
+    let (b, _) = record(&ROM, 4, Format::Text);
+    let divergence = tracediff::diff(read(&a), read(&b), 1).unwrap().unwrap();
+
+    assert_eq!(divergence.step, 4);
+    assert_eq!(divergence.differences, ["b ended"]);
+    assert_eq!(divergence.before, entries[3..4]);
+    assert_eq!(divergence.a.as_ref(), Some(&entries[4]));
+    assert_eq!(divergence.b, None);
+    assert!(divergence.after_b.is_empty());
+}